@@ -0,0 +1,128 @@
+//変換ごとの速さを、大きさと色の型を変えて測る 画像の読み込みは測らない
+//make bench で今のコミットの結果をbenches/resultsに書き出し、make bench-compare BASE=<コミット> で比べる
+use std::path::PathBuf;
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use image::{DynamicImage, ImageBuffer, Rgb, Rgba};
+
+use coffee_image::convert::image_wrap::{
+    ascii_art_lines, save_temp_image, threshold_image, ImageConverter,
+};
+use coffee_image::convert::operation::Operation;
+use coffee_image::convert::progress::Progress;
+use coffee_image::io::temp_session::remove_session_files;
+use coffee_image::string_art::ascii::Ramp;
+
+const SIZES: [(u32, u32); 3] = [(256, 256), (1024, 768), (1920, 1080)];
+const BLUR_SIGMAS: [f32; 3] = [1.0, 4.0, 16.0];
+
+//(名前, 画像) 色の型ごとに同じ模様を作る
+fn create_images(width: u32, height: u32) -> Vec<(&'static str, DynamicImage)> {
+    let value = move |x: u32, y: u32| ((x * 7 + y * 3) % 256) as f32 / 255.0;
+    let alpha = move |x: u32, _: u32| (x * 255 / width.max(1)) as f32 / 255.0;
+
+    let rgb8 = ImageBuffer::from_fn(width, height, |x, y| {
+        Rgb([(value(x, y) * 255.0) as u8, (y % 256) as u8, 128])
+    });
+    let rgba8 = ImageBuffer::from_fn(width, height, |x, y| {
+        Rgba([(value(x, y) * 255.0) as u8, (y % 256) as u8, 128, (alpha(x, y) * 255.0) as u8])
+    });
+    let rgb16 = ImageBuffer::from_fn(width, height, |x, y| {
+        Rgb([(value(x, y) * 65535.0) as u16, (y % 256) as u16 * 257, 32768])
+    });
+    let rgba32f = ImageBuffer::from_fn(width, height, |x, y| {
+        Rgba([value(x, y), (y % 256) as f32 / 255.0, 0.5, alpha(x, y)])
+    });
+
+    vec![
+        ("rgb8", DynamicImage::ImageRgb8(rgb8)),
+        ("rgba8", DynamicImage::ImageRgba8(rgba8)),
+        ("rgb16", DynamicImage::ImageRgb16(rgb16)),
+        ("rgba32f", DynamicImage::ImageRgba32F(rgba32f)),
+    ]
+}
+
+struct Fixture {
+    id: String,
+    image: DynamicImage,
+    path: PathBuf,
+    mask: DynamicImage,
+    pixels: u64,
+}
+
+fn fixtures() -> Vec<Fixture> {
+    SIZES
+        .iter()
+        .flat_map(|&(width, height)| {
+            create_images(width, height)
+                .into_iter()
+                .map(move |(color, image)| Fixture {
+                    id: format!("{}/{}x{}", color, width, height),
+                    path: save_temp_image(&image).unwrap(),
+                    mask: threshold_image(&image.grayscale()),
+                    pixels: width as u64 * height as u64,
+                    image,
+                })
+        })
+        .collect()
+}
+
+fn operations(fixture: &Fixture) -> Vec<(String, Operation)> {
+    let mut operations = vec![
+        ("gray".to_string(), Operation::Gray),
+        ("bitwise_not".to_string(), Operation::BitwiseNot),
+        ("hue_rotate".to_string(), Operation::HueRotate(90)),
+        ("rotate_45".to_string(), Operation::Rotate(45.0)),
+        ("add".to_string(), Operation::Add(fixture.path.clone())),
+    ];
+    for sigma in BLUR_SIGMAS {
+        operations.push((format!("blur_{}", sigma), Operation::Blur(sigma)));
+    }
+    operations
+}
+
+fn bench_operations(c: &mut Criterion) {
+    let fixtures = fixtures();
+
+    for fixture in &fixtures {
+        let mut ic = ImageConverter::new();
+
+        for (name, operation) in operations(fixture) {
+            //addの2枚目の画像も先に読んでおく
+            let operation = operation.preload().unwrap();
+            let mut group = c.benchmark_group(name);
+            group.throughput(Throughput::Elements(fixture.pixels));
+            group.bench_with_input(BenchmarkId::from_parameter(&fixture.id), &operation, |b, operation| {
+                b.iter(|| {
+                    operation
+                        .apply_with_progress(&fixture.image, &Progress::new())
+                        .unwrap()
+                })
+            });
+            group.finish();
+        }
+
+        let mut group = c.benchmark_group("bitwise_and");
+        group.throughput(Throughput::Elements(fixture.pixels));
+        group.bench_function(BenchmarkId::from_parameter(&fixture.id), |b| {
+            b.iter(|| ic.bitwise_and(&fixture.image, &fixture.mask))
+        });
+        group.finish();
+
+        let mut group = c.benchmark_group("ascii_art");
+        group.throughput(Throughput::Elements(fixture.pixels));
+        group.bench_function(BenchmarkId::from_parameter(&fixture.id), |b| {
+            b.iter(|| ascii_art_lines(&fixture.image, 1, Ramp::Standard))
+        });
+        group.finish();
+    }
+
+    remove_session_files();
+}
+
+criterion_group! {
+    name = benches;
+    config = Criterion::default().sample_size(10);
+    targets = bench_operations
+}
+criterion_main!(benches);
@@ -0,0 +1,65 @@
+//24MP(6000x4000)の画像で、1スレッドと全コアの速さを比べる
+//cargo bench --bench parallel
+use criterion::{criterion_group, criterion_main, Criterion};
+use image::{DynamicImage, Rgba, RgbaImage};
+use rayon::{ThreadPool, ThreadPoolBuilder};
+
+use coffee_image::convert::image_wrap::{
+    add_two_images, ascii_art_lines, crop_image, rotate_image, threshold_image, ImageConverter,
+};
+use coffee_image::string_art::ascii::Ramp;
+
+const WIDTH: u32 = 6000;
+const HEIGHT: u32 = 4000;
+
+fn create_image() -> DynamicImage {
+    DynamicImage::ImageRgba8(RgbaImage::from_fn(WIDTH, HEIGHT, |x, y| {
+        Rgba([(x % 256) as u8, (y % 256) as u8, ((x + y) % 256) as u8, 255])
+    }))
+}
+
+fn pools() -> Vec<(String, ThreadPool)> {
+    let mut threads = vec![1, rayon::current_num_threads()];
+    threads.dedup();
+    threads
+        .into_iter()
+        .map(|count| {
+            let pool = ThreadPoolBuilder::new().num_threads(count).build().unwrap();
+            (format!("{} threads", count), pool)
+        })
+        .collect()
+}
+
+fn bench_parallel(c: &mut Criterion) {
+    let image = create_image();
+    let mask = threshold_image(&image.grayscale());
+
+    for (name, pool) in pools() {
+        let mut group = c.benchmark_group(name);
+        group.sample_size(10);
+
+        group.bench_function("rotate", |b| {
+            b.iter(|| pool.install(|| rotate_image(&image, 30.0)))
+        });
+        group.bench_function("add_image", |b| {
+            b.iter(|| pool.install(|| add_two_images(&image, &image)))
+        });
+        group.bench_function("bitwise_and", |b| {
+            let mut ic = ImageConverter::new();
+            b.iter(|| pool.install(|| ic.bitwise_and(&image, &mask)))
+        });
+        group.bench_function("threshold", |b| {
+            b.iter(|| pool.install(|| threshold_image(&image)))
+        });
+        group.bench_function("resize_from_img", |b| {
+            b.iter(|| pool.install(|| crop_image(&image, WIDTH / 2, HEIGHT / 2)))
+        });
+        group.bench_function("ascii_art", |b| {
+            b.iter(|| pool.install(|| ascii_art_lines(&image, 1, Ramp::Standard)))
+        });
+        group.finish();
+    }
+}
+
+criterion_group!(benches, bench_parallel);
+criterion_main!(benches);
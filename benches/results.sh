@@ -0,0 +1,51 @@
+#!/bin/sh
+# ベンチマークの結果をbenches/results/<コミット>.jsonに書き出して比べる
+# target/criterionはcargo cleanで消えるので、書き出したjsonをコミットして共有する
+#
+#   sh benches/results.sh export <コミット>         target/criterion/*/*/<コミット>/の平均時間(ns)を書き出す
+#   sh benches/results.sh compare <元> <先>         2つのjsonを比べて変化率を出す
+set -eu
+
+RESULTS_DIR=$(dirname "$0")/results
+CRITERION_DIR=${CARGO_TARGET_DIR:-target}/criterion
+
+# 1行に1つ "名前": 平均時間(ns)
+export_results() {
+    baseline=$1
+    mkdir -p "$RESULTS_DIR"
+    output="$RESULTS_DIR/$baseline.json"
+
+    find "$CRITERION_DIR" -path "*/$baseline/benchmark.json" | sort | while read -r benchmark; do
+        name=$(sed -E 's/.*"full_id":"([^"]*)".*/\1/' "$benchmark")
+        mean=$(sed -E 's/^\{"mean":\{"confidence_interval":\{[^}]*\},"point_estimate":([^,]*),.*/\1/' \
+            "$(dirname "$benchmark")/estimates.json")
+        printf '  "%s": %s\n' "$name" "$mean"
+    done | awk 'BEGIN { print "{" } NR > 1 { print previous "," } { previous = $0 } END { if (NR) print previous; print "}" }' > "$output"
+
+    if [ "$(wc -l < "$output")" -le 2 ]; then
+        echo "no results for $baseline in $CRITERION_DIR" >&2
+        rm "$output"
+        exit 1
+    fi
+    echo "$output"
+}
+
+compare_results() {
+    awk -F'": ' '
+        /": / {
+            name = substr($1, index($1, "\"") + 1)
+            time = $2 + 0
+            if (FILENAME == ARGV[1]) base[name] = time
+            else if (name in base) printf "%-40s %12.0f ns -> %12.0f ns  %+6.1f%%\n", name, base[name], time, (time / base[name] - 1) * 100
+        }
+    ' "$RESULTS_DIR/$1.json" "$RESULTS_DIR/$2.json"
+}
+
+case "${1:-}" in
+    export) export_results "$2" ;;
+    compare) compare_results "$2" "$3" ;;
+    *)
+        echo "usage: $0 export <commit> | compare <base> <head>" >&2
+        exit 2
+        ;;
+esac
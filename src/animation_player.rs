@@ -0,0 +1,71 @@
+use std::time::{Duration, Instant};
+
+use iced::widget::image::Handle;
+use image::RgbaImage;
+
+use coffee_image::convert::animation::Animation;
+
+//アニメーションのプレビュー再生
+#[derive(Debug, Clone)]
+pub struct AnimationPlayer {
+    pub animation: Animation,
+    handles: Vec<Handle>,
+    frame_index: usize,
+    frame_started: Option<Instant>,
+    plays: u16,
+}
+
+impl AnimationPlayer {
+    pub fn new(animation: Animation) -> Self {
+        let handles = animation
+            .frames
+            .iter()
+            .map(|frame| {
+                let (width, height) = frame.image.dimensions();
+                Handle::from_pixels(width, height, frame.image.as_raw().clone())
+            })
+            .collect();
+
+        Self {
+            animation,
+            handles,
+            frame_index: 0,
+            frame_started: None,
+            plays: 0,
+        }
+    }
+
+    //ループ回数が決まっている場合は最後のフレームで止める
+    pub fn is_playing(&self) -> bool {
+        self.animation.loop_count == 0 || self.plays < self.animation.loop_count
+    }
+
+    pub fn tick(&mut self, now: Instant) {
+        let Some(frame_started) = self.frame_started else {
+            self.frame_started = Some(now);
+            return;
+        };
+        let delay = Duration::from_millis(self.animation.frames[self.frame_index].delay_ms as u64);
+        if now.duration_since(frame_started) < delay {
+            return;
+        }
+
+        if self.frame_index + 1 < self.handles.len() {
+            self.frame_index += 1;
+        } else {
+            self.plays = self.plays.saturating_add(1);
+            if self.is_playing() {
+                self.frame_index = 0;
+            }
+        }
+        self.frame_started = Some(now);
+    }
+
+    pub fn current_handle(&self) -> Handle {
+        self.handles[self.frame_index].clone()
+    }
+
+    pub fn current_frame(&self) -> &RgbaImage {
+        &self.animation.frames[self.frame_index].image
+    }
+}
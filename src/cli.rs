@@ -0,0 +1,128 @@
+use std::path::{Path, PathBuf};
+
+use coffee_image::{
+    convert::image_wrap::get_dynamic_image, encoder_options::EncoderOptions, error::Error,
+    save_format::SaveFormat, string_art::ascii::Ramp,
+};
+
+use crate::{
+    i18n::error_message,
+    pipeline::{Pipeline, Step},
+    recipe::{Preset, Recipe},
+    select_mode::{ParamKind, SelectMode},
+};
+
+//フラグか入力と出力の2つ以上の引数があればGUIを開かずに変換する
+//coffee_image [--recipe <ファイルかプリセット>] <入力> <出力> [変換]...
+//変換はパイプラインの1行と同じく "blur sigma=2" のように書く レシピの後に適用する
+//引数が画像1つだけならGUIでその画像を開く
+pub fn is_cli(args: &[String]) -> bool {
+    args.len() >= 2 || args.iter().any(|arg| arg.starts_with('-'))
+}
+
+pub fn run(args: &[String]) -> i32 {
+    if args.iter().any(|arg| arg == "-h" || arg == "--help") {
+        println!("{}", usage());
+        return 0;
+    }
+    let (recipe, args) = match args {
+        [flag, recipe, args @ ..] if flag == "--recipe" => (Some(recipe.as_str()), args),
+        args => (None, args),
+    };
+    let [input, output, steps @ ..] = args else {
+        eprintln!("{}", usage());
+        return 2;
+    };
+    match convert(recipe, input, output, steps) {
+        Ok(output) => {
+            println!("{}", output.display());
+            0
+        }
+        Err(error) => {
+            eprintln!("{}", error_message(&error));
+            1
+        }
+    }
+}
+
+fn convert(
+    recipe: Option<&str>,
+    input: &str,
+    output: &str,
+    steps: &[String],
+) -> Result<PathBuf, Error> {
+    let mut pipeline = match recipe {
+        Some(recipe) => load_recipe(recipe)?,
+        None => Pipeline::default(),
+    };
+    for step in steps {
+        pipeline.steps.push(step.parse::<Step>()?);
+    }
+    let output = PathBuf::from(output);
+    let save_format = SaveFormat::from_path(&output).ok_or_else(|| Error::InvalidParameter {
+        name: "output".to_string(),
+        value: output.display().to_string(),
+    })?;
+
+    let image = pipeline.apply(get_dynamic_image(&PathBuf::from(input))?)?;
+    save_format.save(&image, &output, &EncoderOptions::default())?;
+    Ok(output)
+}
+
+//プリセットの名前が優先
+fn load_recipe(recipe: &str) -> Result<Pipeline, Error> {
+    match Preset::from_key(recipe) {
+        Some(preset) => Ok(preset.pipeline()),
+        None => Recipe::load(Path::new(recipe))?.pipeline(),
+    }
+}
+
+//使えるモードとパラメータの一覧はスキーマから作る
+pub fn usage() -> String {
+    let mut usage = "usage: coffee_image [--recipe <file.json|file.toml|preset>] <input> <output> [\"operation key=value ...\"]...\n".to_string();
+    usage.push_str(&format!(
+        "\npresets: {}\n",
+        Preset::ALL.map(|preset| preset.key()).join(", ")
+    ));
+    for mode in SelectMode::ALL
+        .into_iter()
+        .filter(SelectMode::is_pipeline_step)
+    {
+        usage.push_str(&format!("\n  {}", mode.key()));
+        for spec in mode.params() {
+            let kind = match spec.kind {
+                ParamKind::Float {
+                    min, max, default, ..
+                } => {
+                    format!("{}..{} (default {})", min, max, default)
+                }
+                ParamKind::Int { min, max, default } => {
+                    format!("{}..{} (default {})", min, max, default)
+                }
+                ParamKind::Ramp => Ramp::ALL
+                    .map(|ramp| ramp.to_string().to_lowercase())
+                    .join("|"),
+                ParamKind::Color { required: true } => "#RRGGBB[AA]".to_string(),
+                ParamKind::Color { required: false } => "#RRGGBB[AA] (optional)".to_string(),
+                ParamKind::Image => "path".to_string(),
+            };
+            usage.push_str(&format!(" {}={}", spec.key, kind));
+        }
+    }
+    usage
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn is_cli_test() {
+        let args = |args: &[&str]| args.iter().map(|arg| arg.to_string()).collect::<Vec<_>>();
+        assert!(!is_cli(&args(&[])));
+        assert!(!is_cli(&args(&["photo 1.png"])));
+        assert!(is_cli(&args(&["--help"])));
+        assert!(is_cli(&args(&["in.png", "out.png"])));
+        assert!(is_cli(&args(&["--recipe", "sepia", "in.png", "out.png"])));
+    }
+}
@@ -0,0 +1,438 @@
+use std::fmt::Display;
+use std::fs::File;
+use std::io::{BufRead, BufReader, BufWriter};
+use std::path::PathBuf;
+
+use image::codecs::gif::{GifDecoder, GifEncoder, Repeat};
+use image::codecs::png::PngDecoder;
+use image::{imageops, AnimationDecoder, Delay, DynamicImage, Frame, ImageFormat, RgbaImage};
+
+use crate::coffee_image::error::{Error, ResultExt};
+
+use super::image_wrap::get_dynamic_image;
+use super::operation::Operation;
+use super::progress::Progress;
+
+//NETSCAPE2.0アプリケーション拡張にループ回数が入っている
+const NETSCAPE_EXTENSION: &[u8] = b"NETSCAPE2.0";
+//ディレイ0のフレームはブラウザと同じく100msとして扱う
+const DEFAULT_DELAY_MS: u32 = 100;
+//GifEncoderの減色の速さ(1が最高画質、30が最速)
+pub const DEFAULT_GIF_SPEED: i32 = 10;
+
+//1枚の画像のパラメータを変えながらフレームを作る
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Sweep {
+    #[default]
+    HueRotate,
+    Rotate,
+    Blur,
+}
+
+impl Sweep {
+    pub const ALL: [Sweep; 3] = [Sweep::HueRotate, Sweep::Rotate, Sweep::Blur];
+
+    pub fn range(&self) -> (f32, f32) {
+        match self {
+            Sweep::HueRotate | Sweep::Rotate => (0.0, 360.0),
+            Sweep::Blur => (0.0, 10.0),
+        }
+    }
+
+    //一周する変換は最後のフレームが最初と同じにならないよう終点を含めない
+    pub fn values(&self, frame_count: u32) -> Vec<f32> {
+        let (start, end) = self.range();
+        let frame_count = frame_count.max(2);
+        let divisor = match self {
+            Sweep::HueRotate | Sweep::Rotate => frame_count,
+            Sweep::Blur => frame_count - 1,
+        };
+
+        (0..frame_count)
+            .map(|index| start + (end - start) * index as f32 / divisor as f32)
+            .collect()
+    }
+
+    pub fn operation(&self, value: f32) -> Operation {
+        match self {
+            Sweep::HueRotate => Operation::HueRotate(value as i32),
+            Sweep::Rotate => Operation::Rotate(value),
+            Sweep::Blur => Operation::Blur(value),
+        }
+    }
+}
+
+impl Display for Sweep {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                Sweep::HueRotate => "HueRotate",
+                Sweep::Rotate => "Rotate",
+                Sweep::Blur => "Blur",
+            }
+        )
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct AnimationFrame {
+    pub image: RgbaImage,
+    pub delay_ms: u32,
+}
+
+//loop_countは再生回数(APNGのnum_playsと同じ) 0で無限ループ
+#[derive(Debug, Clone)]
+pub struct Animation {
+    pub frames: Vec<AnimationFrame>,
+    pub loop_count: u16,
+}
+
+impl Animation {
+    //アニメーションでない画像(1フレームのみ)の場合はNoneを返す
+    pub fn open(path: &PathBuf) -> Result<Option<Animation>, Error> {
+        Self::read(path).open_context(path)
+    }
+
+    fn read(path: &PathBuf) -> Result<Option<Animation>, Error> {
+        let format = image::io::Reader::open(path)?
+            .with_guessed_format()?
+            .format();
+
+        let animation = match format {
+            Some(ImageFormat::Gif) => {
+                let decoder = GifDecoder::new(open_reader(path)?).map_err(Error::image)?;
+                Animation::from_frames(decoder.into_frames(), gif_loop_count(path)?)?
+            }
+            Some(ImageFormat::Png) => {
+                let decoder = PngDecoder::new(open_reader(path)?).map_err(Error::image)?;
+                if !decoder.is_apng() {
+                    return Ok(None);
+                }
+                Animation::from_frames(decoder.apng().into_frames(), apng_loop_count(path)?)?
+            }
+            _ => return Ok(None),
+        };
+
+        Ok((animation.frames.len() > 1).then_some(animation))
+    }
+
+    fn from_frames(frames: image::Frames, loop_count: u16) -> Result<Animation, Error> {
+        let frames = frames
+            .map(|frame| {
+                let frame = frame.map_err(Error::image)?;
+                let (numerator, denominator) = frame.delay().numer_denom_ms();
+                let delay_ms = numerator / denominator.max(1);
+
+                Ok(AnimationFrame {
+                    image: frame.into_buffer(),
+                    delay_ms: if delay_ms == 0 { DEFAULT_DELAY_MS } else { delay_ms },
+                })
+            })
+            .collect::<Result<Vec<_>, Error>>()?;
+
+        Ok(Animation { frames, loop_count })
+    }
+
+    //大きさが違う画像は1枚目の大きさに合わせる
+    pub fn from_images(
+        paths: &[PathBuf],
+        delay_ms: u32,
+        loop_count: u16,
+    ) -> Result<Animation, Error> {
+        let mut frames: Vec<AnimationFrame> = Vec::new();
+        for path in paths {
+            let mut image = get_dynamic_image(path)?.to_rgba8();
+            if let Some(first) = frames.first() {
+                let (width, height) = first.image.dimensions();
+                if image.dimensions() != (width, height) {
+                    image = imageops::resize(&image, width, height, imageops::FilterType::Triangle);
+                }
+            }
+            frames.push(AnimationFrame { image, delay_ms });
+        }
+
+        Ok(Animation { frames, loop_count })
+    }
+
+    pub fn from_sweep(
+        image: &DynamicImage,
+        sweep: Sweep,
+        frame_count: u32,
+        delay_ms: u32,
+        loop_count: u16,
+    ) -> Result<Animation, Error> {
+        let frames = sweep
+            .values(frame_count)
+            .into_iter()
+            .map(|value| {
+                Ok(AnimationFrame {
+                    image: sweep.operation(value).apply(image)?.to_rgba8(),
+                    delay_ms,
+                })
+            })
+            .collect::<Result<Vec<_>, Error>>()?;
+
+        let mut animation = Animation { frames, loop_count };
+        let (width, height) = animation.dimensions();
+        for frame in animation.frames.iter_mut() {
+            frame.image = fit_to_canvas(&frame.image, width, height);
+        }
+        Ok(animation)
+    }
+
+    pub fn map(&self, operation: &Operation) -> Result<Animation, Error> {
+        self.map_with_progress(operation, &Progress::new())
+    }
+
+    //進み具合は全フレームの行数の合計で報告する
+    pub fn map_with_progress(
+        &self,
+        operation: &Operation,
+        progress: &Progress,
+    ) -> Result<Animation, Error> {
+        progress.start(self.frames.iter().map(|frame| frame.image.height()).sum());
+        let operation = operation.preload()?;
+        let frames = self
+            .frames
+            .iter()
+            .map(|frame| {
+                let image = operation.apply_with_progress(
+                    &DynamicImage::ImageRgba8(frame.image.clone()),
+                    progress,
+                )?;
+                Ok(AnimationFrame {
+                    image: image.to_rgba8(),
+                    delay_ms: frame.delay_ms,
+                })
+            })
+            .collect::<Result<Vec<_>, Error>>()?;
+
+        Ok(Animation {
+            frames,
+            loop_count: self.loop_count,
+        })
+    }
+
+    //回転などでフレームの大きさが違う場合は一番大きいフレームに合わせる
+    pub fn dimensions(&self) -> (u32, u32) {
+        self.frames.iter().fold((0, 0), |(width, height), frame| {
+            (width.max(frame.image.width()), height.max(frame.image.height()))
+        })
+    }
+
+    pub fn save_gif(&self, path: &PathBuf, speed: i32) -> Result<(), Error> {
+        self.write_gif(path, speed).save_context(path)
+    }
+
+    fn write_gif(&self, path: &PathBuf, speed: i32) -> Result<(), Error> {
+        let file = File::create(path)?;
+        let mut encoder = GifEncoder::new_with_speed(BufWriter::new(file), speed.clamp(1, 30));
+        //GIFのループ回数は1回目の再生の後に繰り返す回数 1回だけ再生する場合は拡張を書かない
+        match self.loop_count {
+            0 => encoder.set_repeat(Repeat::Infinite).map_err(Error::image)?,
+            1 => {}
+            loop_count => encoder
+                .set_repeat(Repeat::Finite(loop_count - 1))
+                .map_err(Error::image)?,
+        }
+
+        let (width, height) = self.dimensions();
+        encoder
+            .encode_frames(self.frames.iter().map(|frame| {
+                Frame::from_parts(
+                    fit_to_canvas(&frame.image, width, height),
+                    0,
+                    0,
+                    Delay::from_numer_denom_ms(frame.delay_ms, 1),
+                )
+            }))
+            .map_err(Error::image)
+    }
+
+    //imageクレート0.24はAPNGのエンコードに対応していないのでpngクレートを直接使う
+    pub fn save_apng(&self, path: &PathBuf) -> Result<(), Error> {
+        self.write_apng(path).save_context(path)
+    }
+
+    fn write_apng(&self, path: &PathBuf) -> Result<(), Error> {
+        let file = File::create(path)?;
+        let (width, height) = self.dimensions();
+
+        let mut encoder = png::Encoder::new(BufWriter::new(file), width, height);
+        encoder.set_color(png::ColorType::Rgba);
+        encoder.set_depth(png::BitDepth::Eight);
+        encoder
+            .set_animated(self.frames.len() as u32, self.loop_count as u32)
+            .map_err(Error::image)?;
+
+        let mut writer = encoder.write_header().map_err(Error::image)?;
+        for frame in &self.frames {
+            writer
+                .set_frame_delay(frame.delay_ms.min(u16::MAX as u32) as u16, 1000)
+                .map_err(Error::image)?;
+            writer
+                .write_image_data(fit_to_canvas(&frame.image, width, height).as_raw())
+                .map_err(Error::image)?;
+        }
+        writer.finish().map_err(Error::image)
+    }
+}
+
+//小さいフレームは透明のキャンバスの中央に置く
+pub fn fit_to_canvas(image: &RgbaImage, width: u32, height: u32) -> RgbaImage {
+    if image.dimensions() == (width, height) {
+        return image.clone();
+    }
+    let mut canvas = RgbaImage::new(width, height);
+    let left = (width as i64 - image.width() as i64) / 2;
+    let top = (height as i64 - image.height() as i64) / 2;
+    imageops::overlay(&mut canvas, image, left, top);
+    canvas
+}
+
+//ループ回数の拡張は最初のフレームより前にある 見つからなければ1回だけ再生する
+fn gif_loop_count(path: &PathBuf) -> Result<u16, Error> {
+    let mut reader = open_reader(path)?;
+    let mut decoder = gif::StreamingDecoder::new();
+    loop {
+        let buf = reader.fill_buf()?;
+        if buf.is_empty() {
+            return Ok(1);
+        }
+        let (consumed, loop_count) = match decoder.update(buf).map_err(Error::image)? {
+            (_, gif::Decoded::Frame(_) | gif::Decoded::Trailer) => return Ok(1),
+            (consumed, gif::Decoded::BlockFinished(id, data))
+                if id.0 == gif::Extension::Application as u8 =>
+            {
+                (consumed, netscape_loop_count(data))
+            }
+            (consumed, _) => (consumed, None),
+        };
+        if let Some(loop_count) = loop_count {
+            return Ok(loop_count);
+        }
+        reader.consume(consumed);
+    }
+}
+
+//[11] NETSCAPE2.0 [1] [ループ回数(リトルエンディアン)] 他のアプリケーション拡張はNone
+fn netscape_loop_count(data: &[u8]) -> Option<u16> {
+    match data.get(1..)?.strip_prefix(NETSCAPE_EXTENSION)? {
+        [1, low, high, ..] => Some(match u16::from_le_bytes([*low, *high]) {
+            0 => 0,
+            repeat => repeat.saturating_add(1),
+        }),
+        _ => None,
+    }
+}
+
+fn apng_loop_count(path: &PathBuf) -> Result<u16, Error> {
+    let reader = png::Decoder::new(open_reader(path)?)
+        .read_info()
+        .map_err(Error::image)?;
+
+    Ok(reader
+        .info()
+        .animation_control()
+        .map(|control| control.num_plays.min(u16::MAX as u32) as u16)
+        .unwrap_or(0))
+}
+
+fn open_reader(path: &PathBuf) -> Result<BufReader<File>, Error> {
+    File::open(path)
+        .map(BufReader::new)
+        .map_err(Error::from)
+}
+
+#[cfg(test)]
+mod test {
+    use std::fs;
+
+    use image::Rgba;
+
+    use super::*;
+
+    fn create_animation_helper() -> Animation {
+        let frames = (0..4)
+            .map(|index| AnimationFrame {
+                image: RgbaImage::from_pixel(8, 6, Rgba([index * 60, 0, 255 - index * 60, 255])),
+                delay_ms: 50 + index as u32 * 10,
+            })
+            .collect();
+        Animation {
+            frames,
+            loop_count: 3,
+        }
+    }
+
+    #[test]
+    fn gif_round_trip_test() {
+        let path = std::env::temp_dir().join("coffee_image_animation_test.gif");
+        create_animation_helper().save_gif(&path, 1).unwrap();
+
+        let animation = Animation::open(&path).unwrap().unwrap();
+        let _ = fs::remove_file(&path);
+        assert_eq!(animation.frames.len(), 4);
+        assert_eq!(animation.loop_count, 3);
+        assert_eq!(animation.frames[1].delay_ms, 60);
+    }
+
+    //コメントの中のNETSCAPE2.0はループ回数として読まない
+    #[test]
+    fn gif_loop_count_test() {
+        let path = std::env::temp_dir().join("coffee_image_loop_count_test.gif");
+        let mut animation = create_animation_helper();
+        animation.loop_count = 1;
+        animation.save_gif(&path, 30).unwrap();
+
+        let mut bytes = fs::read(&path).unwrap();
+        let trailer = bytes.pop().unwrap();
+        let comment = b"NETSCAPE2.0\x03\x01\x05\x00";
+        bytes.extend_from_slice(&[0x21, 0xFE, comment.len() as u8]);
+        bytes.extend_from_slice(comment);
+        bytes.extend_from_slice(&[0x00, trailer]);
+        fs::write(&path, bytes).unwrap();
+
+        let reopened = Animation::open(&path).unwrap().unwrap();
+        let _ = fs::remove_file(&path);
+        assert_eq!(reopened.frames.len(), 4);
+        assert_eq!(reopened.loop_count, 1);
+    }
+
+    #[test]
+    fn sweep_test() {
+        assert_eq!(Sweep::HueRotate.values(4), vec![0.0, 90.0, 180.0, 270.0]);
+        assert_eq!(Sweep::Blur.values(3), vec![0.0, 5.0, 10.0]);
+
+        let image = DynamicImage::ImageRgba8(RgbaImage::from_pixel(10, 4, Rgba([255, 0, 0, 255])));
+        let animation = Animation::from_sweep(&image, Sweep::Rotate, 8, 40, 0).unwrap();
+        assert_eq!(animation.frames.len(), 8);
+        //90度回転したフレームは縦長になるのでキャンバスは両方を含む大きさ
+        assert_eq!(animation.dimensions(), (10, 10));
+
+        let path = std::env::temp_dir().join("coffee_image_sweep_test.gif");
+        animation.save_gif(&path, 30).unwrap();
+        let reopened = Animation::open(&path).unwrap().unwrap();
+        let _ = fs::remove_file(&path);
+        assert_eq!(reopened.loop_count, 0);
+        assert_eq!(reopened.frames[2].image.dimensions(), (10, 10));
+    }
+
+    #[test]
+    fn apng_round_trip_test() {
+        let path = std::env::temp_dir().join("coffee_image_animation_test.png");
+        let inverted = create_animation_helper()
+            .map(&Operation::BitwiseNot)
+            .unwrap();
+        inverted.save_apng(&path).unwrap();
+
+        let animation = Animation::open(&path).unwrap().unwrap();
+        let _ = fs::remove_file(&path);
+        assert_eq!(animation.frames.len(), 4);
+        assert_eq!(animation.loop_count, 3);
+        assert_eq!(animation.frames[3].delay_ms, 80);
+        assert_eq!(animation.frames[0].image.get_pixel(0, 0), &Rgba([255, 255, 0, 255]));
+    }
+}
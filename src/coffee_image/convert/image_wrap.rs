@@ -1,14 +1,26 @@
 use image::{io::Reader as ImageReader, DynamicImage, GenericImageView};
-use image::{GenericImage, Rgba};
+use image::{imageops, ImageBuffer, Primitive, Rgba};
+use imageproc::gradients::{horizontal_sobel, vertical_sobel};
+use rayon::prelude::*;
 use std::io::prelude::Write;
 
+use std::fs;
+use std::ops::Range;
 use std::path::PathBuf;
 
-use crate::coffee_image::io::coffee_image_io::get_result_folder;
+use crate::coffee_image::io::temp_session::create_temp_path;
 use crate::coffee_image::io::text::TextFile;
+use super::operation::Operation;
+use super::progress::{bands, stitch_bands, Progress};
+use super::pixel_type::{
+    color_channel_count, from_f64, map_typed_image, par_pixels_mut, pixel_from_rgba8, premultiply,
+    to_f64, unpremultiply, TypedBuffer, TypedPixel,
+};
+use crate::coffee_image::encoder_options::{EncoderOptions, PngCompression};
+use crate::coffee_image::metadata::{apply_orientation, ImageMetadata, MetadataOptions};
 use crate::coffee_image::save_format::SaveFormat;
-use crate::coffee_image::string_art::ascii::get_byte_ascii;
-use crate::coffee_image::{error::Error, rng::generate_strings};
+use crate::coffee_image::string_art::ascii::{get_byte_edge_ascii, Ramp, EDGE_THRESHOLD};
+use crate::coffee_image::error::{Error, Input, ResultExt};
 
 //https://docs.rs/image/latest/image/
 #[derive(Debug, Clone, Default)]
@@ -16,6 +28,9 @@ pub struct ImageConverter {
     temp_converted_image_path: Option<PathBuf>,
     orgin_image_path: PathBuf,
     pub save_format: SaveFormat,
+    pub encoder_options: EncoderOptions,
+    pub metadata: Option<ImageMetadata>,
+    pub metadata_options: MetadataOptions,
     mask:Option<DynamicImage>,
 }
 
@@ -24,240 +39,129 @@ type StdError = Box<dyn std::error::Error>;
 
 //Convert Methods
 impl ImageConverter {
-    pub fn gray_scale(&mut self) -> Result<DynamicImage, Error> {
+    pub fn ascii_art(self, scale: u32, ramp: Ramp) -> Result<TextFile, Error> {
         let image = get_dynamic_image(&self.orgin_image_path)?;
+        let (text_file, mut output) = TextFile::new()?;
 
-        let gray_image = image.grayscale();
-
-        Ok(gray_image)
-    }
-
-    pub fn blur(&mut self, blur_value: f32) -> Result<DynamicImage, Error> {
-        let image = get_dynamic_image(&self.orgin_image_path)?;
-
-        let bulred_image = image.blur(blur_value);
-
-        Ok(bulred_image)
-    }
-
-    pub async fn async_blur(&mut self, blur_value: f32) -> Result<(), Error> {
-        let image = get_dynamic_image(&self.orgin_image_path)?;
-
-        let bulred_image = image.blur(blur_value);
-
-        Ok(())
-    }
-
-    pub fn bitwise_not(&mut self) -> Result<DynamicImage, Error> {
-        let mut image = get_dynamic_image(&self.orgin_image_path)?;
-
-        image.invert();
-
-        Ok(image)
+        for line in ascii_art_lines(&image, scale, ramp) {
+            let _ = output.write_all(&line);
+        }
+        Ok(text_file)
     }
-
-    pub fn hue_rotate(&mut self, rotate_value: i32) -> Result<DynamicImage, Error> {
+    //ascii_art/ascii_edge_artの各文字に対応する色(ANSI/HTML出力用)
+    pub fn ascii_cell_colors(&self, scale: u32) -> Result<Vec<Vec<Rgba<u8>>>, Error> {
         let image = get_dynamic_image(&self.orgin_image_path)?;
-        let rotate_image = image.huerotate(rotate_value);
-
-        Ok(rotate_image)
+        let (width, height) = image.dimensions();
+        let (cell_width, cell_height) = (scale.max(1), scale.max(1) * 2);
+
+        let colors = (0..height)
+            .step_by(cell_height as usize)
+            .map(|y| {
+                (0..width)
+                    .step_by(cell_width as usize)
+                    .map(|x| image.get_pixel(x, y))
+                    .collect()
+            })
+            .collect();
+        Ok(colors)
     }
-
-    pub fn ascii_art(self, scale: u32) -> Result<TextFile, Error> {
-        let image = get_dynamic_image(&self.orgin_image_path)?;
+    //エッジ検出をしてエッジのセルは向きに合わせた文字(| / - \ _)、それ以外は明るさで文字を選ぶ
+    pub fn ascii_edge_art(self, scale: u32, ramp: Ramp) -> Result<TextFile, Error> {
+        let image = get_dynamic_image(&self.orgin_image_path)?.to_rgba8();
+        let gray_image = DynamicImage::ImageRgba8(image.clone()).to_luma8();
+        let gradient_x = horizontal_sobel(&gray_image);
+        let gradient_y = vertical_sobel(&gray_image);
         let (width, height) = image.dimensions();
-
-        let (text_file, mut output) = TextFile::new();
-
-        for y in 0..height {
-            for x in 0..width {
-                if y % (scale * 2) == 0 && x % scale == 0 {
-                    let pixel = image.get_pixel(x, y);
-                    let mut intent = pixel[0] / 3 + pixel[1] / 3 + pixel[2] / 3;
-                    if pixel[3] == 0 {
-                        intent = 0;
+        let (cell_width, cell_height) = (scale.max(1), scale.max(1) * 2);
+
+        let (text_file, mut output) = TextFile::new()?;
+
+        let cell_rows: Vec<u32> = (0..height).step_by(cell_height as usize).collect();
+        let lines: Vec<Vec<u8>> = cell_rows.into_par_iter().map(|cell_y| {
+            let mut line = Vec::new();
+            for cell_x in (0..width).step_by(cell_width as usize) {
+                let mut strongest = (0.0, 0.0, 0.0, false);
+                let mut intent_sum = 0u32;
+                let mut count = 0u32;
+
+                for y in cell_y..(cell_y + cell_height).min(height) {
+                    for x in cell_x..(cell_x + cell_width).min(width) {
+                        let gx = gradient_x.get_pixel(x, y)[0] as f32;
+                        let gy = gradient_y.get_pixel(x, y)[0] as f32;
+                        let magnitude = (gx * gx + gy * gy).sqrt();
+                        if magnitude > strongest.0 {
+                            strongest = (magnitude, gx, gy, y - cell_y >= cell_height / 2);
+                        }
+                        intent_sum += gray_image.get_pixel(x, y)[0] as u32;
+                        count += 1;
                     }
-                    let _ = output.write(get_byte_ascii(intent));
                 }
+
+                let (magnitude, gx, gy, lower) = strongest;
+                let glyph = if image.get_pixel(cell_x, cell_y)[3] == 0 {
+                    ramp.get_byte(0)
+                } else if magnitude > EDGE_THRESHOLD {
+                    get_byte_edge_ascii(gx, gy, lower)
+                } else {
+                    ramp.get_byte((intent_sum / count) as u8)
+                };
+                line.extend_from_slice(glyph);
             }
-            if y % (scale * 2) == 0 {
-                let _ = output.write(b"\n");
-            }
+            line.push(b'\n');
+            line
+        }).collect();
+
+        for line in lines {
+            let _ = output.write_all(&line);
         }
         Ok(text_file)
     }
-    //https://qiita.com/yaju/items/680086b39bec5db93366
-    pub fn rotate(&mut self, angle: f32) -> Result<DynamicImage, Error> {
-        let radian = angle.to_radians();
-        let (sin, cos) = radian.sin_cos();
-
+    //元画像にoperationをかける 進み具合は元画像の行数で報告する
+    pub fn apply(&self, operation: &Operation, progress: &Progress) -> Result<DynamicImage, Error> {
         let image = get_dynamic_image(&self.orgin_image_path)?;
-        let (width, height) = image.dimensions();
-
-        let new_width = (cos.abs() * width as f32 + sin.abs() * height as f32).abs() as u32;
-        let new_height = (sin.abs() * width as f32 + cos.abs() * height as f32).abs() as u32;
-
-        let mut rotated_image = DynamicImage::new_rgba8(new_width, new_height);
-
-        let new_width_center = new_width as f32 / 2.0;
-        let new_height_center = new_height as f32 / 2.0;
-        let orgin_width_center = width as f32 / 2.0;
-        let orgin_height_center = height as f32 / 2.0;
-
-        for y in 0..new_height {
-            for x in 0..new_width {
-                let orgin_x = (cos * (x as f32 - new_width_center)
-                    - sin * (y as f32 - new_height_center)
-                    + orgin_width_center)
-                    .round() as i32;
-                let origin_y = (sin * (x as f32 - new_width_center)
-                    + cos * (y as f32 - new_height_center)
-                    + orgin_height_center)
-                    .round() as i32;
-
-                if orgin_x >= 0
-                    && orgin_x < width as i32
-                    && origin_y >= 0
-                    && origin_y < height as i32
-                {
-                    let pixel = image.get_pixel(orgin_x as u32, origin_y as u32);
-                    rotated_image.put_pixel(x, y, Rgba(pixel.0));
-                }
-            }
-        }
+        progress.start(image.height());
 
-        Ok(rotated_image)
-    }
-    pub fn add_images(&mut self, image_path2: &PathBuf) -> Result<DynamicImage, Error> {
-        let img1 = get_dynamic_image(&self.orgin_image_path)?;
-        let img2 = get_dynamic_image(&image_path2)?;
-        if !self.is_image_width_height_equal(&img1, &img2) {
-            return Err(Error::WidthHeightNotEqualError);
-        }
-        Ok(self.add_image(&img1, &img2))
-        // let (width, height) = img1.dimensions();
-        // let mut result_image = DynamicImage::new_rgba8(width, height);
-
-        // for y in 0..height {
-        //     for x in 0..width {
-        //         let pixel1 = img1.get_pixel(x, y);
-        //         let pixel2 = img2.get_pixel(x, y);
-
-        //         let new_pixel = [
-        //             pixel1[0].saturating_add(pixel2[0]),
-        //             pixel1[1].saturating_add(pixel2[1]),
-        //             pixel1[2].saturating_add(pixel2[2]),
-        //             pixel1[3].saturating_add(pixel2[3]),
-        //         ];
-        //         result_image.put_pixel(x, y, Rgba(new_pixel));
-        //     }
-        // }
-        // Ok(result_image)
-    }
-    fn add_image(&mut self,img1:&DynamicImage,img2:&DynamicImage) ->DynamicImage{
-        let (mut result_image,width,height) = self.new_image_create(&img1);
-
-        for y in 0..height {
-            for x in 0..width {
-                let pixel1 = img1.get_pixel(x, y);
-                let pixel2 = img2.get_pixel(x, y);
-
-                let new_pixel = [
-                    pixel1[0].saturating_add(pixel2[0]),
-                    pixel1[1].saturating_add(pixel2[1]),
-                    pixel1[2].saturating_add(pixel2[2]),
-                    pixel1[3].saturating_add(pixel2[3]),
-                ];
-                result_image.put_pixel(x, y, Rgba(new_pixel));
-            }
-        }
-        result_image
+        operation.apply_with_progress(&image, progress)
     }
     //https://whitewell.sakura.ne.jp/OpenCV/py_tutorials/py_core/py_image_arithmetics/py_image_arithmetics.html
     //TODO 画像の合成
+    //透明な画素は透明のまま(元のアルファを残す)
     fn threshold(&mut self) ->Result<DynamicImage,Error>{
-        let gray_image=self.gray_scale()?;
-        let threshold_value=5;
-
-        let (width,height)=gray_image.dimensions();
-        let (mut dst_image,_,_) = self.new_image_create(&gray_image);
-
-        for y in 0..height{
-            for x in 0..width{
-                let pixel=gray_image.get_pixel(x, y);
-                if pixel[0] > threshold_value{
-                    dst_image.put_pixel(x, y, Rgba([0,0,0,255]));
-                }else{
-                    dst_image.put_pixel(x, y, Rgba([255,255,255,255]));
-                }
-            }
-        }
-
-        Ok(dst_image)
+        let gray_image=self.apply(&Operation::Gray, &Progress::new())?;
+        Ok(threshold_image(&gray_image))
     }
 
     pub fn bitwise_and(&mut self,src:&DynamicImage,mask:&DynamicImage) ->DynamicImage {
-        let (mut result_image,width,height) = self.new_image_create(mask);
-
-        for y in 0..height{
-            for x in 0..width{
-                let src_pixel = src.get_pixel(x, y);
-                let mask_pixel = mask.get_pixel(x, y);
-
-                let result_pixel = Rgba([
-                    src_pixel[0] & mask_pixel[0],
-                    src_pixel[1] & mask_pixel[1],
-                    src_pixel[2] & mask_pixel[2],
-                    src_pixel[3] & mask_pixel[3],
-                ]);
-
-                result_image.put_pixel(x, y, result_pixel);
-            }
-        }
-
-        result_image
+        map_typed_image!(src, buffer => mask_buffer(buffer, mask))
     }
 
     fn create_mask_and_mask_inv(&mut self) ->Result<(DynamicImage,DynamicImage),Error>{
         let thresholded_image = self.threshold()?;
         let mut mask_inv = thresholded_image.clone();
         mask_inv.invert();
-        self.mask=Some(thresholded_image);
-        Ok((self.mask.clone().unwrap(),mask_inv))
+        self.mask=Some(thresholded_image.clone());
+        Ok((thresholded_image,mask_inv))
     }
 
     fn resize_from_img(&mut self,logo_image:&DynamicImage) ->Result<DynamicImage,Error>{
         let orginal_img = get_dynamic_image(&self.orgin_image_path)?;
-        let (mut resized_img,width,height) = self.new_image_create(&logo_image);
+        let (width,height) = logo_image.dimensions();
 
-        for y in 0..height{
-            for x in 0..width{
-                let pixel = orginal_img.get_pixel(x, y);
-                resized_img.put_pixel(x, y, pixel);
-            }
-        }
-
-        Ok(resized_img)
+        Ok(crop_image(&orginal_img, width, height))
     }
 
     //TODO名前変更
     fn transparete_add_img(&mut self,trans_img:&DynamicImage) ->Result<DynamicImage,Error>{
-        let mut orginal_img = get_dynamic_image(&self.orgin_image_path)?;
-        let (width,height) = trans_img.dimensions();
+        let orginal_img = get_dynamic_image(&self.orgin_image_path)?;
 
-        for y in 0..height{
-            for x in 0..width{
-                let pixel = trans_img.get_pixel(x, y);
-                orginal_img.put_pixel(x, y, pixel);
-            }
-        }
-        Ok(orginal_img)
+        //trans_imgの透明な部分は元の画像が見えるように重ねる(over)
+        Ok(map_typed_image!(&orginal_img, buffer => {
+            let mut result_image = buffer.clone();
+            imageops::overlay(&mut result_image, &convert_like(buffer, trans_img), 0, 0);
+            result_image
+        }))
     }
 
-    fn new_image_create(&mut self,image:&DynamicImage) ->(DynamicImage,u32,u32){
-        let (width,height) = image.dimensions();
-        (DynamicImage::new_rgb8(width, height),width,height)
-    }
 }
 //https://www.youtube.com/watch?v=t4DmszQfD-Q
 //汎用的なメッソド
@@ -267,14 +171,41 @@ impl ImageConverter {
             temp_converted_image_path: None,
             orgin_image_path: PathBuf::from(""),
             save_format: SaveFormat::Png,
+            encoder_options: EncoderOptions::default(),
+            metadata: None,
+            metadata_options: MetadataOptions::default(),
             mask:None,
         }
     }
-    pub fn save_converted_image(&self, path: &PathBuf, save_format: SaveFormat) {
-        let result_image = get_dynamic_image(self.temp_converted_image_path.as_ref().unwrap());
-        let _ = result_image.map(|result_image| {
-            result_image.save_with_format(path, save_format.convert_to_imageformat())
-        });
+    pub fn save_converted_image(&self, path: &PathBuf, save_format: SaveFormat) -> Result<(), Error> {
+        let temp_path = self
+            .temp_converted_image_path
+            .as_ref()
+            .ok_or(Error::MissingInput(Input::ConvertedImage))?;
+        let result_image = get_dynamic_image(temp_path)?;
+        let bytes = self
+            .encode_with_metadata(&result_image, save_format)
+            .save_context(path)?;
+
+        fs::write(path, bytes).save_context(path)
+    }
+
+    pub fn estimate_encoded_size(&self, save_format: SaveFormat) -> Result<usize, Error> {
+        let path = self.temp_converted_image_path.as_ref().unwrap_or(&self.orgin_image_path);
+        let image = get_dynamic_image(path)?;
+
+        self.encode_with_metadata(&image, save_format)
+            .map(|bytes| bytes.len())
+    }
+
+    //元画像のEXIFをmetadata_optionsに従って埋め込む
+    fn encode_with_metadata(&self, image: &DynamicImage, save_format: SaveFormat) -> Result<Vec<u8>, Error> {
+        let bytes = save_format.encode(image, &self.encoder_options)?;
+
+        match &self.metadata {
+            Some(metadata) => metadata.embed(bytes, save_format, &self.metadata_options),
+            None => Ok(bytes),
+        }
     }
 
     pub fn get_temp_result_path(&self) -> Option<PathBuf> {
@@ -285,132 +216,506 @@ impl ImageConverter {
         self.orgin_image_path = image_path
     }
 
+    pub fn set_temp_result_path(&mut self, temp_image_path: PathBuf) {
+        self.temp_converted_image_path = Some(temp_image_path);
+    }
+
     pub fn is_result_temp_path(&self) -> bool {
         self.temp_converted_image_path.is_some()
     }
 
-    pub fn save_temp_result_image(&mut self, temp_image: DynamicImage) {
-        let file_name = format!("{}", generate_strings());
+    pub fn save_temp_result_image(&mut self, temp_image: DynamicImage) -> Result<(), Error> {
+        let temp_image_path = save_temp_image(&temp_image)?;
 
-        let temp_image_path = get_result_folder()
-            .map(|mut path| {
-                path.push(file_name + "." + self.save_format.to_string().as_ref());
-                path
-            })
-            .ok();
+        self.temp_converted_image_path = Some(temp_image_path);
+        Ok(())
+    }
+}
 
-        let _ = &temp_image.save_with_format(
-            temp_image_path.as_ref().unwrap(),
-            self.save_format.convert_to_imageformat(),
-        );
+//一時ファイルは劣化しないようにpng(32bit floatはexr)で保存する
+pub fn save_temp_image(temp_image: &DynamicImage) -> Result<PathBuf, Error> {
+    let temp_format = match temp_image {
+        DynamicImage::ImageRgb32F(_) | DynamicImage::ImageRgba32F(_) => SaveFormat::OpenExr,
+        _ => SaveFormat::Png,
+    };
+
+    let temp_image_path = create_temp_path(temp_format.extension())?;
+
+    let temp_options = EncoderOptions {
+        png_compression: PngCompression::Fast,
+        ..EncoderOptions::default()
+    };
+    temp_format.save(temp_image, &temp_image_path, &temp_options)?;
+
+    Ok(temp_image_path)
+}
+//ascii_artの1行ずつの文字列(改行つき) 行ごとに別スレッドで作る
+pub fn ascii_art_lines(image: &DynamicImage, scale: u32, ramp: Ramp) -> Vec<Vec<u8>> {
+    let scale = scale.max(1);
+    let image = image.to_rgba8();
+    let (width, height) = image.dimensions();
+
+    let rows: Vec<u32> = (0..height).step_by((scale * 2) as usize).collect();
+    rows.into_par_iter()
+        .map(|y| {
+            let mut line = Vec::new();
+            for x in (0..width).step_by(scale as usize) {
+                let pixel = image.get_pixel(x, y);
+                let mut intent = pixel[0] / 3 + pixel[1] / 3 + pixel[2] / 3;
+                if pixel[3] == 0 {
+                    intent = 0;
+                }
+                line.extend_from_slice(ramp.get_byte(intent));
+            }
+            line.push(b'\n');
+            line
+        })
+        .collect()
+}
 
-        self.temp_converted_image_path = temp_image_path;
+//https://qiita.com/yaju/items/680086b39bec5db93366
+pub fn rotate_image(image: &DynamicImage, angle: f32) -> DynamicImage {
+    rotate_image_filled(image, angle, None)
+}
+
+//fillがあればはみ出した部分をその色で塗る
+pub fn rotate_image_filled(image: &DynamicImage, angle: f32, fill: Option<Rgba<u8>>) -> DynamicImage {
+    let (_, new_height) = rotated_size(image.dimensions(), angle);
+    map_typed_image!(image, buffer => rotate_rows(buffer, angle, 0..new_height, fill))
+}
+
+//回転後の行を帯ごとに作る 進み具合は元画像の行数に換算して報告する
+pub fn rotate_image_with_progress(
+    image: &DynamicImage,
+    angle: f32,
+    fill: Option<Rgba<u8>>,
+    progress: &Progress,
+) -> Result<DynamicImage, Error> {
+    let (_, new_height) = rotated_size(image.dimensions(), angle);
+    if new_height == 0 {
+        return Ok(rotate_image_filled(image, angle, fill));
     }
 
-    fn is_image_width_height_equal(&self, image1: &DynamicImage, image2: &DynamicImage) -> bool {
-        let (width1, height1) = image1.dimensions();
-        let (width2, height2) = image2.dimensions();
+    let mut reported = 0;
+    let rotated_bands = bands(new_height)
+        .map(|(top, rows)| {
+            let band =
+                map_typed_image!(image, buffer => rotate_rows(buffer, angle, top..top + rows, fill));
+            let done = ((top + rows) as u64 * image.height() as u64 / new_height as u64) as u32;
+            progress.advance(done - reported)?;
+            reported = done;
+            Ok(band)
+        })
+        .collect::<Result<Vec<_>, Error>>()?;
+
+    Ok(stitch_bands(rotated_bands))
+}
+
+fn rotated_size((width, height): (u32, u32), angle: f32) -> (u32, u32) {
+    let (sin, cos) = angle.to_radians().sin_cos();
+    let new_width = (cos.abs() * width as f32 + sin.abs() * height as f32).abs() as u32;
+    let new_height = (sin.abs() * width as f32 + cos.abs() * height as f32).abs() as u32;
+    (new_width, new_height)
+}
+
+//回転後の画像のrowsの行だけを返す はみ出した部分はfill、なければ0(アルファがあれば透明、なければ黒)
+fn rotate_rows<P: TypedPixel>(
+    image: &TypedBuffer<P>,
+    angle: f32,
+    rows: Range<u32>,
+    fill: Option<Rgba<u8>>,
+) -> TypedBuffer<P> {
+    let radian = angle.to_radians();
+    let (sin, cos) = radian.sin_cos();
+
+    let (width, height) = image.dimensions();
+    let (new_width, new_height) = rotated_size((width, height), angle);
+
+    let mut rotated_image = match fill {
+        Some(fill) => ImageBuffer::from_pixel(new_width, rows.len() as u32, pixel_from_rgba8(fill)),
+        None => ImageBuffer::new(new_width, rows.len() as u32),
+    };
+
+    let new_width_center = new_width as f32 / 2.0;
+    let new_height_center = new_height as f32 / 2.0;
+    let orgin_width_center = width as f32 / 2.0;
+    let orgin_height_center = height as f32 / 2.0;
+
+    par_pixels_mut(&mut rotated_image, |x, y, pixel| {
+        let y = y + rows.start;
+        let orgin_x = (cos * (x as f32 - new_width_center)
+            - sin * (y as f32 - new_height_center)
+            + orgin_width_center)
+            .round() as i32;
+        let origin_y = (sin * (x as f32 - new_width_center)
+            + cos * (y as f32 - new_height_center)
+            + orgin_height_center)
+            .round() as i32;
+
+        if orgin_x >= 0
+            && orgin_x < width as i32
+            && origin_y >= 0
+            && origin_y < height as i32
+        {
+            *pixel = *image.get_pixel(orgin_x as u32, origin_y as u32);
+        }
+    });
+
+    rotated_image
+}
+
+//img2はimg1の色の型に合わせてから足す
+//アルファがあればPorter-Duffのplus(乗算済みの色とアルファをそれぞれ足して1で打ち切る)
+pub fn add_two_images(img1: &DynamicImage, img2: &DynamicImage) -> DynamicImage {
+    map_typed_image!(img1, buffer => add_buffers(buffer, img2))
+}
+
+fn add_buffers<P: TypedPixel>(image1: &TypedBuffer<P>, image2: &DynamicImage) -> TypedBuffer<P> {
+    let image2 = P::buffer_from(image2);
+    let mut result_image = image1.clone();
+
+    //img2からはみ出した部分はimg1のまま
+    par_pixels_mut(&mut result_image, |x, y, pixel1| {
+        let Some(pixel2) = image2.get_pixel_checked(x, y) else {
+            return;
+        };
+        if !P::HAS_ALPHA {
+            pixel1.apply2(pixel2, |value1, value2| {
+                from_f64(to_f64(value1) + to_f64(value2))
+            });
+            return;
+        }
+        let premultiplied1 = premultiply(pixel1);
+        let premultiplied2 = premultiply(pixel2);
+        let sum: Vec<f64> = premultiplied1
+            .iter()
+            .zip(premultiplied2.iter())
+            .map(|(value1, value2)| (value1 + value2).min(1.0))
+            .collect();
+        unpremultiply(pixel1, &sum);
+    });
+    result_image
+}
+
+//帯に分けてぼかすときに上下に足す行数 imageops::blurのカーネルは2σまで届く
+pub fn blur_margin(sigma: f32) -> u32 {
+    let sigma = if sigma <= 0.0 { 1.0 } else { sigma };
+    (2.0 * sigma).ceil() as u32 + 2
+}
+
+//アルファがあれば乗算済みにしてからぼかす(透明部分の黒がにじまないように)
+pub fn blur_image(image: &DynamicImage, sigma: f32) -> DynamicImage {
+    if !image.color().has_alpha() {
+        return image.blur(sigma);
+    }
+    let mut premultiplied = image.to_rgba32f();
+    for pixel in premultiplied.pixels_mut() {
+        let alpha = pixel[3];
+        pixel.0.iter_mut().take(3).for_each(|value| *value *= alpha);
+    }
+
+    let mut blurred = imageops::blur(&premultiplied, sigma);
+    for pixel in blurred.pixels_mut() {
+        let alpha = pixel[3];
+        pixel.0.iter_mut().take(3).for_each(|value| {
+            *value = if alpha > 0.0 { (*value / alpha).min(1.0) } else { 0.0 }
+        });
+    }
+
+    let blurred = DynamicImage::ImageRgba32F(blurred);
+    map_typed_image!(image, buffer => convert_like(buffer, &blurred))
+}
+
+//imageクレートのhuerotateは最大値が255固定なので16bit/32Fでも使えるようにしたもの
+pub fn hue_rotate_image(image: &DynamicImage, rotate_value: i32) -> DynamicImage {
+    map_typed_image!(image, buffer => hue_rotate_buffer(buffer, rotate_value))
+}
+
+fn hue_rotate_buffer<P: TypedPixel>(image: &TypedBuffer<P>, rotate_value: i32) -> TypedBuffer<P> {
+    let mut result_image = image.clone();
+    if color_channel_count::<P>() < 3 {
+        return result_image;
+    }
+
+    let (sin, cos) = (rotate_value as f64).to_radians().sin_cos();
+    let matrix: [f64; 9] = [
+        0.213 + cos * 0.787 - sin * 0.213,
+        0.715 - cos * 0.715 - sin * 0.715,
+        0.072 - cos * 0.072 + sin * 0.928,
+        0.213 - cos * 0.213 + sin * 0.143,
+        0.715 + cos * 0.285 + sin * 0.140,
+        0.072 - cos * 0.072 - sin * 0.283,
+        0.213 - cos * 0.213 - sin * 0.787,
+        0.715 - cos * 0.715 + sin * 0.715,
+        0.072 + cos * 0.928 + sin * 0.072,
+    ];
+
+    par_pixels_mut(&mut result_image, |_, _, pixel| {
+        let channels = pixel.channels_mut();
+        let rgb = [to_f64(channels[0]), to_f64(channels[1]), to_f64(channels[2])];
+        for (index, channel) in channels.iter_mut().take(3).enumerate() {
+            let row = &matrix[index * 3..index * 3 + 3];
+            *channel = from_f64(row[0] * rgb[0] + row[1] * rgb[1] + row[2] * rgb[2]);
+        }
+    });
+    result_image
+}
+
+//maskの明るさが0の画素は0(アルファがあれば透明)、それ以外はsrcの値を残す
+fn mask_buffer<P: TypedPixel>(src: &TypedBuffer<P>, mask: &DynamicImage) -> TypedBuffer<P> {
+    let mask = mask.to_luma32f();
+    let mut result_image = src.clone();
+
+    par_pixels_mut(&mut result_image, |x, y, pixel| {
+        if mask.get_pixel_checked(x, y).is_some_and(|mask_pixel| mask_pixel[0] == 0.0) {
+            pixel.apply(|_| from_f64(0.0));
+        }
+    });
+    result_image
+}
+
+//グレースケール画像の明るさが5/255より大きい画素は黒、それ以外は白にする アルファは残す
+pub fn threshold_image(gray_image: &DynamicImage) -> DynamicImage {
+    map_typed_image!(gray_image, buffer => threshold_buffer(buffer, 5.0 / 255.0, 0.0, 1.0))
+}
+
+//明るさがlevel(0.0~1.0)より大きい画素は白、それ以外は黒にする 結果はグレースケール
+pub fn binarize_image(image: &DynamicImage, level: f32) -> DynamicImage {
+    let gray_image = image.grayscale();
+    map_typed_image!(&gray_image, buffer => threshold_buffer(buffer, level as f64, 1.0, 0.0))
+}
+
+//明るさがlevelより大きい画素をabove、それ以外をbelowにする(どれも最大値を1.0とした値)
+fn threshold_buffer<P: TypedPixel>(
+    gray_image: &TypedBuffer<P>,
+    level: f64,
+    above: f64,
+    below: f64,
+) -> TypedBuffer<P> {
+    let max = to_f64(P::Subpixel::DEFAULT_MAX_VALUE);
+    let threshold_value = max * level;
+    let color_count = color_channel_count::<P>();
+    let mut result_image = gray_image.clone();
+
+    par_pixels_mut(&mut result_image, |_, _, pixel| {
+        let channels = pixel.channels_mut();
+        let value = if to_f64(channels[0]) > threshold_value { above } else { below };
+        channels
+            .iter_mut()
+            .take(color_count)
+            .for_each(|channel| *channel = from_f64(value * max));
+    });
+    result_image
+}
+
+//keyの色との距離(RGBを0.0~1.0にしたユークリッド距離/√3)がtolerance以下の画素を透明にする
+//アルファのない画像はアルファ付きの同じ深さの型にしてから抜く
+pub fn chroma_key_image(image: &DynamicImage, key: Rgba<u8>, tolerance: f32) -> DynamicImage {
+    let image = match image {
+        DynamicImage::ImageLuma8(_) => DynamicImage::ImageLumaA8(image.to_luma_alpha8()),
+        DynamicImage::ImageRgb8(_) => DynamicImage::ImageRgba8(image.to_rgba8()),
+        DynamicImage::ImageLuma16(_) => DynamicImage::ImageLumaA16(image.to_luma_alpha16()),
+        DynamicImage::ImageRgb16(_) => DynamicImage::ImageRgba16(image.to_rgba16()),
+        DynamicImage::ImageRgb32F(_) => DynamicImage::ImageRgba32F(image.to_rgba32f()),
+        _ => image.clone(),
+    };
+    map_typed_image!(&image, buffer => chroma_key_buffer(buffer, key, tolerance as f64))
+}
+
+fn chroma_key_buffer<P: TypedPixel>(image: &TypedBuffer<P>, key: Rgba<u8>, tolerance: f64) -> TypedBuffer<P> {
+    let max = to_f64(P::Subpixel::DEFAULT_MAX_VALUE);
+    let key = key.0.map(|value| value as f64 / 255.0);
+    let mut result_image = image.clone();
+
+    par_pixels_mut(&mut result_image, |_, _, pixel| {
+        let rgb = pixel.to_rgb();
+        let squared_sum: f64 = rgb
+            .0
+            .iter()
+            .zip(key)
+            .map(|(value, key)| (to_f64(*value) / max - key).powi(2))
+            .sum();
+        if (squared_sum / 3.0).sqrt() <= tolerance {
+            if let Some(alpha) = pixel.channels_mut().last_mut() {
+                *alpha = from_f64(0.0);
+            }
+        }
+    });
+    result_image
+}
+
+//左上から(width, height)を切り出す 画像より大きければ画像の大きさまで
+pub fn crop_image(image: &DynamicImage, width: u32, height: u32) -> DynamicImage {
+    map_typed_image!(image, buffer => crop_buffer(buffer, width, height))
+}
 
-        width1 == width2 && height1 == height2
+fn crop_buffer<P: TypedPixel>(image: &TypedBuffer<P>, width: u32, height: u32) -> TypedBuffer<P> {
+    let mut cropped = ImageBuffer::new(width.min(image.width()), height.min(image.height()));
+    par_pixels_mut(&mut cropped, |x, y, pixel| *pixel = *image.get_pixel(x, y));
+    cropped
+}
+
+//長い辺がmax_side以下になるように縮小する (縮小した画像, 元の画像に対する倍率)
+pub fn downscale_image(image: &DynamicImage, max_side: u32) -> (DynamicImage, f32) {
+    let longest = image.width().max(image.height());
+    if longest <= max_side {
+        return (image.clone(), 1.0);
     }
+    let downscaled = image.thumbnail(max_side, max_side);
+    let factor = downscaled.width().max(downscaled.height()) as f32 / longest as f32;
+    (downscaled, factor)
+}
+
+fn convert_like<P: TypedPixel>(_like: &TypedBuffer<P>, image: &DynamicImage) -> TypedBuffer<P> {
+    P::buffer_from(image)
 }
+
 pub fn get_dynamic_image(path: &PathBuf) -> Result<DynamicImage, Error> {
     let image = ImageReader::open(path)
-        .map_err(|error| error.kind())
-        .map_err(Error::IOFailed)?
+        .open_context(path)?
+        .with_guessed_format()
+        .open_context(path)?
         .decode()
-        .map_err(|error| error.to_string())
-        .map_err(Error::ImageError)?;
-    Ok(image)
+        .open_context(path)?;
+    //スマホの写真は横向きで保存されていることがあるのでEXIFの向きに合わせる
+    let orientation = ImageMetadata::read(path).map_or(1, |metadata| metadata.orientation());
+    Ok(apply_orientation(image, orientation))
 }
 
 #[cfg(test)]
 mod test{
     use super::*;
-    fn create_imageconverter_helper() ->ImageConverter{
+
+    //透明な背景(色は緑)に不透明な赤い四角と半透明な青い帯を置いたpng
+    fn transparent_fixture(name: &str) -> PathBuf {
+        let image = image::RgbaImage::from_fn(16, 16, |x, y| {
+            if (4..12).contains(&x) && (4..12).contains(&y) {
+                Rgba([255, 0, 0, 255])
+            } else if y < 2 {
+                Rgba([0, 0, 255, 128])
+            } else {
+                Rgba([0, 255, 0, 0])
+            }
+        });
+        let path = std::env::temp_dir().join(format!("coffee_image_alpha_{}.png", name));
+        image.save(&path).unwrap();
+        path
+    }
+
+    fn alpha_fixture_converter(name: &str) -> ImageConverter {
         let mut ic = ImageConverter::new();
-        ic.set_image_path(PathBuf::from("examplesImages/people.jpg"));
+        ic.set_image_path(transparent_fixture(name));
         ic
     }
 
-    fn create_black_image(src:&DynamicImage) -> DynamicImage{
-        let (height,width) = src.dimensions();
-        let mut result_img = DynamicImage::new_rgb8(width, height);
-        for y in 0..height{
-            for x in 0..width{
-                let black_rgba= Rgba([0,0,0,255]);
-                result_img.put_pixel(x, y, black_rgba);
+    #[test]
+    fn color_filters_keep_alpha_test() {
+        let ic = alpha_fixture_converter("filters");
+        let original = get_dynamic_image(&ic.orgin_image_path).unwrap().to_rgba8();
+        let filtered = [
+            ic.apply(&Operation::Gray, &Progress::new()).unwrap(),
+            ic.apply(&Operation::BitwiseNot, &Progress::new()).unwrap(),
+            ic.apply(&Operation::HueRotate(120), &Progress::new()).unwrap(),
+        ];
+
+        for image in filtered {
+            assert!(image.color().has_alpha());
+            for (original, filtered) in original.pixels().zip(image.to_rgba8().pixels()) {
+                assert_eq!(original[3], filtered[3]);
             }
         }
-        result_img
-    }
-    #[test]
-    fn threshold_test(){
-        let mut ic = ImageConverter::new();
-        ic.set_image_path(PathBuf::from("examplesImages/people.jpg"));
-        let mask=ic.threshold().unwrap();
-        let _=mask.save("test.jpg");
     }
 
     #[test]
-    fn create_mask_test(){
-        let mut ic = create_imageconverter_helper();
-        let (mask,_) = ic.create_mask_and_mask_inv().unwrap();
-        let _=mask.save("cm.jpg");
+    fn blur_without_dark_fringe_test() {
+        let ic = alpha_fixture_converter("blur");
+        let blurred = ic.apply(&Operation::Blur(2.0), &Progress::new()).unwrap().to_rgba8();
+
+        //四角のすぐ外側は半透明の赤になり、背景の緑や黒は混ざらない
+        let edge = blurred.get_pixel(3, 8);
+        assert!(edge[3] > 0 && edge[3] < 255);
+        assert!(edge[0] >= 250, "{:?}", edge);
+        assert!(edge[1] <= 5, "{:?}", edge);
     }
 
     #[test]
-    fn bitwise_and_test(){
-        let mut ic = create_imageconverter_helper();
-        //let mask = ic.create_mask().unwrap();
-        let src = get_dynamic_image(&ic.orgin_image_path).unwrap();
-        let mask = get_dynamic_image(&PathBuf::from("examplesImages/people_mask.jpg")).unwrap();
+    fn rotate_corners_transparent_test() {
+        let ic = alpha_fixture_converter("rotate");
+        let rotated = ic.apply(&Operation::Rotate(45.0), &Progress::new()).unwrap().to_rgba8();
 
-        let bitwise_and_image = ic.bitwise_and(&src, &mask);
-        let _ =bitwise_and_image.save("bitwise_and.jpg");
+        assert_eq!(rotated.get_pixel(0, 0)[3], 0);
+        let (width, height) = rotated.dimensions();
+        assert_eq!(*rotated.get_pixel(width / 2, height / 2), Rgba([255, 0, 0, 255]));
     }
 
     #[test]
-    fn resize_from_img_test(){
-        let mut ic = create_imageconverter_helper();
-
-        let logo_img = get_dynamic_image(&PathBuf::from("examplesImages/images.jpg")).unwrap();
-        let resized_img = ic.resize_from_img(&logo_img).unwrap();
-
-        let _=resized_img.save("resized.jpg");
+    fn add_premultiplied_test() {
+        let ic = alpha_fixture_converter("add");
+        let added = ic
+            .apply(&Operation::Add(transparent_fixture("add_other")), &Progress::new())
+            .unwrap().to_rgba8();
+
+        //透明+透明は透明、不透明は飽和、半透明の青は2倍のアルファで色はそのまま
+        assert_eq!(added.get_pixel(0, 8)[3], 0);
+        assert_eq!(*added.get_pixel(8, 8), Rgba([255, 0, 0, 255]));
+        assert_eq!(*added.get_pixel(0, 0), Rgba([0, 0, 255, 255]));
+
+        let opaque = DynamicImage::ImageRgba8(image::RgbaImage::from_pixel(16, 16, Rgba([10, 20, 30, 255])));
+        let background = get_dynamic_image(&ic.orgin_image_path).unwrap();
+        let added = add_two_images(&opaque, &background).to_rgba8();
+        assert_eq!(*added.get_pixel(0, 8), Rgba([10, 20, 30, 255]));
     }
 
     #[test]
-    fn image_add_transprate(){
-        let logo_img_path = "examplesImages/unitylogo.png";//"examplesImages/python.jpg";
-        let logo_img = get_dynamic_image(&PathBuf::from(logo_img_path)).unwrap();
-
-        let mut ic = ImageConverter::new();
-        ic.set_image_path(PathBuf::from("examplesImages/add2.jpg"));
-
-        let roi = ic.resize_from_img(&logo_img).unwrap();
-
-        let mut ic_logo = ImageConverter::new();
-        ic_logo.set_image_path(PathBuf::from(logo_img_path));
-
-        let (mask,mask_inv) = ic_logo.create_mask_and_mask_inv().unwrap();
-        let _=mask.save("mask.png");
-        let _=mask_inv.save("mask_inv.png");
-
-        let img1_bg=ic.bitwise_and(&roi, &mask_inv);
-        let _=img1_bg.save("img1_bg.png");
-        let img2_fg = ic.bitwise_and(&img_open(logo_img_path), &mask);
-        let _ = img2_fg.save("img2_fg.png");
-        let add_img = ic.add_image(&img1_bg, &img2_fg);
-        let _=add_img.save("addtrans.png");
-        let dst =ic.transparete_add_img(&add_img).unwrap();
-        let _=dst.save("transPreateAdd.png");
+    fn mask_operations_keep_alpha_test() {
+        let mut ic = alpha_fixture_converter("mask");
+        let source = get_dynamic_image(&ic.orgin_image_path).unwrap();
+
+        let mask = ic.threshold().unwrap();
+        assert!(mask.color().has_alpha());
+        assert_eq!(mask.to_rgba8().get_pixel(0, 8)[3], 0);
+
+        let (mask, mask_inv) = ic.create_mask_and_mask_inv().unwrap();
+        //赤い四角はしきい値より明るいのでmaskは黒 → bitwise_andで透明になる
+        assert_eq!(ic.bitwise_and(&source, &mask).to_rgba8().get_pixel(8, 8)[3], 0);
+        assert_eq!(*ic.bitwise_and(&source, &mask_inv).to_rgba8().get_pixel(8, 8), Rgba([255, 0, 0, 255]));
+
+        let cropped = ic.resize_from_img(&DynamicImage::new_rgba8(8, 8)).unwrap();
+        assert_eq!(cropped.color(), source.color());
+        assert_eq!(cropped.to_rgba8().get_pixel(0, 0)[3], 128);
+
+        //透明な画素を重ねても下の画像が残る
+        let base = DynamicImage::ImageRgba8(image::RgbaImage::from_pixel(16, 16, Rgba([1, 2, 3, 255])));
+        let base_path = std::env::temp_dir().join("coffee_image_alpha_base.png");
+        base.save(&base_path).unwrap();
+        ic.set_image_path(base_path);
+        let overlaid = ic.transparete_add_img(&source).unwrap().to_rgba8();
+        assert_eq!(*overlaid.get_pixel(0, 8), Rgba([1, 2, 3, 255]));
+        assert_eq!(*overlaid.get_pixel(8, 8), Rgba([255, 0, 0, 255]));
     }
 
-    fn img_open(path :&str) ->DynamicImage{
-        get_dynamic_image(&PathBuf::from(path)).unwrap()
+    #[test]
+    fn threshold_and_crop_16bit_test() {
+        let gray = DynamicImage::ImageLumaA16(ImageBuffer::from_fn(700, 3, |x, y| {
+            image::LumaA([x as u16 * 2, y as u16 * 1000])
+        }));
+
+        //しきい値は最大値の5/255(16bitなら1285)
+        let thresholded = threshold_image(&gray).into_luma_alpha16();
+        assert_eq!(*thresholded.get_pixel(642, 0), image::LumaA([65535, 0]));
+        assert_eq!(*thresholded.get_pixel(643, 2), image::LumaA([0, 2000]));
+
+        let cropped = crop_image(&gray, 10, 100);
+        assert_eq!(cropped.dimensions(), (10, 3));
+        assert_eq!(cropped.as_bytes(), gray.crop_imm(0, 0, 10, 3).as_bytes());
     }
 
-    
-
+    #[test]
+    fn ascii_art_zero_scale_test() {
+        let image = DynamicImage::ImageRgba8(image::RgbaImage::new(4, 4));
+        assert_eq!(
+            ascii_art_lines(&image, 0, Ramp::Standard),
+            ascii_art_lines(&image, 1, Ramp::Standard)
+        );
+    }
 }
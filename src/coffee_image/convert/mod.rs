@@ -1 +1,5 @@
-pub mod image_wrap;
\ No newline at end of file
+pub mod image_wrap;
+pub mod operation;
+pub mod pixel_type;
+pub mod progress;
+pub mod animation;
\ No newline at end of file
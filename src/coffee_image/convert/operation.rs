@@ -0,0 +1,291 @@
+use std::{path::PathBuf, sync::Arc};
+
+use image::{DynamicImage, GenericImageView, Rgba};
+
+use crate::coffee_image::error::Error;
+
+use super::image_wrap::{
+    add_two_images, binarize_image, blur_image, blur_margin, chroma_key_image, get_dynamic_image,
+    downscale_image, hue_rotate_image, rotate_image_with_progress,
+};
+use super::progress::{map_bands, Progress};
+
+//画像から画像への変換 アニメーションの各フレームにも同じ変換をかける
+#[derive(Debug, Clone, PartialEq)]
+pub enum Operation {
+    Gray,
+    BitwiseNot,
+    HueRotate(i32),
+    Blur(f32),
+    Rotate(f32),
+    //はみ出した部分を塗る色を指定して回転
+    RotateFill(f32, Rgba<u8>),
+    //(抜く色, 許容量0.0~1.0)
+    ChromaKey(Rgba<u8>, f32),
+    //明るさ0.0~1.0のしきい値で白黒にする
+    Threshold(f32),
+    Add(PathBuf),
+    //読み込み済みの2枚目の画像を足す preloadで作る
+    AddImage(Arc<DynamicImage>),
+    //長い辺がこの長さ以下になるように縮小する
+    Resize(u32),
+    //順にかける レシピはこれにまとめて1回の変換にする
+    Sequence(Vec<Operation>),
+}
+
+impl Operation {
+    //縮小した画像に使うときは大きさに比例するパラメータを合わせる
+    pub fn scaled(&self, factor: f32) -> Operation {
+        match self {
+            Operation::Blur(sigma) => Operation::Blur(sigma * factor),
+            Operation::Resize(max_side) => Operation::Resize(((*max_side as f32 * factor) as u32).max(1)),
+            Operation::Sequence(operations) => Operation::Sequence(
+                operations.iter().map(|operation| operation.scaled(factor)).collect(),
+            ),
+            operation => operation.clone(),
+        }
+    }
+
+    //ファイルを読む変換はフレームごとに読み直さないように先に読んでおく
+    pub fn preload(&self) -> Result<Operation, Error> {
+        match self {
+            Operation::Add(image_path2) => {
+                Ok(Operation::AddImage(Arc::new(get_dynamic_image(image_path2)?)))
+            }
+            Operation::Sequence(operations) => operations
+                .iter()
+                .map(Operation::preload)
+                .collect::<Result<_, _>>()
+                .map(Operation::Sequence),
+            operation => Ok(operation.clone()),
+        }
+    }
+
+    pub fn apply(&self, image: &DynamicImage) -> Result<DynamicImage, Error> {
+        self.apply_with_progress(image, &Progress::new())
+    }
+
+    //帯ごとにprogressを進め、中断されたらErr(Cancelled)で抜ける
+    pub fn apply_with_progress(
+        &self,
+        image: &DynamicImage,
+        progress: &Progress,
+    ) -> Result<DynamicImage, Error> {
+        match self {
+            Operation::Gray => map_bands(image, 0, progress, |band, _| band.grayscale()),
+            Operation::BitwiseNot => map_bands(image, 0, progress, |band, _| {
+                let mut band = band.clone();
+                band.invert();
+                band
+            }),
+            Operation::HueRotate(rotate_value) => map_bands(image, 0, progress, |band, _| {
+                hue_rotate_image(band, *rotate_value)
+            }),
+            Operation::Blur(blur_value) => {
+                map_bands(image, blur_margin(*blur_value), progress, |band, _| {
+                    blur_image(band, *blur_value)
+                })
+            }
+            Operation::Rotate(angle) => rotate_image_with_progress(image, *angle, None, progress),
+            Operation::RotateFill(angle, fill) => {
+                rotate_image_with_progress(image, *angle, Some(*fill), progress)
+            }
+            Operation::ChromaKey(key, tolerance) => map_bands(image, 0, progress, |band, _| {
+                chroma_key_image(band, *key, *tolerance)
+            }),
+            Operation::Threshold(level) => {
+                map_bands(image, 0, progress, |band, _| binarize_image(band, *level))
+            }
+            Operation::Add(image_path2) => {
+                Operation::AddImage(Arc::new(get_dynamic_image(image_path2)?))
+                    .apply_with_progress(image, progress)
+            }
+            Operation::AddImage(image2) => {
+                if image.dimensions() != image2.dimensions() {
+                    return Err(Error::WidthHeightNotEqualError {
+                        expected: image.dimensions(),
+                        actual: image2.dimensions(),
+                    });
+                }
+                map_bands(image, 0, progress, |band, top| {
+                    add_two_images(band, &image2.crop_imm(0, top, band.width(), band.height()))
+                })
+            }
+            Operation::Resize(max_side) => {
+                progress.check()?;
+                let (resized, _) = downscale_image(image, *max_side);
+                progress.advance(image.height())?;
+                Ok(resized)
+            }
+            //進み具合は変換ごとに数え直す
+            Operation::Sequence(operations) => {
+                operations.iter().try_fold(image.clone(), |image, operation| {
+                    progress.start(image.height());
+                    operation.apply_with_progress(&image, progress)
+                })
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use image::{ImageBuffer, Rgb, Rgba};
+
+    use super::*;
+    use crate::coffee_image::convert::image_wrap::{add_two_images, downscale_image, rotate_image};
+
+    fn test_images() -> Vec<DynamicImage> {
+        let rgb16 = ImageBuffer::from_fn(8, 6, |x, y| {
+            Rgb([x as u16 * 8000 + 3, y as u16 * 9000 + 5, 40001])
+        });
+        let rgba16 =
+            ImageBuffer::from_fn(8, 6, |x, _| Rgba([x as u16 * 8000 + 7, 300, 65535, 32769]));
+        let rgb32f = ImageBuffer::from_fn(8, 6, |x, _| Rgb([x as f32 / 8.0, 0.5, 0.25]));
+        let rgb16 = DynamicImage::ImageRgb16(rgb16);
+
+        vec![
+            DynamicImage::ImageLuma8(rgb16.to_luma8()),
+            DynamicImage::ImageLuma16(rgb16.to_luma16()),
+            DynamicImage::ImageRgb8(rgb16.to_rgb8()),
+            DynamicImage::ImageRgba8(rgb16.to_rgba8()),
+            rgb16,
+            DynamicImage::ImageRgba16(rgba16),
+            DynamicImage::ImageRgb32F(rgb32f),
+        ]
+    }
+
+    #[test]
+    fn color_type_preserving_test() {
+        let operations = [
+            Operation::BitwiseNot,
+            Operation::HueRotate(90),
+            Operation::Blur(1.0),
+            Operation::Rotate(30.0),
+        ];
+        for image in test_images() {
+            for operation in operations.iter() {
+                let converted = operation.apply(&image).unwrap();
+                assert_eq!(converted.color(), image.color(), "{:?}", operation);
+            }
+            assert_eq!(add_two_images(&image, &image).color(), image.color());
+        }
+    }
+
+    #[test]
+    fn sixteen_bit_precision_test() {
+        let image = &test_images()[4];
+        //0度の色相回転と0度の回転は16bitの値をそのまま残す
+        for operation in [Operation::HueRotate(0), Operation::Rotate(0.0)] {
+            let converted = operation.apply(image).unwrap();
+            for (original, converted) in
+                image.to_rgb16().pixels().zip(converted.to_rgb16().pixels())
+            {
+                for (original, converted) in original.0.iter().zip(converted.0.iter()) {
+                    assert!((*original as i32 - *converted as i32).abs() <= 1);
+                }
+            }
+        }
+
+        let added = add_two_images(image, image).to_rgb16();
+        assert_eq!(added.get_pixel(0, 0)[0], 6);
+        assert_eq!(added.get_pixel(0, 0)[2], 65535);
+    }
+
+    #[test]
+    fn progress_test() {
+        let image = DynamicImage::ImageRgb8(
+            ImageBuffer::from_fn(40, 150, |x, y| Rgb([x as u8 * 6, y as u8, 90])),
+        );
+        for operation in [Operation::Gray, Operation::Blur(3.0), Operation::Rotate(30.0)] {
+            let progress = Progress::new();
+            progress.start(image.height());
+            operation.apply_with_progress(&image, &progress).unwrap();
+            assert_eq!(progress.rows(), (150, 150), "{:?}", operation);
+        }
+
+        //帯に分けて回転しても一度に回転したものと同じ
+        let rotated = Operation::Rotate(30.0).apply(&image).unwrap();
+        assert_eq!(rotated.as_bytes(), rotate_image(&image, 30.0).as_bytes());
+    }
+
+    #[test]
+    fn picked_color_operations_test() {
+        let image = DynamicImage::ImageRgb16(ImageBuffer::from_fn(20, 10, |x, _| {
+            if x < 10 {
+                Rgb([0, 65535, 0])
+            } else {
+                Rgb([65535, 65535, 65535])
+            }
+        }));
+        let green = Rgba([0, 255, 0, 255]);
+
+        //はみ出した角は指定した色になる
+        let rotated = Operation::RotateFill(45.0, Rgba([255, 0, 0, 255])).apply(&image).unwrap();
+        assert_eq!(rotated.color(), image.color());
+        assert_eq!(rotated.to_rgb16().get_pixel(0, 0).0, [65535, 0, 0]);
+
+        //アルファのない16bitはアルファ付きの16bitになり、緑だけ透明になる
+        let keyed = Operation::ChromaKey(green, 0.1).apply(&image).unwrap();
+        assert_eq!(keyed.color(), image::ColorType::Rgba16);
+        assert_eq!(keyed.to_rgba16().get_pixel(2, 2)[3], 0);
+        assert_eq!(keyed.to_rgba16().get_pixel(15, 2)[3], 65535);
+
+        let binarized = Operation::Threshold(0.8).apply(&image).unwrap().to_luma16();
+        assert_eq!(binarized.get_pixel(2, 2)[0], 0);
+        assert_eq!(binarized.get_pixel(15, 2)[0], 65535);
+    }
+
+    #[test]
+    fn downscaled_preview_test() {
+        let image = &test_images()[4];
+        let large = image.resize_exact(400, 300, image::imageops::FilterType::Nearest);
+
+        let (downscaled, factor) = downscale_image(&large, 100);
+        assert_eq!(downscaled.dimensions(), (100, 75));
+        assert_eq!(downscaled.color(), large.color());
+        assert_eq!(factor, 0.25);
+        assert_eq!(Operation::Blur(8.0).scaled(factor), Operation::Blur(2.0));
+        assert_eq!(Operation::Rotate(30.0).scaled(factor), Operation::Rotate(30.0));
+
+        //小さい画像はそのまま
+        assert_eq!(downscale_image(image, 100).1, 1.0);
+    }
+
+    #[test]
+    fn sequence_test() {
+        let image = &test_images()[4];
+        let large = image.resize_exact(400, 300, image::imageops::FilterType::Nearest);
+
+        let resized = Operation::Resize(100).apply(&large).unwrap();
+        assert_eq!(resized.dimensions(), (100, 75));
+        assert_eq!(resized.color(), large.color());
+
+        let sequence = Operation::Sequence(vec![Operation::Resize(100), Operation::BitwiseNot]);
+        let expected = Operation::BitwiseNot.apply(&resized).unwrap();
+        assert_eq!(sequence.apply(&large).unwrap().as_bytes(), expected.as_bytes());
+        assert_eq!(
+            sequence.scaled(0.5),
+            Operation::Sequence(vec![Operation::Resize(50), Operation::BitwiseNot])
+        );
+    }
+
+    #[test]
+    fn preload_test() {
+        let image = &test_images()[2];
+        let path = std::env::temp_dir().join("coffee_image_preload_test.png");
+        image.save(&path).unwrap();
+
+        let sequence = Operation::Sequence(vec![Operation::Gray, Operation::Add(path.clone())]);
+        let preloaded = sequence.preload().unwrap();
+        let _ = std::fs::remove_file(&path);
+        assert!(matches!(
+            &preloaded,
+            Operation::Sequence(operations) if matches!(operations[1], Operation::AddImage(_))
+        ));
+
+        let gray = Operation::Gray.apply(image).unwrap();
+        let expected = add_two_images(&gray, image);
+        assert_eq!(preloaded.apply(image).unwrap().as_bytes(), expected.as_bytes());
+    }
+}
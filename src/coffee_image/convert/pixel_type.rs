@@ -0,0 +1,170 @@
+use image::{DynamicImage, ImageBuffer, Luma, LumaA, Pixel, Primitive, Rgb, Rgba};
+use num_traits::NumCast;
+use rayon::prelude::*;
+
+pub type TypedBuffer<P> = ImageBuffer<P, Vec<<P as Pixel>::Subpixel>>;
+
+//DynamicImageの色の型(8bit/16bit/32F)ごとのバッファを同じ処理で扱う
+pub trait TypedPixel: Pixel<Subpixel: Send + Sync> + Send + Sync + 'static {
+    const HAS_ALPHA: bool;
+
+    fn buffer_from(image: &DynamicImage) -> TypedBuffer<Self>;
+}
+
+macro_rules! typed_pixel {
+    ($pixel:ty, $convert:ident, $has_alpha:expr) => {
+        impl TypedPixel for $pixel {
+            const HAS_ALPHA: bool = $has_alpha;
+
+            fn buffer_from(image: &DynamicImage) -> TypedBuffer<Self> {
+                image.$convert()
+            }
+        }
+    };
+}
+
+typed_pixel!(Luma<u8>, to_luma8, false);
+typed_pixel!(LumaA<u8>, to_luma_alpha8, true);
+typed_pixel!(Rgb<u8>, to_rgb8, false);
+typed_pixel!(Rgba<u8>, to_rgba8, true);
+typed_pixel!(Luma<u16>, to_luma16, false);
+typed_pixel!(LumaA<u16>, to_luma_alpha16, true);
+typed_pixel!(Rgb<u16>, to_rgb16, false);
+typed_pixel!(Rgba<u16>, to_rgba16, true);
+typed_pixel!(Rgb<f32>, to_rgb32f, false);
+typed_pixel!(Rgba<f32>, to_rgba32f, true);
+
+//元の色の型のまま$bodyでバッファを変換する ($bufferは&TypedBuffer<P>)
+macro_rules! map_typed_image {
+    ($image:expr, $buffer:ident => $body:expr) => {
+        match $image {
+            DynamicImage::ImageLuma8($buffer) => DynamicImage::ImageLuma8($body),
+            DynamicImage::ImageLumaA8($buffer) => DynamicImage::ImageLumaA8($body),
+            DynamicImage::ImageRgb8($buffer) => DynamicImage::ImageRgb8($body),
+            DynamicImage::ImageRgba8($buffer) => DynamicImage::ImageRgba8($body),
+            DynamicImage::ImageLuma16($buffer) => DynamicImage::ImageLuma16($body),
+            DynamicImage::ImageLumaA16($buffer) => DynamicImage::ImageLumaA16($body),
+            DynamicImage::ImageRgb16($buffer) => DynamicImage::ImageRgb16($body),
+            DynamicImage::ImageRgba16($buffer) => DynamicImage::ImageRgba16($body),
+            DynamicImage::ImageRgb32F($buffer) => DynamicImage::ImageRgb32F($body),
+            DynamicImage::ImageRgba32F($buffer) => DynamicImage::ImageRgba32F($body),
+            image => {
+                let $buffer = &image.to_rgba32f();
+                DynamicImage::ImageRgba32F($body)
+            }
+        }
+    };
+}
+pub(crate) use map_typed_image;
+
+//行ごとに別のスレッドでfを呼ぶ fには(x, y, 画素)を渡す
+pub fn par_pixels_mut<P, F>(buffer: &mut TypedBuffer<P>, f: F)
+where
+    P: TypedPixel,
+    F: Fn(u32, u32, &mut P) + Sync,
+{
+    let channel_count = P::CHANNEL_COUNT as usize;
+    let row_length = buffer.width() as usize * channel_count;
+    if row_length == 0 {
+        return;
+    }
+
+    buffer
+        .par_chunks_mut(row_length)
+        .enumerate()
+        .for_each(|(y, row)| {
+            for (x, pixel) in row.chunks_exact_mut(channel_count).enumerate() {
+                f(x as u32, y as u32, P::from_slice_mut(pixel));
+            }
+        });
+}
+
+//8bitのRGBAの色をPの色の型に直す(塗りつぶしの色などに使う)
+pub fn pixel_from_rgba8<P: TypedPixel>(color: Rgba<u8>) -> P {
+    let image = DynamicImage::ImageRgba8(ImageBuffer::from_pixel(1, 1, color));
+    *P::buffer_from(&image).get_pixel(0, 0)
+}
+
+pub fn to_f64<S: Primitive>(value: S) -> f64 {
+    <f64 as NumCast>::from(value).unwrap_or(0.0)
+}
+
+//0~色の型の最大値(16bitなら65535、32Fなら1.0)に収めて戻す
+pub fn from_f64<S: Primitive>(value: f64) -> S {
+    let max = to_f64(S::DEFAULT_MAX_VALUE);
+    let value = value.clamp(0.0, max);
+    let value = if max > 1.0 { value.round() } else { value };
+    NumCast::from(value).unwrap_or(S::DEFAULT_MIN_VALUE)
+}
+
+pub fn color_channel_count<P: TypedPixel>() -> usize {
+    if P::HAS_ALPHA {
+        P::CHANNEL_COUNT as usize - 1
+    } else {
+        P::CHANNEL_COUNT as usize
+    }
+}
+
+//色とアルファを0~1にして、色にアルファを掛けたもの(最後がアルファ)
+pub fn premultiply<P: TypedPixel>(pixel: &P) -> Vec<f64> {
+    let max = to_f64(P::Subpixel::DEFAULT_MAX_VALUE);
+    let channels = pixel.channels();
+    let alpha = if P::HAS_ALPHA {
+        to_f64(channels[channels.len() - 1]) / max
+    } else {
+        1.0
+    };
+
+    let mut premultiplied: Vec<f64> = channels[..color_channel_count::<P>()]
+        .iter()
+        .map(|value| to_f64(*value) / max * alpha)
+        .collect();
+    premultiplied.push(alpha);
+    premultiplied
+}
+
+//premultiplyの逆 アルファが0なら色も0にする
+pub fn unpremultiply<P: TypedPixel>(pixel: &mut P, premultiplied: &[f64]) {
+    let max = to_f64(P::Subpixel::DEFAULT_MAX_VALUE);
+    let color_count = color_channel_count::<P>();
+    let alpha = premultiplied[premultiplied.len() - 1];
+    let channels = pixel.channels_mut();
+
+    for (channel, value) in channels
+        .iter_mut()
+        .zip(premultiplied.iter())
+        .take(color_count)
+    {
+        *channel = if alpha > 0.0 {
+            from_f64(value / alpha * max)
+        } else {
+            from_f64(0.0)
+        };
+    }
+    if P::HAS_ALPHA {
+        channels[color_count] = from_f64(alpha * max);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn map_typed_image_test() {
+        let image =
+            DynamicImage::ImageRgb16(ImageBuffer::from_pixel(2, 2, Rgb([1000, 40000, 65535])));
+        let mapped = map_typed_image!(&image, buffer => buffer.clone());
+
+        assert_eq!(mapped.color(), image.color());
+        assert_eq!(mapped.as_bytes(), image.as_bytes());
+    }
+
+    #[test]
+    fn clamp_test() {
+        assert_eq!(from_f64::<u8>(300.0), 255);
+        assert_eq!(from_f64::<u16>(65535.4), 65535);
+        assert_eq!(from_f64::<f32>(1.5), 1.0);
+        assert_eq!(from_f64::<f32>(0.25), 0.25);
+    }
+}
@@ -0,0 +1,171 @@
+use std::sync::{
+    atomic::{AtomicBool, AtomicU32, Ordering},
+    Arc,
+};
+
+use image::{imageops, DynamicImage, ImageBuffer};
+
+use super::pixel_type::{map_typed_image, TypedBuffer, TypedPixel};
+use crate::coffee_image::error::Error;
+
+//進み具合を報告する単位(行数)
+pub const BAND_HEIGHT: u32 = 64;
+
+//変換の進み具合(処理した元画像の行数)と中断の指示を別スレッドと共有する
+#[derive(Debug, Clone, Default)]
+pub struct Progress {
+    state: Arc<ProgressState>,
+}
+
+#[derive(Debug, Default)]
+struct ProgressState {
+    done_rows: AtomicU32,
+    total_rows: AtomicU32,
+    cancelled: AtomicBool,
+}
+
+impl Progress {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn start(&self, total_rows: u32) {
+        self.state.done_rows.store(0, Ordering::Relaxed);
+        self.state.total_rows.store(total_rows, Ordering::Relaxed);
+    }
+
+    //中断されていたらErr(Cancelled)を返すので、処理側は?で抜ける
+    pub fn advance(&self, rows: u32) -> Result<(), Error> {
+        self.state.done_rows.fetch_add(rows, Ordering::Relaxed);
+        self.check()
+    }
+
+    pub fn check(&self) -> Result<(), Error> {
+        if self.is_cancelled() {
+            Err(Error::Cancelled)
+        } else {
+            Ok(())
+        }
+    }
+
+    pub fn cancel(&self) {
+        self.state.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.state.cancelled.load(Ordering::Relaxed)
+    }
+
+    pub fn rows(&self) -> (u32, u32) {
+        (
+            self.state.done_rows.load(Ordering::Relaxed),
+            self.state.total_rows.load(Ordering::Relaxed),
+        )
+    }
+
+    //0.0~1.0
+    pub fn fraction(&self) -> f32 {
+        match self.rows() {
+            (_, 0) => 0.0,
+            (done, total) => (done as f32 / total as f32).min(1.0),
+        }
+    }
+}
+
+//(上端の行, 行数)
+pub fn bands(height: u32) -> impl Iterator<Item = (u32, u32)> {
+    (0..height)
+        .step_by(BAND_HEIGHT as usize)
+        .map(move |top| (top, BAND_HEIGHT.min(height - top)))
+}
+
+//画像を横長の帯に分けてfをかけ、つなぎ直す
+//marginは帯の上下に余分に渡す行数(ぼかしのように周りの画素を使う処理用) fには(帯の画像, 帯の上端の行)を渡す
+pub fn map_bands<F>(
+    image: &DynamicImage,
+    margin: u32,
+    progress: &Progress,
+    f: F,
+) -> Result<DynamicImage, Error>
+where
+    F: Fn(&DynamicImage, u32) -> DynamicImage,
+{
+    let (width, height) = (image.width(), image.height());
+    let results = bands(height)
+        .map(|(top, rows)| {
+            let margin_top = top.saturating_sub(margin);
+            let margin_bottom = (top + rows + margin).min(height);
+            let band = image.crop_imm(0, margin_top, width, margin_bottom - margin_top);
+
+            let result = f(&band, margin_top).crop_imm(0, top - margin_top, width, rows);
+            progress.advance(rows)?;
+            Ok(result)
+        })
+        .collect::<Result<Vec<_>, Error>>()?;
+
+    Ok(stitch_bands(results))
+}
+
+//上から順に縦につなぐ 色の型は最初の帯に合わせる
+pub fn stitch_bands(bands: Vec<DynamicImage>) -> DynamicImage {
+    let Some(first) = bands.first() else {
+        return DynamicImage::new_rgba8(0, 0);
+    };
+    map_typed_image!(first, buffer => stitch_buffers(buffer, &bands))
+}
+
+fn stitch_buffers<P: TypedPixel>(_like: &TypedBuffer<P>, bands: &[DynamicImage]) -> TypedBuffer<P> {
+    let width = bands.iter().map(|band| band.width()).max().unwrap_or(0);
+    let height = bands.iter().map(|band| band.height()).sum();
+    let mut stitched = ImageBuffer::new(width, height);
+
+    let mut top = 0;
+    for band in bands {
+        imageops::replace(&mut stitched, &P::buffer_from(band), 0, top as i64);
+        top += band.height();
+    }
+    stitched
+}
+
+#[cfg(test)]
+mod test {
+    use image::{Luma, Rgb};
+
+    use super::*;
+
+    #[test]
+    fn map_bands_test() {
+        let image = DynamicImage::ImageRgb16(ImageBuffer::from_fn(30, 150, |x, y| {
+            Rgb([x as u16 * 1000, y as u16 * 400, 65535])
+        }));
+        let progress = Progress::new();
+        progress.start(image.height());
+
+        //周りを使う処理でも帯に分けない場合と同じになる
+        let blurred = map_bands(&image, 8, &progress, |band, _| band.blur(2.0)).unwrap();
+        assert_eq!(blurred.color(), image.color());
+        assert_eq!(blurred.as_bytes(), image.blur(2.0).as_bytes());
+        assert_eq!(progress.rows(), (150, 150));
+        assert_eq!(progress.fraction(), 1.0);
+
+        let rows = map_bands(&image, 0, &progress, |band, top| {
+            DynamicImage::ImageLuma8(ImageBuffer::from_fn(band.width(), band.height(), |_, y| {
+                Luma([(top + y) as u8])
+            }))
+        })
+        .unwrap()
+        .to_luma8();
+        assert!((0..150).all(|y| rows.get_pixel(0, y)[0] == y as u8));
+    }
+
+    #[test]
+    fn cancel_test() {
+        let image = DynamicImage::new_rgb8(10, 200);
+        let progress = Progress::new();
+        progress.cancel();
+
+        let result = map_bands(&image, 0, &progress, |band, _| band.clone());
+        assert!(matches!(result, Err(Error::Cancelled)));
+        assert_eq!(progress.rows().0, BAND_HEIGHT);
+    }
+}
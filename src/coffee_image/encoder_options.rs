@@ -0,0 +1,185 @@
+use std::fmt::Display;
+
+use image::codecs::png::{CompressionType, FilterType};
+use jpeg_encoder::SamplingFactor;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EncoderOptions {
+    pub jpeg_quality: u8,
+    pub jpeg_subsampling: ChromaSubsampling,
+    pub png_compression: PngCompression,
+    pub png_filter: PngFilter,
+    pub webp_lossless: bool,
+    pub webp_quality: u8,
+    pub tiff_compression: TiffCompression,
+}
+
+impl Default for EncoderOptions {
+    fn default() -> Self {
+        Self {
+            jpeg_quality: 75,
+            jpeg_subsampling: ChromaSubsampling::default(),
+            png_compression: PngCompression::default(),
+            png_filter: PngFilter::default(),
+            webp_lossless: true,
+            webp_quality: 75,
+            tiff_compression: TiffCompression::default(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ChromaSubsampling {
+    Yuv444,
+    Yuv422,
+    #[default]
+    Yuv420,
+}
+
+impl ChromaSubsampling {
+    pub const ALL: [ChromaSubsampling; 3] = [
+        ChromaSubsampling::Yuv444,
+        ChromaSubsampling::Yuv422,
+        ChromaSubsampling::Yuv420,
+    ];
+
+    pub fn convert_to_sampling_factor(&self) -> SamplingFactor {
+        match self {
+            ChromaSubsampling::Yuv444 => SamplingFactor::R_4_4_4,
+            ChromaSubsampling::Yuv422 => SamplingFactor::R_4_2_2,
+            ChromaSubsampling::Yuv420 => SamplingFactor::R_4_2_0,
+        }
+    }
+}
+
+impl Display for ChromaSubsampling {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                ChromaSubsampling::Yuv444 => "4:4:4",
+                ChromaSubsampling::Yuv422 => "4:2:2",
+                ChromaSubsampling::Yuv420 => "4:2:0",
+            }
+        )
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PngCompression {
+    Fast,
+    #[default]
+    Default,
+    Best,
+}
+
+impl PngCompression {
+    pub const ALL: [PngCompression; 3] =
+        [PngCompression::Fast, PngCompression::Default, PngCompression::Best];
+
+    pub fn convert_to_compression_type(&self) -> CompressionType {
+        match self {
+            PngCompression::Fast => CompressionType::Fast,
+            PngCompression::Default => CompressionType::Default,
+            PngCompression::Best => CompressionType::Best,
+        }
+    }
+}
+
+impl Display for PngCompression {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                PngCompression::Fast => "fast",
+                PngCompression::Default => "default",
+                PngCompression::Best => "best",
+            }
+        )
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PngFilter {
+    NoFilter,
+    Sub,
+    Up,
+    Avg,
+    Paeth,
+    #[default]
+    Adaptive,
+}
+
+impl PngFilter {
+    pub const ALL: [PngFilter; 6] = [
+        PngFilter::NoFilter,
+        PngFilter::Sub,
+        PngFilter::Up,
+        PngFilter::Avg,
+        PngFilter::Paeth,
+        PngFilter::Adaptive,
+    ];
+
+    pub fn convert_to_filter_type(&self) -> FilterType {
+        match self {
+            PngFilter::NoFilter => FilterType::NoFilter,
+            PngFilter::Sub => FilterType::Sub,
+            PngFilter::Up => FilterType::Up,
+            PngFilter::Avg => FilterType::Avg,
+            PngFilter::Paeth => FilterType::Paeth,
+            PngFilter::Adaptive => FilterType::Adaptive,
+        }
+    }
+}
+
+impl Display for PngFilter {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                PngFilter::NoFilter => "none",
+                PngFilter::Sub => "sub",
+                PngFilter::Up => "up",
+                PngFilter::Avg => "avg",
+                PngFilter::Paeth => "paeth",
+                PngFilter::Adaptive => "adaptive",
+            }
+        )
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TiffCompression {
+    Uncompressed,
+    #[default]
+    Lzw,
+    Deflate,
+    Packbits,
+}
+
+impl TiffCompression {
+    pub const ALL: [TiffCompression; 4] = [
+        TiffCompression::Uncompressed,
+        TiffCompression::Lzw,
+        TiffCompression::Deflate,
+        TiffCompression::Packbits,
+    ];
+}
+
+impl Display for TiffCompression {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                TiffCompression::Uncompressed => "uncompressed",
+                TiffCompression::Lzw => "lzw",
+                TiffCompression::Deflate => "deflate",
+                TiffCompression::Packbits => "packbits",
+            }
+        )
+    }
+}
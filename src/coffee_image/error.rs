@@ -1,32 +1,214 @@
-use std::{io, num::{ParseFloatError}};
+use std::{
+    fmt::{self, Display},
+    io,
+    num::ParseFloatError,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
 
-use super::io::dialog::error_dialog_show;
+use super::save_format::SaveFormat;
 
-//use image::ImageError;
+//元のエラーはArcで持つ(GUIのMessageに載せるためにCloneが必要)
+pub type Source = Arc<dyn std::error::Error + Send + Sync>;
 
-
-#[derive(Debug,Clone)]
-pub enum Error{
+#[derive(Debug, Clone)]
+pub enum Error {
     DialogClosed,
-    IOFailed(io::ErrorKind),
+    Cancelled,
+    IOFailed(Arc<io::Error>),
     ParseError(ParseFloatError),
-    ImageError(String),
-    WidthHeightNotEqualError,
+    ImageError(Source),
+    UnsupportedAnimationFormat(SaveFormat),
+    MissingInput(Input),
+    //パイプラインやコマンドラインで読めなかった値
+    InvalidParameter {
+        name: String,
+        value: String,
+    },
+    UnknownOperation(String),
+    //JSONやTOMLとして読めないファイル
+    InvalidFile(Source),
+    WidthHeightNotEqualError {
+        expected: (u32, u32),
+        actual: (u32, u32),
+    },
+    WithContext {
+        context: Context,
+        source: Box<Error>,
+    },
+}
+
+//選ばれていない入力
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Input {
+    Image,
+    SecondImage,
+    TextFile,
+    ConvertedImage,
+    Color,
+}
+
+//どのファイル、どの処理で起きたか
+#[derive(Debug, Clone, PartialEq)]
+pub enum Context {
+    Open(PathBuf),
+    Save(PathBuf),
+    Operation(String),
 }
-impl Into<String> for Error{
-    fn into(self) -> String {
+
+impl Error {
+    //imageクレート以外(png, gif, tiff, exifなど)のエラーもImageErrorとして包む
+    pub fn image<E: std::error::Error + Send + Sync + 'static>(error: E) -> Error {
+        Error::ImageError(Arc::new(error))
+    }
+
+    pub fn invalid_file<E: std::error::Error + Send + Sync + 'static>(error: E) -> Error {
+        Error::InvalidFile(Arc::new(error))
+    }
+
+    pub fn context(self, context: Context) -> Error {
+        Error::WithContext {
+            context,
+            source: Box::new(self),
+        }
+    }
+
+    //contextを外した一番元のエラー
+    pub fn root(&self) -> &Error {
         match self {
-            Error::DialogClosed => "DialogClosed".to_string(),
-            Error::IOFailed(kind) => kind.to_string(),
-            Error::ParseError(_kind) => "数値を入力してください".to_string(),
-            Error::ImageError(kind) => kind,
-            Error::WidthHeightNotEqualError=>"widthとheightの値が等しくありません".to_string(),
+            Error::WithContext { source, .. } => source.root(),
+            error => error,
         }
     }
+
+    pub fn is_dialog_closed(&self) -> bool {
+        matches!(self.root(), Error::DialogClosed)
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        matches!(self.root(), Error::Cancelled)
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(error: io::Error) -> Self {
+        Error::IOFailed(Arc::new(error))
+    }
 }
-impl Error{
-    pub fn show_dialog_return_default<T:Default>(&self) ->T{
-        error_dialog_show(self.clone());
-        T::default()
+
+impl From<image::ImageError> for Error {
+    fn from(error: image::ImageError) -> Self {
+        Error::image(error)
+    }
+}
+
+impl From<ParseFloatError> for Error {
+    fn from(error: ParseFloatError) -> Self {
+        Error::ParseError(error)
     }
-}
\ No newline at end of file
+}
+
+//ライブラリとしての素っ気ないメッセージ GUIでの表示文はGUI側で作る
+impl Display for Input {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Input::Image => write!(f, "image"),
+            Input::SecondImage => write!(f, "second image"),
+            Input::TextFile => write!(f, "text file"),
+            Input::ConvertedImage => write!(f, "converted image"),
+            Input::Color => write!(f, "picked color"),
+        }
+    }
+}
+
+impl Display for Context {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Context::Open(path) => write!(f, "failed to open {}", path.display()),
+            Context::Save(path) => write!(f, "failed to save {}", path.display()),
+            Context::Operation(name) => write!(f, "failed to {}", name),
+        }
+    }
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::DialogClosed => write!(f, "dialog closed"),
+            Error::Cancelled => write!(f, "cancelled"),
+            Error::IOFailed(error) => write!(f, "{}", error),
+            Error::ParseError(error) => write!(f, "invalid number: {}", error),
+            Error::ImageError(error) => write!(f, "{}", error),
+            Error::UnsupportedAnimationFormat(format) => {
+                write!(f, "animations cannot be saved as {}", format)
+            }
+            Error::MissingInput(input) => write!(f, "no {} selected", input),
+            Error::InvalidParameter { name, value } => write!(f, "invalid {}: {}", name, value),
+            Error::UnknownOperation(name) => write!(f, "unknown operation: {}", name),
+            Error::InvalidFile(error) => write!(f, "{}", error),
+            Error::WidthHeightNotEqualError { expected, actual } => write!(
+                f,
+                "image sizes differ ({}x{} and {}x{})",
+                expected.0, expected.1, actual.0, actual.1
+            ),
+            Error::WithContext { context, source } => write!(f, "{}: {}", context, source),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::IOFailed(error) => Some(error.as_ref()),
+            Error::ParseError(error) => Some(error),
+            Error::ImageError(error) => Some(error.as_ref()),
+            Error::InvalidFile(error) => Some(error.as_ref()),
+            Error::WithContext { source, .. } => Some(source.as_ref()),
+            _ => None,
+        }
+    }
+}
+
+//Result<T, E>にファイルや処理の情報を付ける
+pub trait ResultExt<T> {
+    fn open_context(self, path: &Path) -> Result<T, Error>;
+    fn save_context(self, path: &Path) -> Result<T, Error>;
+    fn operation_context(self, name: &str) -> Result<T, Error>;
+}
+
+impl<T, E: Into<Error>> ResultExt<T> for Result<T, E> {
+    fn open_context(self, path: &Path) -> Result<T, Error> {
+        self.map_err(|error| error.into().context(Context::Open(path.to_path_buf())))
+    }
+
+    fn save_context(self, path: &Path) -> Result<T, Error> {
+        self.map_err(|error| error.into().context(Context::Save(path.to_path_buf())))
+    }
+
+    fn operation_context(self, name: &str) -> Result<T, Error> {
+        self.map_err(|error| error.into().context(Context::Operation(name.to_string())))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::error::Error as _;
+
+    #[test]
+    fn source_chain_test() {
+        let path = PathBuf::from("missing.png");
+        let error = std::fs::read(&path).open_context(&path).unwrap_err();
+
+        assert!(matches!(error.root(), Error::IOFailed(_)));
+        assert!(error.to_string().contains("missing.png"));
+        let io_error = error.source().and_then(|source| source.source()).unwrap();
+        assert!(io_error.downcast_ref::<io::Error>().is_some());
+    }
+
+    #[test]
+    fn dialog_closed_test() {
+        let error = Error::DialogClosed.context(Context::Operation("save".to_string()));
+        assert!(error.is_dialog_closed());
+    }
+}
@@ -1,27 +1,73 @@
-use std::fs::{self, read_dir, remove_file};
-
 use std::io::prelude::Write;
 
-use std::{env, fs::File, path::PathBuf};
+use std::{fs::File, path::PathBuf};
 
+use crate::coffee_image::convert::animation::Animation;
 use crate::coffee_image::convert::image_wrap::ImageConverter;
 use crate::coffee_image::error::Error;
 use crate::coffee_image::save_format::{SaveFormat, self};
+use crate::coffee_image::text_format::TextFormat;
 
-const RESULT_FOLDER_NAME: &str = ".resultImages";
 const FILTER_NAME:&str ="image";
 
-pub async fn image_open() -> Result<PathBuf, Error> {
+pub async fn image_open(title: &str) -> Result<PathBuf, Error> {
+    let handle = rfd::AsyncFileDialog::new()
+        .set_title(title)
+        .add_filter(FILTER_NAME, &save_format::OPEN_EXTENSIONS)
+        .pick_file()
+        .await
+        .ok_or(Error::DialogClosed)?;
+
+    Ok(handle.path().to_owned())
+}
+pub async fn images_open(title: &str) -> Result<Vec<PathBuf>, Error> {
+    let handles = rfd::AsyncFileDialog::new()
+        .set_title(title)
+        .add_filter(FILTER_NAME, &save_format::OPEN_EXTENSIONS)
+        .pick_files()
+        .await
+        .ok_or(Error::DialogClosed)?;
+
+    Ok(handles.iter().map(|handle| handle.path().to_owned()).collect())
+}
+
+pub async fn text_open(title: &str) -> Result<PathBuf, Error> {
     let handle = rfd::AsyncFileDialog::new()
-        .set_title("画像パスを教えてください")
-        .add_filter(FILTER_NAME, &save_format::SAVEFORMATS)
+        .set_title(title)
+        .add_filter("text", &["txt"])
         .pick_file()
         .await
         .ok_or(Error::DialogClosed)?;
 
     Ok(handle.path().to_owned())
 }
+//レシピはjsonかtoml 中身の読み書きはGUI側で行う
+pub const RECIPE_EXTENSIONS: [&str; 2] = ["json", "toml"];
+
+pub async fn recipe_open(title: &str) -> Result<PathBuf, Error> {
+    let handle = rfd::AsyncFileDialog::new()
+        .set_title(title)
+        .add_filter("recipe", &RECIPE_EXTENSIONS)
+        .pick_file()
+        .await
+        .ok_or(Error::DialogClosed)?;
+
+    Ok(handle.path().to_owned())
+}
+
+pub async fn recipe_save_path(title: &str) -> Result<PathBuf, Error> {
+    let handle = rfd::AsyncFileDialog::new()
+        .set_title(title)
+        .add_filter("recipe", &RECIPE_EXTENSIONS)
+        .save_file()
+        .await
+        .ok_or(Error::DialogClosed)?;
+
+    Ok(handle.path().to_owned())
+}
+
 pub async fn save(
+    title: &str,
     path: Option<PathBuf>,
     temp_image_path: ImageConverter,
     save_format: SaveFormat,
@@ -30,54 +76,65 @@ pub async fn save(
         path
     } else {
         rfd::AsyncFileDialog::new()
-            .set_title("保存場所")
-            .add_filter(FILTER_NAME, &[save_format.to_string()])
+            .set_title(title)
+            .add_filter(FILTER_NAME, save_format.extensions())
             .save_file()
             .await
             .ok_or(Error::DialogClosed)
             .map(|handle| handle.path().to_owned())?
     };
 
-    temp_image_path.save_converted_image(&path, save_format);
+    temp_image_path.save_converted_image(&path, save_format)?;
     Ok(path)
 }
 
-pub fn get_result_folder() -> Result<PathBuf, Error> {
-    let mut result_folder_path = env::current_dir()
-        .map_err(|error| error.kind())
-        .map_err(Error::IOFailed)?;
-    result_folder_path.push(RESULT_FOLDER_NAME);
+//アニメーションはgif(GIF)かpng(APNG)でのみ保存できる
+pub async fn save_animation(
+    title: &str,
+    animation: Animation,
+    save_format: SaveFormat,
+    gif_speed: i32,
+) -> Result<PathBuf, Error> {
+    if save_format != SaveFormat::Gif && save_format != SaveFormat::Png {
+        return Err(Error::UnsupportedAnimationFormat(save_format));
+    }
+    let path = rfd::AsyncFileDialog::new()
+        .set_title(title)
+        .add_filter(FILTER_NAME, save_format.extensions())
+        .save_file()
+        .await
+        .ok_or(Error::DialogClosed)
+        .map(|handle| handle.path().to_owned())?;
 
-    Ok(result_folder_path)
+    if save_format == SaveFormat::Gif {
+        animation.save_gif(&path, gif_speed)?;
+    } else {
+        animation.save_apng(&path)?;
+    }
+    Ok(path)
 }
 
-pub fn remove_all_temp_file() {
-    let temp_dir = get_result_folder().unwrap();
-    let read_dir = read_dir(temp_dir);
-    let dir = read_dir.unwrap();
+pub async fn save_text(
+    title: &str,
+    content: String,
+    text_format: TextFormat,
+) -> Result<PathBuf, Error> {
+    let path = rfd::AsyncFileDialog::new()
+        .set_title(title)
+        .add_filter(text_format.to_string(), &[text_format.extension()])
+        .save_file()
+        .await
+        .ok_or(Error::DialogClosed)
+        .map(|handle| handle.path().to_owned())?;
 
-    for dir_entry in dir.into_iter() {
-        let file_path = dir_entry.unwrap().path();
-        let _ = remove_file(file_path).map_err(|error| {
-        });
-    }
+    let mut file = File::create(&path)?;
+    file.write_all(content.as_bytes())?;
+    Ok(path)
 }
 
-pub fn mkdir_result_temp_folder() -> Result<(), Error> {
-    let result_temp_dir = get_result_folder()?;
-
-    if let Ok(metadata) = fs::metadata(&result_temp_dir) {
-        if metadata.is_dir() {
-            //fallthrough
-        } else {
-            let _ = fs::create_dir(result_temp_dir)
-                .map_err(|error| error.kind())
-                .map_err(Error::IOFailed)?;
-        }
-    } else {
-        let _ = fs::create_dir(result_temp_dir)
-            .map_err(|error| error.kind())
-            .map_err(Error::IOFailed)?;
-    }
-    Ok(())
+pub async fn estimate_size(
+    image_converter: ImageConverter,
+    save_format: SaveFormat,
+) -> Result<usize, Error> {
+    image_converter.estimate_encoded_size(save_format)
 }
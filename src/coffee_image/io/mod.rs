@@ -1,3 +1,3 @@
 pub mod coffee_image_io;
 pub mod text;
-pub mod dialog;
\ No newline at end of file
+pub mod temp_session;
\ No newline at end of file
@@ -0,0 +1,185 @@
+use std::{
+    env,
+    fs::{self, OpenOptions},
+    io::ErrorKind,
+    path::PathBuf,
+    process,
+    sync::{
+        atomic::{AtomicU32, Ordering},
+        Mutex, OnceLock,
+    },
+    time::{Duration, SystemTime},
+};
+
+use crate::coffee_image::{error::Error, rng::generate_strings};
+
+const SESSION_PREFIX: &str = "coffee_image-";
+//これより古いセッションのフォルダはクラッシュなどで残ったものとして消す
+pub const STALE_SESSION_AGE: Duration = Duration::from_secs(24 * 60 * 60);
+
+static SESSION: OnceLock<TempSession> = OnceLock::new();
+
+//起動ごとにOSの一時フォルダの下にフォルダを作り、このセッションで作ったファイルだけを記録する
+#[derive(Debug)]
+pub struct TempSession {
+    dir: PathBuf,
+    counter: AtomicU32,
+    created_files: Mutex<Vec<PathBuf>>,
+}
+
+impl TempSession {
+    pub fn new(root: PathBuf) -> Self {
+        let dir_name = format!("{}{}-{}", SESSION_PREFIX, process::id(), generate_strings());
+
+        Self {
+            dir: root.join(dir_name),
+            counter: AtomicU32::new(0),
+            created_files: Mutex::new(Vec::new()),
+        }
+    }
+
+    pub fn dir(&self) -> &PathBuf {
+        &self.dir
+    }
+
+    //名前が重ならないように空のファイルを作ってからパスを返す
+    pub fn create_temp_path(&self, extension: &str) -> Result<PathBuf, Error> {
+        fs::create_dir_all(&self.dir)?;
+
+        loop {
+            let number = self.counter.fetch_add(1, Ordering::Relaxed);
+            let path = self
+                .dir
+                .join(format!("{:04}-{}.{}", number, generate_strings(), extension));
+
+            match OpenOptions::new().write(true).create_new(true).open(&path) {
+                Ok(_) => {
+                    if let Ok(mut created_files) = self.created_files.lock() {
+                        created_files.push(path.clone());
+                    }
+                    return Ok(path);
+                }
+                Err(error) if error.kind() == ErrorKind::AlreadyExists => continue,
+                Err(error) => return Err(Error::from(error)),
+            }
+        }
+    }
+
+    //自分で作ったファイルだけを消し、空になったらフォルダも消す
+    pub fn remove_created_files(&self) {
+        if let Ok(mut created_files) = self.created_files.lock() {
+            for path in created_files.drain(..) {
+                let _ = fs::remove_file(path);
+            }
+        }
+        let _ = fs::remove_dir(&self.dir);
+    }
+}
+
+fn session() -> &'static TempSession {
+    SESSION.get_or_init(|| TempSession::new(env::temp_dir()))
+}
+
+pub fn create_temp_path(extension: &str) -> Result<PathBuf, Error> {
+    session().create_temp_path(extension)
+}
+
+pub fn remove_session_files() {
+    session().remove_created_files();
+}
+
+//main()で1つだけ持ち、捨てたときにこのセッションのファイルを消す
+//Cloneできないのでアプリの状態をコピーして捨てても消えない
+#[derive(Debug, Default)]
+pub struct SessionGuard(());
+
+impl Drop for SessionGuard {
+    fn drop(&mut self) {
+        remove_session_files();
+    }
+}
+
+pub fn sweep_stale_sessions() -> usize {
+    sweep_stale_sessions_in(&env::temp_dir(), STALE_SESSION_AGE)
+}
+
+//前回までの起動で消されずに残ったセッションのフォルダを消して、消した数を返す
+pub fn sweep_stale_sessions_in(root: &PathBuf, max_age: Duration) -> usize {
+    let Ok(entries) = fs::read_dir(root) else {
+        return 0;
+    };
+    let now = SystemTime::now();
+
+    entries
+        .flatten()
+        .filter(|entry| entry.file_name().to_string_lossy().starts_with(SESSION_PREFIX))
+        .filter(|entry| Some(entry.path()) != SESSION.get().map(|session| session.dir.clone()))
+        .filter(|entry| {
+            entry
+                .metadata()
+                .and_then(|metadata| metadata.modified())
+                .map(|modified| now.duration_since(modified).unwrap_or_default() >= max_age)
+                .unwrap_or(false)
+        })
+        .filter(|entry| entry.file_type().map(|file_type| file_type.is_dir()).unwrap_or(false))
+        .filter(|entry| fs::remove_dir_all(entry.path()).is_ok())
+        .count()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn test_root(name: &str) -> PathBuf {
+        let root = env::temp_dir().join(format!("coffee_image_temp_test_{}", name));
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(&root).unwrap();
+        root
+    }
+
+    #[test]
+    fn create_and_cleanup_test() {
+        let root = test_root("cleanup");
+        let session = TempSession::new(root.clone());
+
+        let paths: Vec<PathBuf> = (0..20)
+            .map(|_| session.create_temp_path("png").unwrap())
+            .collect();
+        let mut unique = paths.clone();
+        unique.sort();
+        unique.dedup();
+        assert_eq!(unique.len(), paths.len());
+
+        for path in paths.iter() {
+            assert!(path.starts_with(session.dir()));
+            let file_name = path.file_name().unwrap().to_string_lossy().to_string();
+            assert!(file_name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '.'));
+        }
+
+        //セッションが作っていないファイルは残す
+        let other_file = session.dir().join("other.txt");
+        fs::write(&other_file, "keep").unwrap();
+        session.remove_created_files();
+
+        assert!(paths.iter().all(|path| !path.exists()));
+        assert!(other_file.exists());
+        let _ = fs::remove_dir_all(root);
+    }
+
+    #[test]
+    fn sweep_test() {
+        let root = test_root("sweep");
+        let stale = TempSession::new(root.clone());
+        stale.create_temp_path("png").unwrap();
+        let unrelated = root.join("unrelated");
+        fs::create_dir_all(&unrelated).unwrap();
+
+        assert_eq!(sweep_stale_sessions_in(&root, STALE_SESSION_AGE), 0);
+        assert_eq!(sweep_stale_sessions_in(&root, Duration::ZERO), 1);
+        assert!(!stale.dir().exists());
+        assert!(unrelated.exists());
+        let _ = fs::remove_dir_all(root);
+    }
+}
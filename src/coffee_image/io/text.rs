@@ -1,9 +1,9 @@
 use std::{path::PathBuf, io::BufWriter, fs::File};
 use std::io::prelude::Read;
 
-use crate::coffee_image::{rng::generate_strings, error::Error};
+use crate::coffee_image::error::{Error, ResultExt};
 
-use super::coffee_image_io::get_result_folder;
+use super::temp_session::create_temp_path;
 
 #[derive(Debug,Default)]
 pub struct TextFile {
@@ -11,19 +11,15 @@ pub struct TextFile {
 }
 
 impl TextFile {
-    pub fn new() ->(Self,BufWriter<File>)   {
-        let temp_text_file_name = format!("{}.txt", generate_strings());
-
-        let path = get_result_folder().map(|mut save_path| {
-            save_path.push(temp_text_file_name);
-            save_path
-        });
-        (
-            Self{
-                save_temp_path:path.clone().unwrap(),
+    pub fn new() -> Result<(Self, BufWriter<File>), Error> {
+        let path = create_temp_path("txt")?;
+        let file = File::create(&path).save_context(&path)?;
+        Ok((
+            Self {
+                save_temp_path: path,
             },
-            BufWriter::new(File::create(path.unwrap()).unwrap()),
-        )
+            BufWriter::new(file),
+        ))
     }
 
     pub fn get_result_text_file(&self)->PathBuf{
@@ -32,10 +28,9 @@ impl TextFile {
     
     pub fn read_text_file(&self) -> Result<String, Error> {
         let mut content = String::new();
-        let _ = File::open(&self.save_temp_path)
-            .map_err(|error| error.kind())
-            .map_err(Error::IOFailed)?
-            .read_to_string(&mut content);
+        File::open(&self.save_temp_path)
+            .and_then(|mut file| file.read_to_string(&mut content))
+            .open_context(&self.save_temp_path)?;
 
         Ok(content)
     }
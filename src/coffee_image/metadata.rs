@@ -0,0 +1,495 @@
+use std::{
+    fmt::Display,
+    fs::File,
+    io::{BufReader, Cursor},
+    path::PathBuf,
+};
+
+use exif::{experimental::Writer, Context, Field, In, Reader, Tag, Value};
+use image::DynamicImage;
+use img_parts::{riff::RiffContent, webp::CHUNK_EXIF, Bytes, DynImage, ImageEXIF};
+
+use super::{
+    error::{Error, ResultExt},
+    save_format::SaveFormat,
+};
+
+const ORIENTATION_NORMAL: u32 = 1;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MetadataCategory {
+    Camera,
+    Exposure,
+    Date,
+    Gps,
+}
+
+impl MetadataCategory {
+    pub const ALL: [MetadataCategory; 4] = [
+        MetadataCategory::Camera,
+        MetadataCategory::Exposure,
+        MetadataCategory::Date,
+        MetadataCategory::Gps,
+    ];
+
+    //どれにも当てはまらないタグ(解像度など)はNone
+    pub fn of(tag: Tag) -> Option<MetadataCategory> {
+        if tag.context() == Context::Gps {
+            return Some(MetadataCategory::Gps);
+        }
+        match tag {
+            Tag::Make
+            | Tag::Model
+            | Tag::LensMake
+            | Tag::LensModel
+            | Tag::LensSerialNumber
+            | Tag::BodySerialNumber
+            | Tag::CameraOwnerName
+            | Tag::Software
+            | Tag::MakerNote => Some(MetadataCategory::Camera),
+            Tag::ExposureTime
+            | Tag::FNumber
+            | Tag::ExposureProgram
+            | Tag::PhotographicSensitivity
+            | Tag::ExposureBiasValue
+            | Tag::MeteringMode
+            | Tag::Flash
+            | Tag::FocalLength
+            | Tag::FocalLengthIn35mmFilm
+            | Tag::WhiteBalance => Some(MetadataCategory::Exposure),
+            Tag::DateTime
+            | Tag::DateTimeOriginal
+            | Tag::DateTimeDigitized
+            | Tag::OffsetTime
+            | Tag::OffsetTimeOriginal
+            | Tag::OffsetTimeDigitized
+            | Tag::SubSecTime
+            | Tag::SubSecTimeOriginal
+            | Tag::SubSecTimeDigitized => Some(MetadataCategory::Date),
+            _ => None,
+        }
+    }
+}
+
+impl Display for MetadataCategory {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                MetadataCategory::Camera => "camera",
+                MetadataCategory::Exposure => "exposure",
+                MetadataCategory::Date => "date",
+                MetadataCategory::Gps => "GPS",
+            }
+        )
+    }
+}
+
+//情報パネルの項目
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MetadataField {
+    Camera,
+    Lens,
+    Exposure,
+    FNumber,
+    Iso,
+    FocalLength,
+    Date,
+    Gps,
+    Orientation,
+}
+
+impl MetadataField {
+    pub const ALL: [MetadataField; 9] = [
+        MetadataField::Camera,
+        MetadataField::Lens,
+        MetadataField::Exposure,
+        MetadataField::FNumber,
+        MetadataField::Iso,
+        MetadataField::FocalLength,
+        MetadataField::Date,
+        MetadataField::Gps,
+        MetadataField::Orientation,
+    ];
+}
+
+impl Display for MetadataField {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                MetadataField::Camera => "Camera",
+                MetadataField::Lens => "Lens",
+                MetadataField::Exposure => "Exposure",
+                MetadataField::FNumber => "F",
+                MetadataField::Iso => "ISO",
+                MetadataField::FocalLength => "Focal length",
+                MetadataField::Date => "Date",
+                MetadataField::Gps => "GPS",
+                MetadataField::Orientation => "Orientation",
+            }
+        )
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum MetadataPolicy {
+    #[default]
+    Preserve,
+    Strip,
+    Custom,
+}
+
+impl MetadataPolicy {
+    pub const ALL: [MetadataPolicy; 3] = [
+        MetadataPolicy::Preserve,
+        MetadataPolicy::Strip,
+        MetadataPolicy::Custom,
+    ];
+}
+
+impl Display for MetadataPolicy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                MetadataPolicy::Preserve => "keep metadata",
+                MetadataPolicy::Strip => "strip metadata",
+                MetadataPolicy::Custom => "remove selected",
+            }
+        )
+    }
+}
+
+//Customのときだけremovedのカテゴリを消す
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct MetadataOptions {
+    pub policy: MetadataPolicy,
+    pub removed: [bool; 4],
+}
+
+impl MetadataOptions {
+    pub fn is_removed(&self, category: MetadataCategory) -> bool {
+        let index = MetadataCategory::ALL
+            .iter()
+            .position(|all| *all == category)
+            .unwrap_or(0);
+        self.removed[index]
+    }
+
+    pub fn set_removed(&self, category: MetadataCategory, removed: bool) -> MetadataOptions {
+        let mut options = *self;
+        if let Some(index) = MetadataCategory::ALL
+            .iter()
+            .position(|all| *all == category)
+        {
+            options.removed[index] = removed;
+        }
+        options
+    }
+
+    pub fn keeps(&self, tag: Tag) -> bool {
+        match self.policy {
+            MetadataPolicy::Preserve => true,
+            MetadataPolicy::Strip => false,
+            MetadataPolicy::Custom => match MetadataCategory::of(tag) {
+                Some(category) => !self.is_removed(category),
+                None => true,
+            },
+        }
+    }
+}
+
+//読み込んだ画像のEXIF サムネイル(1枚目のIFD以外)は保存しない
+#[derive(Debug, Clone)]
+pub struct ImageMetadata {
+    fields: Vec<Field>,
+    little_endian: bool,
+}
+
+impl ImageMetadata {
+    //EXIFがない、または対応していない形式ならNone
+    pub fn read(path: &PathBuf) -> Option<ImageMetadata> {
+        let file = File::open(path).ok()?;
+        let exif = Reader::new()
+            .read_from_container(&mut BufReader::new(file))
+            .ok()?;
+
+        Some(ImageMetadata {
+            fields: exif
+                .fields()
+                .filter(|field| field.ifd_num == In::PRIMARY)
+                .cloned()
+                .collect(),
+            little_endian: exif.little_endian(),
+        })
+    }
+
+    pub fn from_fields(fields: Vec<Field>) -> ImageMetadata {
+        ImageMetadata {
+            fields,
+            little_endian: false,
+        }
+    }
+
+    fn get_field(&self, tag: Tag) -> Option<&Field> {
+        self.fields.iter().find(|field| field.tag == tag)
+    }
+
+    pub fn orientation(&self) -> u32 {
+        self.get_field(Tag::Orientation)
+            .and_then(|field| field.value.get_uint(0))
+            .unwrap_or(ORIENTATION_NORMAL)
+    }
+
+    //情報パネルに出す主な項目 (項目, 値) 項目名は表示する側で付ける
+    pub fn summary(&self) -> Vec<(MetadataField, String)> {
+        let display = |tag: Tag| {
+            self.get_field(tag).map(|field| {
+                field
+                    .display_value()
+                    .to_string()
+                    .trim_matches('"')
+                    .to_string()
+            })
+        };
+        let mut summary = vec![
+            (
+                MetadataField::Camera,
+                display(Tag::Make)
+                    .into_iter()
+                    .chain(display(Tag::Model))
+                    .collect::<Vec<_>>()
+                    .join(" "),
+            ),
+            (
+                MetadataField::Lens,
+                display(Tag::LensModel).unwrap_or_default(),
+            ),
+            (
+                MetadataField::Exposure,
+                display(Tag::ExposureTime)
+                    .map(|value| format!("{} s", value))
+                    .unwrap_or_default(),
+            ),
+            (
+                MetadataField::FNumber,
+                display(Tag::FNumber).unwrap_or_default(),
+            ),
+            (
+                MetadataField::Iso,
+                display(Tag::PhotographicSensitivity).unwrap_or_default(),
+            ),
+            (
+                MetadataField::FocalLength,
+                display(Tag::FocalLength)
+                    .map(|value| format!("{} mm", value))
+                    .unwrap_or_default(),
+            ),
+            (
+                MetadataField::Date,
+                display(Tag::DateTimeOriginal)
+                    .or(display(Tag::DateTime))
+                    .unwrap_or_default(),
+            ),
+        ];
+
+        if let (Some(latitude), Some(longitude)) =
+            (display(Tag::GPSLatitude), display(Tag::GPSLongitude))
+        {
+            let latitude_ref = display(Tag::GPSLatitudeRef).unwrap_or_default();
+            let longitude_ref = display(Tag::GPSLongitudeRef).unwrap_or_default();
+            summary.push((
+                MetadataField::Gps,
+                format!(
+                    "{} {}, {} {}",
+                    latitude, latitude_ref, longitude, longitude_ref
+                ),
+            ));
+        }
+        summary.retain(|(_, value)| !value.is_empty());
+        summary.push((MetadataField::Orientation, self.orientation().to_string()));
+        summary
+    }
+
+    //画素は読み込み時に回転済みなのでOrientationは1にして書き出す
+    pub fn to_exif_bytes(&self, options: &MetadataOptions) -> Result<Option<Vec<u8>>, Error> {
+        let orientation = Field {
+            tag: Tag::Orientation,
+            ifd_num: In::PRIMARY,
+            value: Value::Short(vec![ORIENTATION_NORMAL as u16]),
+        };
+        let fields: Vec<&Field> = self
+            .fields
+            .iter()
+            .filter(|field| options.keeps(field.tag))
+            .map(|field| {
+                if field.tag == Tag::Orientation {
+                    &orientation
+                } else {
+                    field
+                }
+            })
+            .collect();
+        if fields.is_empty() {
+            return Ok(None);
+        }
+
+        let mut writer = Writer::new();
+        for field in fields {
+            writer.push_field(field);
+        }
+        let mut bytes = Cursor::new(Vec::new());
+        writer
+            .write(&mut bytes, self.little_endian)
+            .map_err(Error::image)
+            .operation_context("write EXIF")?;
+        Ok(Some(bytes.into_inner()))
+    }
+
+    //エンコード済みのjpeg/png/webpにEXIFを埋め込む それ以外の形式はそのまま返す
+    pub fn embed(
+        &self,
+        encoded: Vec<u8>,
+        save_format: SaveFormat,
+        options: &MetadataOptions,
+    ) -> Result<Vec<u8>, Error> {
+        if !save_format.supports_metadata() {
+            return Ok(encoded);
+        }
+        let exif = self.to_exif_bytes(options)?;
+        let image = DynImage::from_bytes(Bytes::from(encoded.clone()))
+            .map_err(Error::image)
+            .operation_context("embed EXIF")?;
+
+        match image {
+            Some(mut image) => {
+                image.set_exif(exif.clone().map(Bytes::from));
+                //img-partsはWebPにも"Exif\0\0"を付けるが、WebPの仕様ではTIFFヘッダから始まる
+                if let (DynImage::WebP(webp), Some(exif)) = (&mut image, exif) {
+                    for chunk in webp.chunks_mut() {
+                        if chunk.id() == CHUNK_EXIF {
+                            *chunk.content_mut() = RiffContent::Data(Bytes::from(exif.clone()));
+                        }
+                    }
+                }
+                let mut bytes = Vec::new();
+                image
+                    .encoder()
+                    .write_to(&mut bytes)?;
+                Ok(bytes)
+            }
+            None => Ok(encoded),
+        }
+    }
+}
+
+//EXIFのOrientation(1~8)に合わせて画素を回転・反転する
+pub fn apply_orientation(image: DynamicImage, orientation: u32) -> DynamicImage {
+    match orientation {
+        2 => image.fliph(),
+        3 => image.rotate180(),
+        4 => image.flipv(),
+        5 => image.rotate90().fliph(),
+        6 => image.rotate90(),
+        7 => image.rotate270().fliph(),
+        8 => image.rotate270(),
+        _ => image,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use image::{GenericImageView, Rgba, RgbaImage};
+
+    use super::*;
+    use crate::coffee_image::encoder_options::EncoderOptions;
+
+    fn test_fields() -> Vec<Field> {
+        let ascii = |tag: Tag, value: &str| Field {
+            tag,
+            ifd_num: In::PRIMARY,
+            value: Value::Ascii(vec![value.as_bytes().to_vec()]),
+        };
+        vec![
+            ascii(Tag::Make, "Coffee"),
+            ascii(Tag::Model, "Phone"),
+            ascii(Tag::DateTimeOriginal, "2023:10:01 12:00:00"),
+            ascii(Tag::GPSLatitudeRef, "N"),
+            Field {
+                tag: Tag::Orientation,
+                ifd_num: In::PRIMARY,
+                value: Value::Short(vec![6]),
+            },
+        ]
+    }
+
+    fn embed_and_read(save_format: SaveFormat, options: &MetadataOptions) -> Option<ImageMetadata> {
+        let image = DynamicImage::ImageRgb8(image::RgbImage::new(8, 4));
+        let encoded = save_format
+            .encode(&image, &EncoderOptions::default())
+            .unwrap();
+        let bytes = ImageMetadata::from_fields(test_fields())
+            .embed(encoded, save_format, options)
+            .unwrap();
+
+        let path = std::env::temp_dir().join(format!(
+            "coffee_image_metadata_test.{}",
+            save_format.extension()
+        ));
+        std::fs::write(&path, bytes).unwrap();
+        let metadata = ImageMetadata::read(&path);
+        let _ = std::fs::remove_file(path);
+        metadata
+    }
+
+    #[test]
+    fn orientation_test() {
+        let mut image = RgbaImage::new(3, 2);
+        image.put_pixel(0, 0, Rgba([255, 0, 0, 255]));
+        let image = DynamicImage::ImageRgba8(image);
+
+        //6は時計回りに90度回すと正しい向き
+        let rotated = apply_orientation(image.clone(), 6);
+        assert_eq!(rotated.dimensions(), (2, 3));
+        assert_eq!(rotated.get_pixel(1, 0), Rgba([255, 0, 0, 255]));
+        assert_eq!(apply_orientation(image.clone(), 1).dimensions(), (3, 2));
+        assert_eq!(
+            apply_orientation(image, 3).get_pixel(2, 1),
+            Rgba([255, 0, 0, 255])
+        );
+    }
+
+    #[test]
+    fn preserve_and_strip_test() {
+        for save_format in [SaveFormat::Jpeg, SaveFormat::Png, SaveFormat::WebP] {
+            let metadata = embed_and_read(save_format, &MetadataOptions::default()).unwrap();
+            assert!(metadata.get_field(Tag::Make).is_some());
+            assert_eq!(metadata.orientation(), ORIENTATION_NORMAL);
+
+            let stripped = MetadataOptions {
+                policy: MetadataPolicy::Strip,
+                ..Default::default()
+            };
+            assert!(embed_and_read(save_format, &stripped).is_none());
+        }
+    }
+
+    #[test]
+    fn selective_remove_test() {
+        let options = MetadataOptions {
+            policy: MetadataPolicy::Custom,
+            ..Default::default()
+        }
+        .set_removed(MetadataCategory::Gps, true)
+        .set_removed(MetadataCategory::Date, true);
+        let metadata = embed_and_read(SaveFormat::Jpeg, &options).unwrap();
+
+        assert!(metadata.get_field(Tag::Model).is_some());
+        assert!(metadata.get_field(Tag::GPSLatitudeRef).is_none());
+        assert!(metadata.get_field(Tag::DateTimeOriginal).is_none());
+    }
+}
@@ -3,4 +3,7 @@ pub mod convert;
 pub mod rng;
 pub mod string_art;
 pub mod io;
-pub mod save_format;
\ No newline at end of file
+pub mod save_format;
+pub mod encoder_options;
+pub mod text_format;
+pub mod metadata;
\ No newline at end of file
@@ -1,8 +1,9 @@
 use rand::Rng;
 
+//ファイル名に使うのでどのファイルシステムでも安全な英数字だけ
 const CHARSET: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZ\
                             abcdefghijklmnopqrstuvwxyz\
-                            0123456789)(*&^%$#@!~";
+                            0123456789";
 
 const NAME_LENGTH: usize = 8;
 
@@ -13,7 +14,7 @@ pub fn generate_strings() -> String {
         .map(|_| {
             let idx = rng.gen_range(0..CHARSET.len());
 
-            char::from(unsafe { *CHARSET.get_unchecked(idx) })
+            char::from(CHARSET[idx])
         })
         .collect();
 
@@ -1,32 +1,286 @@
 use std::fmt::Display;
+use std::fs;
+use std::io::Cursor;
+use std::path::PathBuf;
+
+use image::codecs::png::PngEncoder;
+use image::codecs::pnm::{PnmEncoder, PnmSubtype, SampleEncoding};
+use image::{imageops::FilterType, DynamicImage, ImageEncoder, ImageFormat, ImageOutputFormat};
+use tiff::encoder::{colortype, compression, TiffEncoder};
+
+use super::encoder_options::{EncoderOptions, TiffCompression};
+use super::error::{Error, ResultExt};
+
+//開くダイアログのフィルタ imageクレートでデコードできる拡張子
+pub const OPEN_EXTENSIONS: [&str; 21] = [
+    "png", "jpg", "jpeg", "gif", "webp", "bmp", "tif", "tiff", "ico", "pnm", "pbm", "pgm", "ppm",
+    "pam", "tga", "qoi", "ff", "exr", "hdr", "dds", "jfif",
+];
+
+//icoは256x256まで
+const ICO_MAX_SIZE: u32 = 256;
 
-use image::ImageFormat;
-pub const SAVEFORMATS:[&str;2] = ["png","jpg"];
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
 pub enum SaveFormat {
     #[default]
     Png,
     Jpeg,
-    // Gif,
-    // Avif,
+    Gif,
+    WebP,
+    Bmp,
+    Tiff,
+    Ico,
+    Pnm,
+    Tga,
+    Qoi,
+    Farbfeld,
+    OpenExr,
 }
 
 impl SaveFormat {
-    pub const ALL: [SaveFormat; 2] = [
+    pub const ALL: [SaveFormat; 12] = [
         SaveFormat::Png,
         SaveFormat::Jpeg,
-        // SaveFormat::Gif,
-        // SaveFormat::Avif,
+        SaveFormat::Gif,
+        SaveFormat::WebP,
+        SaveFormat::Bmp,
+        SaveFormat::Tiff,
+        SaveFormat::Ico,
+        SaveFormat::Pnm,
+        SaveFormat::Tga,
+        SaveFormat::Qoi,
+        SaveFormat::Farbfeld,
+        SaveFormat::OpenExr,
     ];
 
     pub fn convert_to_imageformat(&self) -> ImageFormat {
         match self {
             SaveFormat::Png => ImageFormat::Png,
             SaveFormat::Jpeg => ImageFormat::Jpeg,
-            // SaveFormat::Gif => ImageFormat::Gif,
-            // SaveFormat::Avif => ImageFormat::Avif,
+            SaveFormat::Gif => ImageFormat::Gif,
+            SaveFormat::WebP => ImageFormat::WebP,
+            SaveFormat::Bmp => ImageFormat::Bmp,
+            SaveFormat::Tiff => ImageFormat::Tiff,
+            SaveFormat::Ico => ImageFormat::Ico,
+            SaveFormat::Pnm => ImageFormat::Pnm,
+            SaveFormat::Tga => ImageFormat::Tga,
+            SaveFormat::Qoi => ImageFormat::Qoi,
+            SaveFormat::Farbfeld => ImageFormat::Farbfeld,
+            SaveFormat::OpenExr => ImageFormat::OpenExr,
+        }
+    }
+
+    //先頭が保存時に付ける拡張子
+    pub fn extensions(&self) -> &'static [&'static str] {
+        match self {
+            SaveFormat::Png => &["png"],
+            SaveFormat::Jpeg => &["jpg", "jpeg"],
+            SaveFormat::Gif => &["gif"],
+            SaveFormat::WebP => &["webp"],
+            SaveFormat::Bmp => &["bmp"],
+            SaveFormat::Tiff => &["tiff", "tif"],
+            SaveFormat::Ico => &["ico"],
+            SaveFormat::Pnm => &["ppm", "pgm", "pnm"],
+            SaveFormat::Tga => &["tga"],
+            SaveFormat::Qoi => &["qoi"],
+            SaveFormat::Farbfeld => &["ff"],
+            SaveFormat::OpenExr => &["exr"],
         }
     }
+
+    //拡張子から保存形式を決める コマンドラインの出力先に使う
+    pub fn from_path(path: &std::path::Path) -> Option<SaveFormat> {
+        let extension = path.extension()?.to_str()?.to_lowercase();
+        SaveFormat::ALL
+            .into_iter()
+            .find(|format| format.extensions().contains(&extension.as_str()))
+    }
+
+    pub fn extension(&self) -> &'static str {
+        self.extensions()[0]
+    }
+
+    //EXIFを埋め込める形式
+    pub fn supports_metadata(&self) -> bool {
+        matches!(self, SaveFormat::Jpeg | SaveFormat::Png | SaveFormat::WebP)
+    }
+
+    //各エンコーダが受け付ける色の型に変換する
+    pub fn prepare_image(&self, image: &DynamicImage) -> DynamicImage {
+        let is_16bit = image.color().bytes_per_pixel() / image.color().channel_count() > 1;
+        let has_alpha = image.color().has_alpha();
+
+        match self {
+            SaveFormat::Png | SaveFormat::Tiff => match image {
+                DynamicImage::ImageRgb32F(_) | DynamicImage::ImageRgba32F(_) => {
+                    DynamicImage::ImageRgba16(image.to_rgba16())
+                }
+                _ => image.clone(),
+            },
+            SaveFormat::Jpeg => match image {
+                DynamicImage::ImageLuma8(_) => image.clone(),
+                _ => DynamicImage::ImageRgb8(image.to_rgb8()),
+            },
+            SaveFormat::Pnm => match (image, is_16bit) {
+                (DynamicImage::ImageLuma8(_), _) | (DynamicImage::ImageLuma16(_), _) => {
+                    image.clone()
+                }
+                (_, true) => DynamicImage::ImageRgb16(image.to_rgb16()),
+                (_, false) => DynamicImage::ImageRgb8(image.to_rgb8()),
+            },
+            SaveFormat::Farbfeld => DynamicImage::ImageRgba16(image.to_rgba16()),
+            SaveFormat::OpenExr => match image {
+                DynamicImage::ImageRgb32F(_) | DynamicImage::ImageRgba32F(_) => image.clone(),
+                _ if has_alpha => DynamicImage::ImageRgba32F(image.to_rgba32f()),
+                _ => DynamicImage::ImageRgb32F(image.to_rgb32f()),
+            },
+            SaveFormat::Ico => {
+                let image = if image.width() > ICO_MAX_SIZE || image.height() > ICO_MAX_SIZE {
+                    image.resize(ICO_MAX_SIZE, ICO_MAX_SIZE, FilterType::Lanczos3)
+                } else {
+                    image.clone()
+                };
+                DynamicImage::ImageRgba8(image.to_rgba8())
+            }
+            SaveFormat::Gif
+            | SaveFormat::WebP
+            | SaveFormat::Bmp
+            | SaveFormat::Tga
+            | SaveFormat::Qoi => {
+                if has_alpha {
+                    DynamicImage::ImageRgba8(image.to_rgba8())
+                } else {
+                    DynamicImage::ImageRgb8(image.to_rgb8())
+                }
+            }
+        }
+    }
+
+    pub fn save(
+        &self,
+        image: &DynamicImage,
+        path: &PathBuf,
+        options: &EncoderOptions,
+    ) -> Result<(), Error> {
+        let bytes = self.encode(image, options).save_context(path)?;
+
+        fs::write(path, bytes).save_context(path)
+    }
+
+    //保存後のファイルサイズの見積もりにも使う
+    pub fn encode(&self, image: &DynamicImage, options: &EncoderOptions) -> Result<Vec<u8>, Error> {
+        let image = self.prepare_image(image);
+        let (width, height) = (image.width(), image.height());
+        let mut bytes = Vec::new();
+
+        match self {
+            SaveFormat::Png => PngEncoder::new_with_quality(
+                &mut bytes,
+                options.png_compression.convert_to_compression_type(),
+                options.png_filter.convert_to_filter_type(),
+            )
+            .write_image(image.as_bytes(), width, height, image.color())
+            .map_err(Error::image)?,
+            //imageクレート0.24のjpegエンコーダはサブサンプリングを選べないのでjpeg-encoderを使う
+            SaveFormat::Jpeg => {
+                let (width, height) = (
+                    u16::try_from(width).map_err(Error::image)?,
+                    u16::try_from(height).map_err(Error::image)?,
+                );
+                let color_type = match image {
+                    DynamicImage::ImageLuma8(_) => jpeg_encoder::ColorType::Luma,
+                    _ => jpeg_encoder::ColorType::Rgb,
+                };
+                let mut encoder = jpeg_encoder::Encoder::new(&mut bytes, options.jpeg_quality);
+                encoder.set_sampling_factor(options.jpeg_subsampling.convert_to_sampling_factor());
+                encoder
+                    .encode(image.as_bytes(), width, height, color_type)
+                    .map_err(Error::image)?;
+            }
+            //imageクレート0.24はwebpのデコードのみ対応なのでlibwebpでエンコードする
+            SaveFormat::WebP => {
+                let encoder = match &image {
+                    DynamicImage::ImageRgba8(buffer) => {
+                        webp::Encoder::from_rgba(buffer.as_raw(), width, height)
+                    }
+                    _ => webp::Encoder::from_rgb(image.as_bytes(), width, height),
+                };
+                let memory = if options.webp_lossless {
+                    encoder.encode_lossless()
+                } else {
+                    encoder.encode(options.webp_quality as f32)
+                };
+                bytes.extend_from_slice(&memory);
+            }
+            SaveFormat::Tiff => {
+                bytes = encode_tiff(&image, options.tiff_compression)
+                    .map_err(Error::image)?;
+            }
+            //save_with_formatだとPAM(P7)になるのでPGM/PPMで書き出す
+            SaveFormat::Pnm => {
+                let subtype = match image {
+                    DynamicImage::ImageLuma8(_) | DynamicImage::ImageLuma16(_) => {
+                        PnmSubtype::Graymap(SampleEncoding::Binary)
+                    }
+                    _ => PnmSubtype::Pixmap(SampleEncoding::Binary),
+                };
+                PnmEncoder::new(&mut bytes)
+                    .with_subtype(subtype)
+                    .write_image(image.as_bytes(), width, height, image.color())
+                    .map_err(Error::image)?;
+            }
+            _ => image
+                .write_to(
+                    &mut Cursor::new(&mut bytes),
+                    ImageOutputFormat::from(self.convert_to_imageformat()),
+                )
+                .map_err(Error::image)?,
+        }
+        Ok(bytes)
+    }
+}
+
+fn encode_tiff(image: &DynamicImage, tiff_compression: TiffCompression) -> tiff::TiffResult<Vec<u8>> {
+    match tiff_compression {
+        TiffCompression::Uncompressed => {
+            encode_tiff_with_compression(image, compression::Uncompressed)
+        }
+        TiffCompression::Lzw => encode_tiff_with_compression(image, compression::Lzw),
+        TiffCompression::Deflate => encode_tiff_with_compression(
+            image,
+            compression::Deflate::with_level(compression::DeflateLevel::Balanced),
+        ),
+        TiffCompression::Packbits => encode_tiff_with_compression(image, compression::Packbits),
+    }
+}
+
+fn encode_tiff_with_compression<D: compression::Compression>(
+    image: &DynamicImage,
+    compression: D,
+) -> tiff::TiffResult<Vec<u8>> {
+    let mut cursor = Cursor::new(Vec::new());
+    let mut encoder = TiffEncoder::new(&mut cursor)?;
+    let (width, height) = (image.width(), image.height());
+
+    match image {
+        DynamicImage::ImageLuma8(buffer) => encoder
+            .write_image_with_compression::<colortype::Gray8, _>(width, height, compression, buffer),
+        DynamicImage::ImageLuma16(buffer) => encoder
+            .write_image_with_compression::<colortype::Gray16, _>(width, height, compression, buffer),
+        DynamicImage::ImageRgb8(buffer) => encoder
+            .write_image_with_compression::<colortype::RGB8, _>(width, height, compression, buffer),
+        DynamicImage::ImageRgb16(buffer) => encoder
+            .write_image_with_compression::<colortype::RGB16, _>(width, height, compression, buffer),
+        DynamicImage::ImageRgba16(buffer) => encoder
+            .write_image_with_compression::<colortype::RGBA16, _>(width, height, compression, buffer),
+        _ => encoder.write_image_with_compression::<colortype::RGBA8, _>(
+            width,
+            height,
+            compression,
+            &image.to_rgba8(),
+        ),
+    }?;
+    Ok(cursor.into_inner())
 }
 
 impl Display for SaveFormat {
@@ -37,9 +291,79 @@ impl Display for SaveFormat {
             match self {
                 SaveFormat::Png => "png",
                 SaveFormat::Jpeg => "jpeg",
-                // SaveFormat::Gif => "gif",
-                // SaveFormat::Avif => "avif",
+                SaveFormat::Gif => "gif",
+                SaveFormat::WebP => "webp",
+                SaveFormat::Bmp => "bmp",
+                SaveFormat::Tiff => "tiff",
+                SaveFormat::Ico => "ico",
+                SaveFormat::Pnm => "pnm",
+                SaveFormat::Tga => "tga",
+                SaveFormat::Qoi => "qoi",
+                SaveFormat::Farbfeld => "farbfeld",
+                SaveFormat::OpenExr => "exr",
             }
         )
     }
 }
+
+#[cfg(test)]
+mod test {
+    use image::{GenericImageView, Rgba, RgbaImage};
+
+    use super::*;
+
+    #[test]
+    fn save_all_formats_test() {
+        let image = DynamicImage::ImageRgba8(RgbaImage::from_fn(300, 200, |x, y| {
+            Rgba([x as u8, y as u8, 128, if x < 150 { 255 } else { 128 }])
+        }));
+
+        for save_format in SaveFormat::ALL {
+            let path = std::env::temp_dir().join(format!(
+                "coffee_image_save_test.{}",
+                save_format.extension()
+            ));
+            assert_eq!(SaveFormat::from_path(&path), Some(save_format));
+            save_format
+                .save(&image, &path, &EncoderOptions::default())
+                .unwrap();
+
+            let reloaded = image::open(&path).unwrap();
+            let _ = fs::remove_file(&path);
+            if save_format == SaveFormat::Ico {
+                assert_eq!(reloaded.dimensions(), (256, 171));
+            } else {
+                assert_eq!(reloaded.dimensions(), image.dimensions(), "{}", save_format);
+            }
+        }
+    }
+
+    #[test]
+    fn encoder_options_test() {
+        let image = DynamicImage::ImageRgb8(image::RgbImage::from_fn(64, 64, |x, y| {
+            image::Rgb([(x * 4) as u8, (y * 4) as u8, ((x ^ y) * 4) as u8])
+        }));
+        let low_quality = EncoderOptions {
+            jpeg_quality: 10,
+            ..EncoderOptions::default()
+        };
+        let high_quality = EncoderOptions {
+            jpeg_quality: 95,
+            jpeg_subsampling: crate::coffee_image::encoder_options::ChromaSubsampling::Yuv444,
+            ..EncoderOptions::default()
+        };
+        let low = SaveFormat::Jpeg.encode(&image, &low_quality).unwrap();
+        let high = SaveFormat::Jpeg.encode(&image, &high_quality).unwrap();
+        assert!(low.len() < high.len());
+
+        for tiff_compression in TiffCompression::ALL {
+            let options = EncoderOptions {
+                tiff_compression,
+                ..EncoderOptions::default()
+            };
+            let bytes = SaveFormat::Tiff.encode(&image, &options).unwrap();
+            let decoded = image::load_from_memory(&bytes).unwrap();
+            assert_eq!(decoded.to_rgb8(), image.to_rgb8(), "{}", tiff_compression);
+        }
+    }
+}
@@ -1,16 +1,141 @@
+use std::fmt::Display;
 
+//https://paulbourke.net/dataformats/asciiart/
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Ramp {
+    #[default]
+    Standard,
+    Detailed,
+    Blocks,
+}
 
-// "", ".", ",", "-", "~", "+", "=", "@"
-const ASCIIS:[&'static str;8] = ["", ".", ",", "-", "~", "+", "=", "@"];
+impl Ramp {
+    pub const ALL: [Ramp; 3] = [Ramp::Standard, Ramp::Detailed, Ramp::Blocks];
 
-pub fn get_str_ascii(intent: u8) -> &'static str {
-    let index = intent / 32;
+    //暗い => 明るい の順
+    pub fn glyphs(&self) -> &'static str {
+        match self {
+            Ramp::Standard => " .,-~+=@",
+            Ramp::Detailed => {
+                " .'`^\",:;Il!i><~+_-?][}{1)(|\\/tfjrxnuvczXYUJCLQ0OZmwqpdbkhao*#MW&8%B@$"
+            }
+            Ramp::Blocks => " ░▒▓█",
+        }
+    }
+
+    pub fn get_str(&self, intent: u8) -> &'static str {
+        let glyphs = self.glyphs();
+        let index = intent as usize * glyphs.chars().count() / 256;
+        let (start, glyph) = glyphs.char_indices().nth(index).unwrap();
+
+        &glyphs[start..start + glyph.len_utf8()]
+    }
 
-    ASCIIS[index as usize]
+    pub fn get_byte(&self, intent: u8) -> &'static [u8] {
+        self.get_str(intent).as_bytes()
+    }
+
+    //get_strの逆変換 文字に対応する明るさの範囲の中央値を返す
+    pub fn get_intent(&self, glyph: char) -> Option<u8> {
+        let glyphs = self.glyphs();
+        let count = glyphs.chars().count();
+        let index = glyphs.chars().position(|c| c == glyph)?;
+
+        Some(((2 * index + 1) * 128 / count) as u8)
+    }
+}
+
+impl Display for Ramp {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                Ramp::Standard => "Standard",
+                Ramp::Detailed => "Detailed",
+                Ramp::Blocks => "Blocks",
+            }
+        )
+    }
+}
+
+//エッジとみなす勾配の強さ(sobel)
+pub const EDGE_THRESHOLD: f32 = 160.0;
+
+//get_byte_edge_asciiが返す文字
+pub const EDGE_GLYPHS: [char; 5] = ['|', '/', '\\', '-', '_'];
+
+pub fn get_str_ascii(intent: u8) -> &'static str {
+    Ramp::Standard.get_str(intent)
 }
 
 pub fn get_byte_ascii<'a>(intent: u8) -> &'a [u8]{
-    let index = intent/32;
+    Ramp::Standard.get_byte(intent)
+}
+
+//横scale x 縦scale*2 ピクセルで1文字
+pub fn scale_from_columns(width: u32, columns: u32) -> u32 {
+    width.div_ceil(columns.max(1)).max(1)
+}
+
+pub fn grid_size(width: u32, height: u32, scale: u32) -> (u32, u32) {
+    let scale = scale.max(1);
+    (width.div_ceil(scale), height.div_ceil(scale * 2))
+}
+
+//勾配(gx,gy)に直交する向きをエッジの向きとして文字を選ぶ
+//lowerはセルの下半分にエッジがある場合true
+pub fn get_byte_edge_ascii<'a>(gx: f32, gy: f32, lower: bool) -> &'a [u8] {
+    //画像座標はy軸が下向きなのでy軸を反転して角度を求める
+    let mut angle = (-gx).atan2(-gy).to_degrees();
+    if angle < 0.0 {
+        angle += 180.0;
+    }
 
-    ASCIIS[index as usize].as_bytes()
-}
\ No newline at end of file
+    if !(22.5..157.5).contains(&angle) {
+        if lower {
+            b"_"
+        } else {
+            b"-"
+        }
+    } else if angle < 67.5 {
+        b"/"
+    } else if angle < 112.5 {
+        b"|"
+    } else {
+        b"\\"
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    //暗い画素も1文字にしないと行ごとに幅がずれる
+    #[test]
+    fn dark_glyph_width_test() {
+        assert_eq!(get_str_ascii(0), " ");
+        assert!((0..=255).all(|intent| get_byte_ascii(intent).len() == 1));
+    }
+
+    #[test]
+    fn ramp_test() {
+        assert_eq!(get_str_ascii(0), " ");
+        assert_eq!(get_str_ascii(255), "@");
+        assert_eq!(Ramp::Blocks.get_str(255), "█");
+        assert_eq!(Ramp::Detailed.glyphs().chars().count(), 70);
+        assert_eq!(grid_size(100, 100, scale_from_columns(100, 25)), (25, 13));
+    }
+
+    #[test]
+    fn edge_ascii_direction_test() {
+        //左右で明るさが変わる => 縦のエッジ
+        assert_eq!(get_byte_edge_ascii(255.0, 0.0, false), b"|");
+        //上下で明るさが変わる => 横のエッジ
+        assert_eq!(get_byte_edge_ascii(0.0, 255.0, false), b"-");
+        assert_eq!(get_byte_edge_ascii(0.0, -255.0, true), b"_");
+        //右下に向かって明るくなる => 左下から右上へのエッジ
+        assert_eq!(get_byte_edge_ascii(255.0, 255.0, false), b"/");
+        assert_eq!(get_byte_edge_ascii(-255.0, 255.0, false), b"\\");
+    }
+}
@@ -0,0 +1,143 @@
+use std::{fs, path::PathBuf};
+
+use image::{GrayImage, Luma};
+
+use crate::coffee_image::error::{Error, ResultExt};
+
+use super::ascii::{Ramp, EDGE_GLYPHS};
+
+//ランプにない文字(エッジの| / - \ _など)は中間の明るさとして扱う
+const UNKNOWN_GLYPH_INTENT: u8 = 128;
+
+//ascii_artの逆変換 1文字をcell_width x cell_heightのグレーのブロックに戻す
+pub fn ascii_to_image(text: &str, ramp: Ramp, cell_width: u32, cell_height: u32) -> GrayImage {
+    let (cell_width, cell_height) = (cell_width.max(1), cell_height.max(1));
+    let lines: Vec<&str> = text.lines().collect();
+    let columns = lines
+        .iter()
+        .map(|line| line.chars().count())
+        .max()
+        .unwrap_or(0) as u32;
+    let background = ramp.get_intent(' ').unwrap_or(0);
+
+    let mut image = GrayImage::from_pixel(
+        columns * cell_width,
+        lines.len() as u32 * cell_height,
+        Luma([background]),
+    );
+
+    for (row, line) in lines.iter().enumerate() {
+        for (column, glyph) in line.chars().enumerate() {
+            let intent = ramp.get_intent(glyph).unwrap_or(UNKNOWN_GLYPH_INTENT);
+            let (left, top) = (column as u32 * cell_width, row as u32 * cell_height);
+
+            for y in top..top + cell_height {
+                for x in left..left + cell_width {
+                    image.put_pixel(x, y, Luma([intent]));
+                }
+            }
+        }
+    }
+    image
+}
+
+//一番多くの文字を含むランプを選ぶ 同数なら文字数の少ないランプを優先
+//エッジの文字はどのランプで作っても出るので数えない
+pub fn detect_ramp(text: &str) -> Ramp {
+    let mut ramps = Ramp::ALL;
+    ramps.sort_by_key(|ramp| ramp.glyphs().chars().count());
+
+    let mut best = (Ramp::default(), 0);
+    for ramp in ramps {
+        let hits = text
+            .chars()
+            .filter(|glyph| {
+                !glyph.is_whitespace()
+                    && !EDGE_GLYPHS.contains(glyph)
+                    && ramp.get_intent(*glyph).is_some()
+            })
+            .count();
+        if hits > best.1 {
+            best = (ramp, hits);
+        }
+    }
+    best.0
+}
+
+pub fn ascii_file_to_image(
+    path: &PathBuf,
+    cell_width: u32,
+    cell_height: u32,
+) -> Result<GrayImage, Error> {
+    let text = fs::read_to_string(path).open_context(path)?;
+
+    Ok(ascii_to_image(&text, detect_ramp(&text), cell_width, cell_height))
+}
+
+#[cfg(test)]
+mod test {
+    use crate::coffee_image::convert::image_wrap::ImageConverter;
+
+    use super::*;
+
+    fn gradient_to_ascii(ramp: Ramp, width: u32) -> (GrayImage, String) {
+        let image = GrayImage::from_fn(width, 2, |x, _| Luma([(x * 255 / (width - 1)) as u8]));
+        let line: String = (0..width)
+            .map(|x| ramp.get_str(image.get_pixel(x, 0)[0]))
+            .collect();
+
+        (image, format!("{}\n{}\n", line, line))
+    }
+
+    #[test]
+    fn round_trip_test() {
+        for ramp in Ramp::ALL {
+            let (image, text) = gradient_to_ascii(ramp, 64);
+            let restored = ascii_to_image(&text, detect_ramp(&text), 1, 1);
+            //1文字あたりの明るさの幅以内に収まる
+            let tolerance = 256 / ramp.glyphs().chars().count() as i32;
+
+            assert_eq!(restored.dimensions(), image.dimensions());
+            for (original, restored) in image.pixels().zip(restored.pixels()) {
+                assert!((original[0] as i32 - restored[0] as i32).abs() <= tolerance);
+            }
+        }
+    }
+
+    //左が暗く右が明るい画像 境目に縦のエッジ(|)が出る
+    #[test]
+    fn edge_art_round_trip_test() {
+        let (width, height, scale) = (64, 32, 2);
+        let image =
+            GrayImage::from_fn(width, height, |x, _| Luma([if x < 32 { 40 } else { 220 }]));
+        let path = std::env::temp_dir().join("coffee_image_edge_round_trip.png");
+        image.save(&path).unwrap();
+        let mut ic = ImageConverter::new();
+        ic.set_image_path(path);
+
+        let text = ic
+            .ascii_edge_art(scale, Ramp::Standard)
+            .unwrap()
+            .read_text_file()
+            .unwrap();
+        assert!(text.contains('|'), "{}", text);
+        assert_eq!(detect_ramp(&text), Ramp::Standard);
+
+        let restored = ascii_to_image(&text, detect_ramp(&text), scale, scale * 2);
+        let tolerance = 256 / Ramp::Standard.glyphs().chars().count() as i32;
+        //エッジのセルから離れたところは元の明るさに戻る
+        for x in (0..24).chain(40..width) {
+            let (original, restored) = (image.get_pixel(x, 8)[0], restored.get_pixel(x, 8)[0]);
+            assert!((original as i32 - restored as i32).abs() <= tolerance, "x={}", x);
+        }
+    }
+
+    #[test]
+    fn cell_size_test() {
+        let image = ascii_to_image("@ \n@", Ramp::Standard, 3, 6);
+
+        assert_eq!(image.dimensions(), (6, 12));
+        assert_eq!(image.get_pixel(2, 11)[0], Ramp::Standard.get_intent('@').unwrap());
+        assert_eq!(image.get_pixel(5, 11)[0], Ramp::Standard.get_intent(' ').unwrap());
+    }
+}
@@ -0,0 +1,93 @@
+use std::fmt::Display;
+
+use image::Rgba;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TextFormat {
+    #[default]
+    Txt,
+    Ansi,
+    Html,
+}
+
+impl TextFormat {
+    pub const ALL: [TextFormat; 3] = [TextFormat::Txt, TextFormat::Ansi, TextFormat::Html];
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            TextFormat::Txt => "txt",
+            TextFormat::Ansi => "ans",
+            TextFormat::Html => "html",
+        }
+    }
+
+    pub fn is_colored(&self) -> bool {
+        *self != TextFormat::Txt
+    }
+
+    //colorsはascii_artの各文字に対応する元画像の色(行ごと)
+    pub fn render(&self, content: &str, colors: &[Vec<Rgba<u8>>]) -> String {
+        match self {
+            TextFormat::Txt => content.to_string(),
+            TextFormat::Ansi => render_ansi(content, colors),
+            TextFormat::Html => render_html(content, colors),
+        }
+    }
+}
+
+fn render_ansi(content: &str, colors: &[Vec<Rgba<u8>>]) -> String {
+    let mut output = String::new();
+
+    for (y, line) in content.lines().enumerate() {
+        for (x, glyph) in line.chars().enumerate() {
+            if let Some(color) = colors.get(y).and_then(|row| row.get(x)) {
+                output.push_str(&format!("\x1b[38;2;{};{};{}m", color[0], color[1], color[2]));
+            }
+            output.push(glyph);
+        }
+        output.push_str("\x1b[0m\n");
+    }
+    output
+}
+
+fn render_html(content: &str, colors: &[Vec<Rgba<u8>>]) -> String {
+    let mut output = String::from(
+        "<!DOCTYPE html>\n<html>\n<head><meta charset=\"utf-8\"></head>\n\
+         <body style=\"background:#000\">\n<pre style=\"font-family:monospace;line-height:1\">\n",
+    );
+
+    for (y, line) in content.lines().enumerate() {
+        for (x, glyph) in line.chars().enumerate() {
+            let escaped = match glyph {
+                '&' => "&amp;".to_string(),
+                '<' => "&lt;".to_string(),
+                '>' => "&gt;".to_string(),
+                _ => glyph.to_string(),
+            };
+            match colors.get(y).and_then(|row| row.get(x)) {
+                Some(color) => output.push_str(&format!(
+                    "<span style=\"color:#{:02x}{:02x}{:02x}\">{}</span>",
+                    color[0], color[1], color[2], escaped
+                )),
+                None => output.push_str(&escaped),
+            }
+        }
+        output.push('\n');
+    }
+    output.push_str("</pre>\n</body>\n</html>\n");
+    output
+}
+
+impl Display for TextFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                TextFormat::Txt => "txt",
+                TextFormat::Ansi => "ansi",
+                TextFormat::Html => "html",
+            }
+        )
+    }
+}
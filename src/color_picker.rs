@@ -0,0 +1,193 @@
+use std::{path::PathBuf, sync::Arc};
+
+use iced::{widget::text, Color, Command, Point};
+use image::{Rgba, RgbaImage};
+
+use coffee_image::convert::image_wrap::get_dynamic_image;
+
+use crate::Message;
+
+//カーソルの下の画素
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PixelInfo {
+    pub x: u32,
+    pub y: u32,
+    pub color: Rgba<u8>,
+}
+
+impl PixelInfo {
+    pub fn hex(&self) -> String {
+        hex(self.color)
+    }
+
+    pub fn view(&self) -> iced::Element<'_, Message> {
+        let [red, green, blue, alpha] = self.color.0;
+        let (hue, saturation, value) = rgb_to_hsv(self.color);
+        text(format!(
+            "({}, {})  RGBA {} {} {} {}  HSV {:.0}° {:.0}% {:.0}%  {}",
+            self.x,
+            self.y,
+            red,
+            green,
+            blue,
+            alpha,
+            hue,
+            saturation * 100.0,
+            value * 100.0,
+            self.hex()
+        ))
+        .size(14)
+        .into()
+    }
+}
+
+//#RRGGBBAA
+pub fn hex(color: Rgba<u8>) -> String {
+    let [red, green, blue, alpha] = color.0;
+    format!("#{:02X}{:02X}{:02X}{:02X}", red, green, blue, alpha)
+}
+
+//#RRGGBBか#RRGGBBAA #は省略できる
+pub fn parse_hex(text: &str) -> Option<Rgba<u8>> {
+    let digits = text.strip_prefix('#').unwrap_or(text);
+    if !(digits.len() == 6 || digits.len() == 8) || !digits.is_ascii() {
+        return None;
+    }
+    let mut color = [255; 4];
+    for (channel, start) in color.iter_mut().zip((0..digits.len()).step_by(2)) {
+        *channel = u8::from_str_radix(&digits[start..start + 2], 16).ok()?;
+    }
+    Some(Rgba(color))
+}
+
+//色相(0~360) 彩度と明度(0.0~1.0)
+pub fn rgb_to_hsv(color: Rgba<u8>) -> (f32, f32, f32) {
+    let [red, green, blue, _] = color.0.map(|value| value as f32 / 255.0);
+    let max = red.max(green).max(blue);
+    let min = red.min(green).min(blue);
+    let delta = max - min;
+
+    let hue = if delta == 0.0 {
+        0.0
+    } else if max == red {
+        60.0 * ((green - blue) / delta).rem_euclid(6.0)
+    } else if max == green {
+        60.0 * ((blue - red) / delta + 2.0)
+    } else {
+        60.0 * ((red - green) / delta + 4.0)
+    };
+    let saturation = if max == 0.0 { 0.0 } else { delta / max };
+    (hue, saturation, max)
+}
+
+//取った色の見本用
+pub fn swatch_color(color: Rgba<u8>) -> Color {
+    let [red, green, blue, alpha] = color.0;
+    Color::from_rgba8(red, green, blue, alpha as f32 / 255.0)
+}
+
+//pointは画像の座標 画像の外ならNone
+pub fn pixel_at(image: &RgbaImage, point: Point) -> Option<PixelInfo> {
+    if point.x < 0.0 || point.y < 0.0 {
+        return None;
+    }
+    let (x, y) = (point.x as u32, point.y as u32);
+    image.get_pixel_checked(x, y).map(|color| PixelInfo {
+        x,
+        y,
+        color: *color,
+    })
+}
+
+//カーソルの下の色を読むために表示中の画像を別スレッドでデコードして持っておく
+//比べるときは元画像と変換後の画像を行き来するので2枚まで
+#[derive(Debug, Clone, Default)]
+pub struct PixelProbe {
+    //デコード中か読めなかった画像はNone
+    images: Vec<(PathBuf, Option<Arc<RgbaImage>>)>,
+}
+
+const PROBE_CACHE_SIZE: usize = 2;
+
+impl PixelProbe {
+    //デコードが済むまではNone
+    pub fn pixel(&self, path: &PathBuf, point: Point) -> Option<PixelInfo> {
+        let (_, image) = self.images.iter().find(|(cached, _)| cached == path)?;
+        pixel_at(image.as_ref()?, point)
+    }
+
+    //まだ読んでいない画像ならデコードを始める
+    pub fn load(&mut self, path: &PathBuf) -> Command<Message> {
+        if self.images.iter().any(|(cached, _)| cached == path) {
+            return Command::none();
+        }
+        if self.images.len() >= PROBE_CACHE_SIZE {
+            self.images.remove(0);
+        }
+        self.images.push((path.clone(), None));
+
+        let (path, loaded_path) = (path.clone(), path.clone());
+        Command::perform(
+            async move {
+                get_dynamic_image(&path)
+                    .ok()
+                    .map(|image| Arc::new(image.to_rgba8()))
+            },
+            move |image| Message::ProbeImageLoaded(loaded_path, image),
+        )
+    }
+
+    //デコード中に追い出された画像は捨てる
+    pub fn finish(&mut self, path: PathBuf, image: Option<Arc<RgbaImage>>) {
+        if let Some((_, cached)) = self.images.iter_mut().find(|(cached, _)| *cached == path) {
+            *cached = image;
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn hsv_test() {
+        assert_eq!(rgb_to_hsv(Rgba([255, 0, 0, 255])), (0.0, 1.0, 1.0));
+        assert_eq!(rgb_to_hsv(Rgba([0, 0, 255, 255])), (240.0, 1.0, 1.0));
+        assert_eq!(rgb_to_hsv(Rgba([255, 0, 255, 255])), (300.0, 1.0, 1.0));
+        assert_eq!(rgb_to_hsv(Rgba([0, 0, 0, 255])), (0.0, 0.0, 0.0));
+
+        let info = PixelInfo {
+            x: 0,
+            y: 0,
+            color: Rgba([18, 52, 171, 128]),
+        };
+        assert_eq!(info.hex(), "#1234AB80");
+        assert_eq!(parse_hex("#1234AB80"), Some(info.color));
+        assert_eq!(parse_hex("1234ab"), Some(Rgba([18, 52, 171, 255])));
+        assert_eq!(parse_hex("#12345"), None);
+        assert_eq!(parse_hex("#12345G"), None);
+    }
+
+    #[test]
+    fn probe_test() {
+        let path = PathBuf::from("probe.png");
+        let point = Point::new(1.5, 0.0);
+        let mut probe = PixelProbe::default();
+        let _ = probe.load(&path);
+        assert_eq!(probe.pixel(&path, point), None);
+
+        let image = RgbaImage::from_fn(2, 2, |x, _| Rgba([x as u8, 0, 0, 255]));
+        probe.finish(path.clone(), Some(Arc::new(image)));
+        assert_eq!(
+            probe.pixel(&path, point).map(|pixel| pixel.color),
+            Some(Rgba([1, 0, 0, 255]))
+        );
+        assert_eq!(probe.pixel(&path, Point::new(2.0, 0.0)), None);
+
+        //追い出された画像の結果は入れない
+        let _ = probe.load(&PathBuf::from("a.png"));
+        let _ = probe.load(&PathBuf::from("b.png"));
+        probe.finish(path.clone(), None);
+        assert!(probe.images.iter().all(|(cached, _)| *cached != path));
+    }
+}
@@ -0,0 +1,59 @@
+use std::{
+    path::PathBuf,
+    sync::atomic::{AtomicU64, Ordering},
+};
+
+use iced::{
+    widget::{button, progress_bar, row, text},
+    Alignment, Length,
+};
+
+use coffee_image::convert::{animation::Animation, progress::Progress};
+
+use crate::{
+    i18n::{tr, Text},
+    Message,
+};
+
+static NEXT_JOB_ID: AtomicU64 = AtomicU64::new(0);
+
+//別スレッドの処理の結果を見分けるid
+pub fn next_job_id() -> u64 {
+    NEXT_JOB_ID.fetch_add(1, Ordering::Relaxed)
+}
+
+//別スレッドで動いている変換 idで結果が今の変換のものか確かめる
+#[derive(Debug, Clone)]
+pub struct ConvertJob {
+    pub id: u64,
+    pub progress: Progress,
+}
+
+#[derive(Debug, Clone)]
+pub enum ConvertOutput {
+    Image(PathBuf),
+    Animation(Animation),
+}
+
+impl ConvertJob {
+    pub fn new() -> Self {
+        Self {
+            id: next_job_id(),
+            progress: Progress::new(),
+        }
+    }
+
+    pub fn view(&self) -> iced::Element<'_, Message> {
+        let (done, total) = self.progress.rows();
+
+        row![
+            progress_bar(0.0..=1.0, self.progress.fraction()).width(Length::Fill),
+            text(format!("{} / {}", done.min(total), total)),
+            button(tr(Text::Cancel)).on_press(Message::CancelConvert),
+        ]
+        .spacing(10)
+        .padding([0, 10])
+        .align_items(Alignment::Center)
+        .into()
+    }
+}
@@ -0,0 +1,174 @@
+use std::fmt::Display;
+use std::path::PathBuf;
+
+use iced::{
+    widget::{button, pick_list, row, slider, text},
+    Alignment,
+};
+
+use coffee_image::{
+    convert::{
+        animation::{Animation, Sweep, DEFAULT_GIF_SPEED},
+        image_wrap::get_dynamic_image,
+    },
+    error::{Error, Input},
+};
+
+use crate::{
+    i18n::{image_count, tr, Localized, Text},
+    Message,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GifSource {
+    Sweep(Sweep),
+    Images,
+}
+
+impl GifSource {
+    pub const ALL: [GifSource; 4] = [
+        GifSource::Sweep(Sweep::HueRotate),
+        GifSource::Sweep(Sweep::Rotate),
+        GifSource::Sweep(Sweep::Blur),
+        GifSource::Images,
+    ];
+}
+
+impl Default for GifSource {
+    fn default() -> Self {
+        GifSource::Sweep(Sweep::default())
+    }
+}
+
+impl Display for GifSource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            GifSource::Sweep(sweep) => {
+                let (start, end) = sweep.range();
+                write!(f, "{} {}-{}", Localized(*sweep), start, end)
+            }
+            GifSource::Images => write!(f, "{}", tr(Text::Images)),
+        }
+    }
+}
+
+//MakeGifモードの設定 loop_countは0で無限ループ
+#[derive(Debug, Clone, PartialEq)]
+pub struct GifSettings {
+    pub source: GifSource,
+    pub frame_count: u32,
+    pub delay_ms: u32,
+    pub loop_count: u16,
+    pub speed: i32,
+    pub image_paths: Vec<PathBuf>,
+}
+
+impl Default for GifSettings {
+    fn default() -> Self {
+        Self {
+            source: GifSource::default(),
+            frame_count: 12,
+            delay_ms: 80,
+            loop_count: 0,
+            speed: DEFAULT_GIF_SPEED,
+            image_paths: Vec::new(),
+        }
+    }
+}
+
+impl GifSettings {
+    pub fn can_build(&self, image_path: Option<&PathBuf>) -> bool {
+        match self.source {
+            GifSource::Sweep(_) => image_path.is_some(),
+            GifSource::Images => !self.image_paths.is_empty(),
+        }
+    }
+
+    //Sweepは開いている画像から、Imagesは選んだ画像を順番に並べて作る
+    pub fn build(&self, image_path: Option<&PathBuf>) -> Result<Animation, Error> {
+        match self.source {
+            GifSource::Sweep(sweep) => {
+                let image = get_dynamic_image(image_path.ok_or(Error::MissingInput(Input::Image))?)?;
+                Animation::from_sweep(
+                    &image,
+                    sweep,
+                    self.frame_count,
+                    self.delay_ms,
+                    self.loop_count,
+                )
+            }
+            GifSource::Images => {
+                Animation::from_images(&self.image_paths, self.delay_ms, self.loop_count)
+            }
+        }
+    }
+
+    pub fn view(&self) -> iced::Element<'_, Message> {
+        let changed = |settings: GifSettings| Message::GifSettingsChanged(settings);
+
+        let source_list = pick_list(&GifSource::ALL[..], Some(self.source), move |source| {
+            changed(GifSettings {
+                source,
+                ..self.clone()
+            })
+        });
+
+        let source_controll = match self.source {
+            GifSource::Sweep(_) => row![
+                text(format!("{} {}", tr(Text::Frames), self.frame_count)),
+                slider(2..=60, self.frame_count, move |frame_count| {
+                    changed(GifSettings {
+                        frame_count,
+                        ..self.clone()
+                    })
+                })
+                .width(120),
+            ],
+            GifSource::Images => row![
+                button(tr(Text::SelectImages)).on_press(Message::GifImagesOpen),
+                text(image_count(self.image_paths.len())),
+            ],
+        }
+        .spacing(10)
+        .align_items(Alignment::Center);
+
+        let loop_label = match self.loop_count {
+            0 => tr(Text::LoopForever).to_string(),
+            loop_count => format!("{} {}", tr(Text::Loop), loop_count),
+        };
+
+        row![
+            source_list,
+            source_controll,
+            text(format!("{} {}ms", tr(Text::Delay), self.delay_ms)),
+            slider(10..=1000, self.delay_ms, move |delay_ms| {
+                changed(GifSettings {
+                    delay_ms,
+                    ..self.clone()
+                })
+            })
+            .step(10)
+            .width(120),
+            text(loop_label),
+            slider(0..=10, self.loop_count, move |loop_count| {
+                changed(GifSettings {
+                    loop_count,
+                    ..self.clone()
+                })
+            })
+            .width(80),
+            //GifEncoderのspeedは小さいほど減色の品質が高い
+            text(format!("{} {}", tr(Text::PaletteQuality), 31 - self.speed)),
+            slider(1..=30, 31 - self.speed, move |quality| {
+                changed(GifSettings {
+                    speed: 31 - quality,
+                    ..self.clone()
+                })
+            })
+            .width(120),
+        ]
+        .spacing(10)
+        .align_items(Alignment::Center)
+        .into()
+    }
+}
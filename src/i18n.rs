@@ -0,0 +1,646 @@
+use std::{
+    fmt::Display,
+    io::ErrorKind,
+    path::Path,
+    sync::atomic::{AtomicU8, Ordering},
+};
+
+use coffee_image::{
+    convert::animation::Sweep,
+    error::{Context, Error, Input},
+    metadata::{MetadataCategory, MetadataField, MetadataPolicy},
+};
+
+use crate::select_mode::param_name;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Language {
+    #[default]
+    English,
+    Japanese,
+}
+
+impl Language {
+    pub const ALL: [Language; 2] = [Language::English, Language::Japanese];
+
+    //"ja-JP"や"ja_JP.UTF-8"のようなロケール名から選ぶ 日本語以外は英語
+    pub fn from_locale(locale: &str) -> Self {
+        if locale.to_lowercase().starts_with("ja") {
+            Language::Japanese
+        } else {
+            Language::English
+        }
+    }
+
+    pub fn system() -> Self {
+        sys_locale::get_locale().map_or(Language::default(), |locale| Self::from_locale(&locale))
+    }
+}
+
+impl Display for Language {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                Language::English => "English",
+                Language::Japanese => "日本語",
+            }
+        )
+    }
+}
+
+//pick_listのDisplayからも参照するので表示言語はグローバルに持つ
+static LANGUAGE: AtomicU8 = AtomicU8::new(0);
+
+pub fn language() -> Language {
+    match LANGUAGE.load(Ordering::Relaxed) {
+        1 => Language::Japanese,
+        _ => Language::English,
+    }
+}
+
+pub fn set_language(language: Language) {
+    let value = match language {
+        Language::English => 0,
+        Language::Japanese => 1,
+    };
+    LANGUAGE.store(value, Ordering::Relaxed);
+}
+
+//固定の文言 引数の入る文言は下の関数で作る
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Text {
+    Open,
+    Save,
+    SaveAs,
+    Convert,
+    Cancel,
+    Reselect,
+    ReturnMain,
+    Rerun,
+    Columns,
+    Quality,
+    Lossless,
+    Frames,
+    SelectImages,
+    Delay,
+    PaletteQuality,
+    Scale,
+    Loop,
+    LoopForever,
+    Gray,
+    Invert,
+    HueRotate,
+    Blur,
+    ToAscii,
+    ToAsciiEdge,
+    FromAscii,
+    Rotate,
+    Add,
+    MakeGif,
+    ChromaKey,
+    Threshold,
+    Resize,
+    Images,
+    KeepMetadata,
+    StripMetadata,
+    RemoveSelected,
+    Camera,
+    Exposure,
+    Date,
+    Gps,
+    Exif,
+    ExifCamera,
+    ExifLens,
+    ExifExposure,
+    ExifFNumber,
+    ExifIso,
+    ExifFocalLength,
+    ExifDate,
+    ExifGps,
+    ExifOrientation,
+    CompareOff,
+    SideBySide,
+    SplitView,
+    HoldOriginal,
+    Before,
+    After,
+    ZoomFit,
+    ZoomFill,
+    Zoom,
+    Selection,
+    Eyedropper,
+    HueAngle,
+    BlurSigma,
+    RotateAngle,
+    Tolerance,
+    CellWidth,
+    Glyphs,
+    FillColor,
+    KeyColor,
+    Level,
+    MaxSide,
+    Preset,
+    PresetVintage,
+    PresetHighContrastBw,
+    PresetWebThumbnail,
+    LoadRecipe,
+    SaveRecipe,
+    OpenRecipeTitle,
+    SaveRecipeTitle,
+    PickedColor,
+    OpenImageTitle,
+    OpenTextTitle,
+    SaveTitle,
+    DialogClosed,
+    Cancelled,
+    FileNotFound,
+    PermissionDenied,
+    EnterNumber,
+    AnimationFormat,
+    InputImage,
+    InputSecondImage,
+    InputTextFile,
+    InputConvertedImage,
+    InputColor,
+    OutputFile,
+    RecipeFile,
+}
+
+pub fn tr(text: Text) -> &'static str {
+    tr_in(text, language())
+}
+
+pub fn tr_in(text: Text, language: Language) -> &'static str {
+    match language {
+        Language::English => english(text),
+        Language::Japanese => japanese(text),
+    }
+}
+
+fn english(text: Text) -> &'static str {
+    match text {
+        Text::Open => "Open",
+        Text::Save => "Save",
+        Text::SaveAs => "Save As",
+        Text::Convert => "Convert",
+        Text::Cancel => "Cancel",
+        Text::Reselect => "Reselect",
+        Text::ReturnMain => "Return Main",
+        Text::Rerun => "Rerun",
+        Text::Columns => "columns",
+        Text::Quality => "quality",
+        Text::Lossless => "lossless",
+        Text::Frames => "frames",
+        Text::SelectImages => "Images...",
+        Text::Delay => "delay",
+        Text::PaletteQuality => "palette quality",
+        Text::Scale => "scale",
+        Text::Loop => "loop",
+        Text::LoopForever => "loop ∞",
+        Text::Gray => "Gray",
+        Text::Invert => "Invert",
+        Text::HueRotate => "HueRotate",
+        Text::Blur => "Blur",
+        Text::ToAscii => "ToAscii",
+        Text::ToAsciiEdge => "ToAsciiEdge",
+        Text::FromAscii => "FromAscii",
+        Text::Rotate => "Rotate",
+        Text::Add => "Add",
+        Text::MakeGif => "MakeGif",
+        Text::ChromaKey => "Chroma key",
+        Text::Threshold => "Threshold",
+        Text::Resize => "Resize",
+        Text::Images => "Images",
+        Text::KeepMetadata => "keep metadata",
+        Text::StripMetadata => "strip metadata",
+        Text::RemoveSelected => "remove selected",
+        Text::Camera => "camera",
+        Text::Exposure => "exposure",
+        Text::Date => "date",
+        Text::Gps => "GPS",
+        Text::Exif => "EXIF",
+        Text::ExifCamera => "Camera",
+        Text::ExifLens => "Lens",
+        Text::ExifExposure => "Exposure",
+        Text::ExifFNumber => "F",
+        Text::ExifIso => "ISO",
+        Text::ExifFocalLength => "Focal length",
+        Text::ExifDate => "Date",
+        Text::ExifGps => "GPS",
+        Text::ExifOrientation => "Orientation",
+        Text::CompareOff => "No comparison",
+        Text::SideBySide => "Side by side",
+        Text::SplitView => "Split",
+        Text::HoldOriginal => "Hold to see original",
+        Text::Before => "Before",
+        Text::After => "After",
+        Text::ZoomFit => "Fit",
+        Text::ZoomFill => "Fill",
+        Text::Zoom => "Zoom",
+        Text::Selection => "Selection",
+        Text::Eyedropper => "Eyedropper",
+        Text::HueAngle => "Hue (°)",
+        Text::BlurSigma => "Blur strength",
+        Text::RotateAngle => "Angle (°)",
+        Text::Tolerance => "Tolerance (%)",
+        Text::CellWidth => "Cell width (px)",
+        Text::Glyphs => "Characters",
+        Text::FillColor => "Background color",
+        Text::KeyColor => "Key color",
+        Text::Level => "Level (%)",
+        Text::MaxSide => "Longest side (px)",
+        Text::Preset => "Preset",
+        Text::PresetVintage => "Vintage",
+        Text::PresetHighContrastBw => "High-contrast B&W",
+        Text::PresetWebThumbnail => "Web thumbnail",
+        Text::LoadRecipe => "Load recipe",
+        Text::SaveRecipe => "Save recipe",
+        Text::OpenRecipeTitle => "Open a recipe",
+        Text::SaveRecipeTitle => "Save the recipe",
+        Text::PickedColor => "Picked",
+        Text::OpenImageTitle => "Choose an image",
+        Text::OpenTextTitle => "Choose a text file",
+        Text::SaveTitle => "Save as",
+        Text::DialogClosed => "The dialog was closed",
+        Text::Cancelled => "The conversion was cancelled",
+        Text::FileNotFound => "File not found",
+        Text::PermissionDenied => "Permission denied",
+        Text::EnterNumber => "Please enter a number",
+        Text::AnimationFormat => "Animations can only be saved as gif or png",
+        Text::InputImage => "image",
+        Text::InputSecondImage => "second image",
+        Text::InputTextFile => "text file",
+        Text::InputConvertedImage => "converted image",
+        Text::InputColor => "picked color",
+        Text::OutputFile => "output file",
+        Text::RecipeFile => "recipe file",
+    }
+}
+
+fn japanese(text: Text) -> &'static str {
+    match text {
+        Text::Open => "開く",
+        Text::Save => "保存",
+        Text::SaveAs => "名前を付けて保存",
+        Text::Convert => "変換",
+        Text::Cancel => "中止",
+        Text::Reselect => "選び直す",
+        Text::ReturnMain => "戻る",
+        Text::Rerun => "再変換",
+        Text::Columns => "列数",
+        Text::Quality => "品質",
+        Text::Lossless => "ロスレス",
+        Text::Frames => "フレーム数",
+        Text::SelectImages => "画像を選ぶ...",
+        Text::Delay => "間隔",
+        Text::PaletteQuality => "パレットの品質",
+        Text::Scale => "倍率",
+        Text::Loop => "ループ",
+        Text::LoopForever => "無限ループ",
+        Text::Gray => "グレースケール",
+        Text::Invert => "色反転",
+        Text::HueRotate => "色相回転",
+        Text::Blur => "ぼかし",
+        Text::ToAscii => "アスキーアート",
+        Text::ToAsciiEdge => "アスキーアート(エッジ)",
+        Text::FromAscii => "アスキーアートから画像",
+        Text::Rotate => "回転",
+        Text::Add => "加算",
+        Text::MakeGif => "GIF作成",
+        Text::ChromaKey => "クロマキー",
+        Text::Threshold => "二値化",
+        Text::Resize => "縮小",
+        Text::Images => "画像から",
+        Text::KeepMetadata => "メタデータを残す",
+        Text::StripMetadata => "メタデータを消す",
+        Text::RemoveSelected => "選んだものを消す",
+        Text::Camera => "カメラ",
+        Text::Exposure => "露出",
+        Text::Date => "日時",
+        Text::Gps => "位置情報",
+        Text::Exif => "EXIF情報",
+        Text::ExifCamera => "カメラ",
+        Text::ExifLens => "レンズ",
+        Text::ExifExposure => "露出時間",
+        Text::ExifFNumber => "F値",
+        Text::ExifIso => "ISO感度",
+        Text::ExifFocalLength => "焦点距離",
+        Text::ExifDate => "撮影日時",
+        Text::ExifGps => "位置情報",
+        Text::ExifOrientation => "向き",
+        Text::CompareOff => "比較しない",
+        Text::SideBySide => "並べて比較",
+        Text::SplitView => "分割して比較",
+        Text::HoldOriginal => "押している間は元画像",
+        Text::Before => "変換前",
+        Text::After => "変換後",
+        Text::ZoomFit => "全体",
+        Text::ZoomFill => "画面いっぱい",
+        Text::Zoom => "倍率",
+        Text::Selection => "選択範囲",
+        Text::Eyedropper => "スポイト",
+        Text::HueAngle => "色相 (°)",
+        Text::BlurSigma => "ぼかしの強さ",
+        Text::RotateAngle => "角度 (°)",
+        Text::Tolerance => "許容量 (%)",
+        Text::CellWidth => "文字の幅 (px)",
+        Text::Glyphs => "使う文字",
+        Text::FillColor => "背景の色",
+        Text::KeyColor => "抜く色",
+        Text::Level => "しきい値 (%)",
+        Text::MaxSide => "長い辺 (px)",
+        Text::Preset => "プリセット",
+        Text::PresetVintage => "ビンテージ",
+        Text::PresetHighContrastBw => "高コントラスト白黒",
+        Text::PresetWebThumbnail => "Web用サムネイル",
+        Text::LoadRecipe => "レシピを読み込む",
+        Text::SaveRecipe => "レシピを保存",
+        Text::OpenRecipeTitle => "レシピを開く",
+        Text::SaveRecipeTitle => "レシピを保存",
+        Text::PickedColor => "取った色",
+        Text::OpenImageTitle => "画像パスを教えてください",
+        Text::OpenTextTitle => "テキストファイルを選んでください",
+        Text::SaveTitle => "保存場所",
+        Text::DialogClosed => "ダイアログが閉じられました",
+        Text::Cancelled => "変換を中止しました",
+        Text::FileNotFound => "ファイルが見つかりません",
+        Text::PermissionDenied => "アクセスが許可されていません",
+        Text::EnterNumber => "数値を入力してください",
+        Text::AnimationFormat => "アニメーションはgifかpngで保存してください",
+        Text::InputImage => "画像",
+        Text::InputSecondImage => "2枚目の画像",
+        Text::InputTextFile => "テキストファイル",
+        Text::InputConvertedImage => "変換結果",
+        Text::InputColor => "スポイトで取った色",
+        Text::OutputFile => "出力ファイル",
+        Text::RecipeFile => "レシピファイル",
+    }
+}
+
+pub fn saved_to(path: &Path) -> String {
+    match language() {
+        Language::English => format!("Saved to {}", path.display()),
+        Language::Japanese => format!("{}に保存しました", path.display()),
+    }
+}
+
+pub fn remove_category(category: MetadataCategory) -> String {
+    match language() {
+        Language::English => format!("remove {}", Localized(category)),
+        Language::Japanese => format!("{}を消す", Localized(category)),
+    }
+}
+
+pub fn image_count(count: usize) -> String {
+    match language() {
+        Language::English => format!("{} images", count),
+        Language::Japanese => format!("{}枚", count),
+    }
+}
+
+pub fn ascii_grid(columns: u32, rows: u32) -> String {
+    match language() {
+        Language::English => format!("{} x {} ({} chars)", columns, rows, columns * rows),
+        Language::Japanese => format!("{} x {} ({}文字)", columns, rows, columns * rows),
+    }
+}
+
+pub fn error_message(error: &Error) -> String {
+    error_message_in(error, language())
+}
+
+//GUIに出すエラーのメッセージ ライブラリのDisplayは使わない
+pub fn error_message_in(error: &Error, language: Language) -> String {
+    let tr = |text| tr_in(text, language);
+
+    match error {
+        Error::DialogClosed => tr(Text::DialogClosed).to_string(),
+        Error::Cancelled => tr(Text::Cancelled).to_string(),
+        Error::IOFailed(error) => match error.kind() {
+            ErrorKind::NotFound => tr(Text::FileNotFound).to_string(),
+            ErrorKind::PermissionDenied => tr(Text::PermissionDenied).to_string(),
+            _ => error.to_string(),
+        },
+        Error::ParseError(_) => tr(Text::EnterNumber).to_string(),
+        Error::ImageError(error) => image_error(error, language),
+        Error::UnsupportedAnimationFormat(_) => tr(Text::AnimationFormat).to_string(),
+        Error::MissingInput(input) => missing_input(tr(input_text(*input)), language),
+        Error::InvalidParameter { name, value } => {
+            invalid_parameter(&parameter_name(name, language), value, language)
+        }
+        Error::UnknownOperation(name) => unknown_operation(name, language),
+        Error::InvalidFile(error) => invalid_file(error, language),
+        Error::WidthHeightNotEqualError { expected, actual } => {
+            size_mismatch(*expected, *actual, language)
+        }
+        Error::WithContext { context, source } => format!(
+            "{}: {}",
+            context_message(context, language),
+            error_message_in(source, language)
+        ),
+    }
+}
+
+fn image_error(error: &impl Display, language: Language) -> String {
+    match language {
+        Language::English => format!("Could not process the image ({})", error),
+        Language::Japanese => format!("画像を処理できません ({})", error),
+    }
+}
+
+fn missing_input(name: &str, language: Language) -> String {
+    match language {
+        Language::English => format!("No {} selected", name),
+        Language::Japanese => format!("{}が選ばれていません", name),
+    }
+}
+
+fn invalid_parameter(name: &str, value: &str, language: Language) -> String {
+    match language {
+        Language::English => format!("Invalid value for {} ({})", name, value),
+        Language::Japanese => format!("{}の値が正しくありません ({})", name, value),
+    }
+}
+
+fn unknown_operation(name: &str, language: Language) -> String {
+    match language {
+        Language::English => format!("Unknown operation {}", name),
+        Language::Japanese => format!("{}という変換はありません", name),
+    }
+}
+
+fn invalid_file(error: &impl Display, language: Language) -> String {
+    match language {
+        Language::English => format!("The file is not valid ({})", error),
+        Language::Japanese => format!("ファイルの内容が正しくありません ({})", error),
+    }
+}
+
+fn size_mismatch(expected: (u32, u32), actual: (u32, u32), language: Language) -> String {
+    match language {
+        Language::English => format!(
+            "The images must be the same size ({}x{} and {}x{})",
+            expected.0, expected.1, actual.0, actual.1
+        ),
+        Language::Japanese => format!(
+            "widthとheightの値が等しくありません ({}x{} と {}x{})",
+            expected.0, expected.1, actual.0, actual.1
+        ),
+    }
+}
+
+//スキーマのキーは表示名にする 知らないキー(打ち間違いなど)はそのまま出す
+fn parameter_name(key: &str, language: Language) -> String {
+    let text = match key {
+        "output" => Some(Text::OutputFile),
+        "recipe" => Some(Text::RecipeFile),
+        key => param_name(key),
+    };
+    text.map_or_else(|| key.to_string(), |text| tr_in(text, language).to_string())
+}
+
+fn input_text(input: Input) -> Text {
+    match input {
+        Input::Image => Text::InputImage,
+        Input::SecondImage => Text::InputSecondImage,
+        Input::TextFile => Text::InputTextFile,
+        Input::ConvertedImage => Text::InputConvertedImage,
+        Input::Color => Text::InputColor,
+    }
+}
+
+fn context_message(context: &Context, language: Language) -> String {
+    match (context, language) {
+        (Context::Open(path), Language::English) => format!("Could not open {}", path.display()),
+        (Context::Open(path), Language::Japanese) => format!("{}を開けません", path.display()),
+        (Context::Save(path), Language::English) => format!("Could not save {}", path.display()),
+        (Context::Save(path), Language::Japanese) => {
+            format!("{}に保存できません", path.display())
+        }
+        (Context::Operation(name), Language::English) => format!("Failed to {}", name),
+        (Context::Operation(name), Language::Japanese) => format!("{}に失敗しました", name),
+    }
+}
+
+//ライブラリの列挙型をpick_listなどで表示言語に合わせて出す
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Localized<T>(pub T);
+
+pub trait Localize {
+    fn text(&self) -> Text;
+}
+
+impl<T: Localize> Display for Localized<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", tr(self.0.text()))
+    }
+}
+
+impl Localize for MetadataPolicy {
+    fn text(&self) -> Text {
+        match self {
+            MetadataPolicy::Preserve => Text::KeepMetadata,
+            MetadataPolicy::Strip => Text::StripMetadata,
+            MetadataPolicy::Custom => Text::RemoveSelected,
+        }
+    }
+}
+
+impl Localize for MetadataCategory {
+    fn text(&self) -> Text {
+        match self {
+            MetadataCategory::Camera => Text::Camera,
+            MetadataCategory::Exposure => Text::Exposure,
+            MetadataCategory::Date => Text::Date,
+            MetadataCategory::Gps => Text::Gps,
+        }
+    }
+}
+
+impl Localize for MetadataField {
+    fn text(&self) -> Text {
+        match self {
+            MetadataField::Camera => Text::ExifCamera,
+            MetadataField::Lens => Text::ExifLens,
+            MetadataField::Exposure => Text::ExifExposure,
+            MetadataField::FNumber => Text::ExifFNumber,
+            MetadataField::Iso => Text::ExifIso,
+            MetadataField::FocalLength => Text::ExifFocalLength,
+            MetadataField::Date => Text::ExifDate,
+            MetadataField::Gps => Text::ExifGps,
+            MetadataField::Orientation => Text::ExifOrientation,
+        }
+    }
+}
+
+impl Localize for Sweep {
+    fn text(&self) -> Text {
+        match self {
+            Sweep::HueRotate => Text::HueRotate,
+            Sweep::Rotate => Text::Rotate,
+            Sweep::Blur => Text::Blur,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{path::PathBuf, sync::Arc};
+
+    use super::*;
+
+    #[test]
+    fn from_locale_test() {
+        assert_eq!(Language::from_locale("ja-JP"), Language::Japanese);
+        assert_eq!(Language::from_locale("ja_JP.UTF-8"), Language::Japanese);
+        assert_eq!(Language::from_locale("en-US"), Language::English);
+        assert_eq!(Language::from_locale("fr"), Language::English);
+    }
+
+    #[test]
+    fn metadata_field_test() {
+        //情報パネルの項目名も日本語になる
+        for field in MetadataField::ALL {
+            assert_ne!(
+                tr_in(field.text(), Language::Japanese),
+                tr_in(field.text(), Language::English),
+                "{}",
+                field
+            );
+        }
+    }
+
+    #[test]
+    fn error_message_test() {
+        let error = Error::IOFailed(Arc::new(std::io::Error::from(ErrorKind::NotFound)))
+            .context(Context::Open(PathBuf::from("a.png")));
+
+        assert_eq!(
+            error_message_in(&error, Language::English),
+            "Could not open a.png: File not found"
+        );
+        assert_eq!(
+            error_message_in(&error, Language::Japanese),
+            "a.pngを開けません: ファイルが見つかりません"
+        );
+        let error = Error::MissingInput(Input::SecondImage);
+        assert_eq!(error_message_in(&error, Language::English), "No second image selected");
+
+        //パラメータはキーではなく表示名で出す
+        let error = Error::InvalidParameter {
+            name: "max_side".to_string(),
+            value: "9".to_string(),
+        };
+        assert_eq!(
+            error_message_in(&error, Language::Japanese),
+            format!("{}の値が正しくありません (9)", tr_in(Text::MaxSide, Language::Japanese))
+        );
+        let error = Error::InvalidParameter {
+            name: "radius".to_string(),
+            value: "2".to_string(),
+        };
+        assert_eq!(error_message_in(&error, Language::English), "Invalid value for radius (2)");
+    }
+}
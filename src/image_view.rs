@@ -0,0 +1,847 @@
+use std::fmt::Display;
+
+use iced::{
+    event::{self, Event},
+    widget::image::Handle,
+    Color, Element, Length, Point, Rectangle, Size, Vector,
+};
+use iced_futures::core::{
+    image::Renderer as _,
+    layout, mouse,
+    renderer::{self, Quad, Renderer as _},
+    widget::{tree, Tree},
+    Clipboard, Layout, Shell, Widget,
+};
+
+use crate::{
+    i18n::{tr, Text},
+    Message,
+};
+
+const PANE_GAP: f32 = 4.0;
+const DIVIDER_WIDTH: f32 = 2.0;
+const MIN_SCALE: f32 = 0.02;
+const MAX_SCALE: f32 = 64.0;
+//ホイール1段での倍率
+const ZOOM_STEP: f32 = 1.2;
+//タッチパッドなどピクセル単位のスクロールはこの量で1段とみなす
+const PIXELS_PER_STEP: f32 = 50.0;
+//これ以上拡大したら画素の境目に線を引く
+const GRID_MIN_SCALE: f32 = 8.0;
+const GRID_COLOR: Color = Color::from_rgba(0.5, 0.5, 0.5, 0.6);
+const CROP_COLOR: Color = Color::from_rgb(1.0, 0.85, 0.2);
+
+//変換前(元画像)と変換後の比べ方
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum CompareMode {
+    #[default]
+    Off,
+    SideBySide,
+    Split,
+    Hold,
+}
+
+impl CompareMode {
+    pub const ALL: [CompareMode; 4] = [
+        CompareMode::Off,
+        CompareMode::SideBySide,
+        CompareMode::Split,
+        CompareMode::Hold,
+    ];
+}
+
+impl Display for CompareMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}",
+            tr(match self {
+                CompareMode::Off => Text::CompareOff,
+                CompareMode::SideBySide => Text::SideBySide,
+                CompareMode::Split => Text::SplitView,
+                CompareMode::Hold => Text::HoldOriginal,
+            })
+        )
+    }
+}
+
+//Fit: 全体が収まる Fill: 表示領域が埋まる Scale: 画像の1pxを画面の何pxで描くか
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Zoom {
+    Fit,
+    Fill,
+    Scale(f32),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ZoomPreset {
+    Fit,
+    Fill,
+    Actual,
+    Double,
+}
+
+impl ZoomPreset {
+    pub const ALL: [ZoomPreset; 4] = [
+        ZoomPreset::Fit,
+        ZoomPreset::Fill,
+        ZoomPreset::Actual,
+        ZoomPreset::Double,
+    ];
+
+    pub fn zoom(&self) -> Zoom {
+        match self {
+            ZoomPreset::Fit => Zoom::Fit,
+            ZoomPreset::Fill => Zoom::Fill,
+            ZoomPreset::Actual => Zoom::Scale(1.0),
+            ZoomPreset::Double => Zoom::Scale(2.0),
+        }
+    }
+}
+
+impl Display for ZoomPreset {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ZoomPreset::Fit => write!(f, "{}", tr(Text::ZoomFit)),
+            ZoomPreset::Fill => write!(f, "{}", tr(Text::ZoomFill)),
+            ZoomPreset::Actual => write!(f, "100%"),
+            ZoomPreset::Double => write!(f, "200%"),
+        }
+    }
+}
+
+//表示の状態 左右の画像で同じ倍率と位置を使うのでアプリ側で持つ
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ImageView {
+    pub compare_mode: CompareMode,
+    //分割の位置(左端0.0~右端1.0) 左が元画像
+    pub split: f32,
+    pub holding: bool,
+    pub zoom: Zoom,
+    //表示領域の中央に見える点の、画像の中心からのずれ(画像のpx)
+    pub offset: Vector,
+    //画像1枚分の表示領域の大きさ(並べるときは半分) FitとFillの倍率に使う
+    pub pane: Size,
+    //スポイト クリックで色を取る
+    pub picking: bool,
+}
+
+impl Default for ImageView {
+    fn default() -> Self {
+        Self {
+            compare_mode: CompareMode::Off,
+            split: 0.5,
+            holding: false,
+            zoom: Zoom::Fit,
+            offset: Vector::ZERO,
+            pane: Size::ZERO,
+            picking: false,
+        }
+    }
+}
+
+impl ImageView {
+    //image_sizeは変換後の画像の大きさ 元画像も同じ倍率で描く
+    pub fn scale(&self, image_size: Size) -> f32 {
+        match self.zoom {
+            Zoom::Fit => fit_scale(self.pane, image_size),
+            Zoom::Fill => fill_scale(self.pane, image_size),
+            Zoom::Scale(scale) => scale,
+        }
+    }
+
+    pub fn set_zoom(&mut self, zoom: Zoom) {
+        self.zoom = zoom;
+        self.offset = Vector::ZERO;
+    }
+
+    //cursor(表示領域の中央からの位置)の下の点が動かないように拡大縮小する
+    pub fn zoom_at(&mut self, image_size: Size, cursor: Vector, factor: f32) {
+        let scale = self.scale(image_size);
+        let new_scale = (scale * factor).clamp(MIN_SCALE, MAX_SCALE);
+        self.offset = self.offset + cursor * (1.0 / scale - 1.0 / new_scale);
+        self.zoom = Zoom::Scale(new_scale);
+        self.clamp_offset(image_size);
+    }
+
+    //画面上でdeltaだけ動かす
+    pub fn pan_from(&mut self, image_size: Size, start_offset: Vector, delta: Vector) {
+        self.offset = start_offset - delta * (1.0 / self.scale(image_size));
+        self.clamp_offset(image_size);
+    }
+
+    //画像の端が表示領域の中央より内側に来ないようにする
+    fn clamp_offset(&mut self, image_size: Size) {
+        self.offset = Vector::new(
+            self.offset
+                .x
+                .clamp(-image_size.width / 2.0, image_size.width / 2.0),
+            self.offset
+                .y
+                .clamp(-image_size.height / 2.0, image_size.height / 2.0),
+        );
+    }
+}
+
+//paneに収まる倍率
+pub fn fit_scale(pane: Size, image_size: Size) -> f32 {
+    if image_size.width <= 0.0 || image_size.height <= 0.0 {
+        return 1.0;
+    }
+    (pane.width / image_size.width).min(pane.height / image_size.height)
+}
+
+//paneが埋まる倍率(はみ出した部分は切れる)
+pub fn fill_scale(pane: Size, image_size: Size) -> f32 {
+    if image_size.width <= 0.0 || image_size.height <= 0.0 {
+        return 1.0;
+    }
+    (pane.width / image_size.width).max(pane.height / image_size.height)
+}
+
+//画像を描く位置 画像の中心+offsetがpaneの中央に来る
+pub fn place_image(pane: Rectangle, image_size: Size, scale: f32, offset: Vector) -> Rectangle {
+    let size = Size::new(image_size.width * scale, image_size.height * scale);
+    Rectangle::new(
+        Point::new(
+            pane.center_x() - (image_size.width / 2.0 + offset.x) * scale,
+            pane.center_y() - (image_size.height / 2.0 + offset.y) * scale,
+        ),
+        size,
+    )
+}
+
+//画面上の点を画像の座標(px)に直す 画像の外なら範囲外の値になる
+pub fn screen_to_image(placed: Rectangle, scale: f32, point: Point) -> Point {
+    Point::new((point.x - placed.x) / scale, (point.y - placed.y) / scale)
+}
+
+//変換後の画像と、比較するときは元画像を描く 切り抜きの枠は画像の座標で持つ
+pub struct ImageCanvas {
+    converted: Handle,
+    original: Option<Handle>,
+    view: ImageView,
+    crop: Option<Rectangle>,
+    //縮小したプレビューを描くときの本来の大きさ
+    converted_size: Option<Size>,
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+struct CanvasState {
+    dragging_split: bool,
+    //(押した位置, そのときのoffset)
+    panning: Option<(Point, Vector)>,
+    //ドラッグ中の切り抜きの枠(画像の座標)
+    cropping: Option<(Point, Point)>,
+    //カーソルの下の画素 同じ画素の中で動いたときは知らせない
+    hovered: Option<(Point, bool)>,
+}
+
+impl ImageCanvas {
+    pub fn new(converted: Handle, original: Option<Handle>, view: ImageView) -> Self {
+        Self {
+            converted,
+            original,
+            view,
+            crop: None,
+            converted_size: None,
+        }
+    }
+
+    //変換後の画像の代わりに縮小したプレビューを本来の大きさに引き伸ばして描く
+    pub fn preview(mut self, preview: Handle, size: Size) -> Self {
+        self.converted = preview;
+        self.converted_size = Some(size);
+        self
+    }
+
+    fn converted_size(&self, renderer: &iced::Renderer) -> Size {
+        self.converted_size
+            .unwrap_or_else(|| image_size(renderer, &self.converted))
+    }
+
+    pub fn crop(mut self, crop: Option<Rectangle>) -> Self {
+        self.crop = crop;
+        self
+    }
+
+    fn compare_mode(&self) -> CompareMode {
+        match self.original {
+            Some(_) => self.view.compare_mode,
+            None => CompareMode::Off,
+        }
+    }
+
+    fn panes(&self, bounds: Rectangle) -> Vec<Rectangle> {
+        if self.compare_mode() != CompareMode::SideBySide {
+            return vec![bounds];
+        }
+        let width = ((bounds.width - PANE_GAP) / 2.0).max(0.0);
+        vec![
+            Rectangle { width, ..bounds },
+            Rectangle {
+                x: bounds.x + width + PANE_GAP,
+                width,
+                ..bounds
+            },
+        ]
+    }
+
+    //アプリ側がまだ知らない表示領域の大きさを入れたもの
+    fn current_view(&self, bounds: Rectangle) -> ImageView {
+        ImageView {
+            pane: self.panes(bounds)[0].size(),
+            ..self.view
+        }
+    }
+
+    //カーソルの下の変換後の画像の位置
+    fn image_point(
+        &self,
+        renderer: &iced::Renderer,
+        bounds: Rectangle,
+        position: Point,
+    ) -> Option<Point> {
+        let view = self.current_view(bounds);
+        let size = self.converted_size(renderer);
+        let scale = view.scale(size);
+        let pane = self
+            .panes(bounds)
+            .into_iter()
+            .find(|pane| pane.contains(position))?;
+        Some(screen_to_image(
+            place_image(pane, size, scale, view.offset),
+            scale,
+            position,
+        ))
+    }
+
+    //カーソルの下の画素と、それが元画像かどうか 元画像は大きさが違っても同じ倍率で描く
+    fn source_pixel(
+        &self,
+        renderer: &iced::Renderer,
+        bounds: Rectangle,
+        position: Point,
+    ) -> Option<(Point, bool)> {
+        let view = self.current_view(bounds);
+        let scale = view.scale(self.converted_size(renderer));
+        let panes = self.panes(bounds);
+        let index = panes.iter().position(|pane| pane.contains(position))?;
+        let is_original = match self.compare_mode() {
+            CompareMode::Off => false,
+            CompareMode::SideBySide => index == 0,
+            CompareMode::Split => position.x < bounds.x + bounds.width * self.view.split,
+            CompareMode::Hold => self.view.holding,
+        };
+        let size = match (&self.original, is_original) {
+            (Some(original), true) => image_size(renderer, original),
+            _ => self.converted_size(renderer),
+        };
+        let placed = place_image(panes[index], size, scale, view.offset);
+        let point = screen_to_image(placed, scale, position);
+        Some((Point::new(point.x.floor(), point.y.floor()), is_original))
+    }
+
+    fn draw_image(
+        &self,
+        renderer: &mut iced::Renderer,
+        handle: &Handle,
+        size: Size,
+        pane: Rectangle,
+        clip: Rectangle,
+        scale: f32,
+    ) -> Rectangle {
+        let placed = place_image(pane, size, scale, self.view.offset);
+        renderer.with_layer(clip, |renderer| renderer.draw(handle.clone(), placed));
+        placed
+    }
+
+    //拡大したときの画素の境目と切り抜きの枠
+    fn draw_overlay(
+        &self,
+        renderer: &mut iced::Renderer,
+        state: &CanvasState,
+        placed: Rectangle,
+        clip: Rectangle,
+        scale: f32,
+    ) {
+        renderer.with_layer(clip, |renderer| {
+            if scale >= GRID_MIN_SCALE {
+                draw_grid(renderer, placed, clip, scale);
+            }
+            let crop = match state.cropping {
+                Some((start, end)) => Some(rectangle_between(start, end)),
+                None => self.crop,
+            };
+            if let Some(crop) = crop {
+                let bounds = Rectangle::new(
+                    Point::new(placed.x + crop.x * scale, placed.y + crop.y * scale),
+                    Size::new(crop.width * scale, crop.height * scale),
+                );
+                renderer.fill_quad(
+                    Quad {
+                        bounds,
+                        border_radius: 0.0.into(),
+                        border_width: 1.0,
+                        border_color: CROP_COLOR,
+                    },
+                    Color::TRANSPARENT,
+                );
+            }
+        });
+    }
+
+    fn split_ratio(&self, bounds: Rectangle, position: Point) -> f32 {
+        ((position.x - bounds.x) / bounds.width.max(1.0)).clamp(0.0, 1.0)
+    }
+}
+
+fn image_size(renderer: &iced::Renderer, handle: &Handle) -> Size {
+    let Size { width, height } = renderer.dimensions(handle);
+    Size::new(width as f32, height as f32)
+}
+
+//2点を角とする四角形
+fn rectangle_between(start: Point, end: Point) -> Rectangle {
+    Rectangle::new(
+        Point::new(start.x.min(end.x), start.y.min(end.y)),
+        Size::new((start.x - end.x).abs(), (start.y - end.y).abs()),
+    )
+}
+
+//画素の境目に合わせて画像の大きさの中に収める
+fn snap_to_pixels(rectangle: Rectangle, image_size: Size) -> Option<Rectangle> {
+    let left = rectangle.x.floor().clamp(0.0, image_size.width);
+    let top = rectangle.y.floor().clamp(0.0, image_size.height);
+    let right = (rectangle.x + rectangle.width)
+        .ceil()
+        .clamp(0.0, image_size.width);
+    let bottom = (rectangle.y + rectangle.height)
+        .ceil()
+        .clamp(0.0, image_size.height);
+    (right - left >= 1.0 && bottom - top >= 1.0)
+        .then(|| Rectangle::new(Point::new(left, top), Size::new(right - left, bottom - top)))
+}
+
+fn draw_grid(renderer: &mut iced::Renderer, placed: Rectangle, clip: Rectangle, scale: f32) {
+    let Some(visible) = placed.intersection(&clip) else {
+        return;
+    };
+    let line = |bounds: Rectangle| Quad {
+        bounds,
+        border_radius: 0.0.into(),
+        border_width: 0.0,
+        border_color: Color::TRANSPARENT,
+    };
+
+    let first_column = ((visible.x - placed.x) / scale).floor() as i64;
+    let last_column = ((visible.x + visible.width - placed.x) / scale).ceil() as i64;
+    for column in first_column..=last_column {
+        let x = placed.x + column as f32 * scale;
+        let bounds = Rectangle::new(Point::new(x, visible.y), Size::new(1.0, visible.height));
+        renderer.fill_quad(line(bounds), GRID_COLOR);
+    }
+
+    let first_row = ((visible.y - placed.y) / scale).floor() as i64;
+    let last_row = ((visible.y + visible.height - placed.y) / scale).ceil() as i64;
+    for row in first_row..=last_row {
+        let y = placed.y + row as f32 * scale;
+        let bounds = Rectangle::new(Point::new(visible.x, y), Size::new(visible.width, 1.0));
+        renderer.fill_quad(line(bounds), GRID_COLOR);
+    }
+}
+
+impl Widget<Message, iced::Renderer> for ImageCanvas {
+    fn width(&self) -> Length {
+        Length::Fill
+    }
+
+    fn height(&self) -> Length {
+        Length::Fill
+    }
+
+    fn layout(&self, _renderer: &iced::Renderer, limits: &layout::Limits) -> layout::Node {
+        layout::Node::new(limits.width(Length::Fill).height(Length::Fill).max())
+    }
+
+    fn tag(&self) -> tree::Tag {
+        tree::Tag::of::<CanvasState>()
+    }
+
+    fn state(&self) -> tree::State {
+        tree::State::new(CanvasState::default())
+    }
+
+    fn on_event(
+        &mut self,
+        tree: &mut Tree,
+        event: Event,
+        layout: Layout<'_>,
+        cursor: mouse::Cursor,
+        renderer: &iced::Renderer,
+        _clipboard: &mut dyn Clipboard,
+        shell: &mut Shell<'_, Message>,
+        _viewport: &Rectangle,
+    ) -> event::Status {
+        let bounds = layout.bounds();
+        let state = tree.state.downcast_mut::<CanvasState>();
+        let mut view = self.current_view(bounds);
+        let converted_size = self.converted_size(renderer);
+
+        //FitとFillの倍率は表示領域の大きさで変わるので知らせておく
+        if view.pane != self.view.pane {
+            shell.publish(Message::ImageViewChanged(view));
+        }
+
+        //ステータスバーに出す画素 ほかの操作の邪魔はしない
+        if let Event::Mouse(mouse::Event::CursorMoved { .. } | mouse::Event::CursorLeft) = event {
+            let hovered = cursor
+                .position_over(bounds)
+                .and_then(|position| self.source_pixel(renderer, bounds, position));
+            if hovered != state.hovered {
+                state.hovered = hovered;
+                shell.publish(Message::ImageHovered(hovered));
+            }
+        }
+
+        match (self.compare_mode(), event) {
+            (_, Event::Mouse(mouse::Event::ButtonPressed(mouse::Button::Left)))
+                if self.view.picking =>
+            {
+                let Some(picked) = cursor
+                    .position_over(bounds)
+                    .and_then(|position| self.source_pixel(renderer, bounds, position))
+                else {
+                    return event::Status::Ignored;
+                };
+                shell.publish(Message::ColorPicked(picked));
+                event::Status::Captured
+            }
+            (_, Event::Mouse(mouse::Event::WheelScrolled { delta })) => {
+                let Some(position) = cursor.position_over(bounds) else {
+                    return event::Status::Ignored;
+                };
+                let steps = match delta {
+                    mouse::ScrollDelta::Lines { y, .. } => y,
+                    mouse::ScrollDelta::Pixels { y, .. } => y / PIXELS_PER_STEP,
+                };
+                let pane = self
+                    .panes(bounds)
+                    .into_iter()
+                    .find(|pane| pane.contains(position))
+                    .unwrap_or(bounds);
+                view.zoom_at(
+                    converted_size,
+                    position - pane.center(),
+                    ZOOM_STEP.powf(steps),
+                );
+                shell.publish(Message::ImageViewChanged(view));
+                event::Status::Captured
+            }
+            (
+                _,
+                Event::Mouse(mouse::Event::ButtonPressed(
+                    mouse::Button::Right | mouse::Button::Middle,
+                )),
+            ) => {
+                let Some(position) = cursor.position_over(bounds) else {
+                    return event::Status::Ignored;
+                };
+                state.panning = Some((position, view.offset));
+                event::Status::Captured
+            }
+            (_, Event::Mouse(mouse::Event::CursorMoved { position }))
+                if state.panning.is_some() =>
+            {
+                if let Some((start, start_offset)) = state.panning {
+                    view.pan_from(converted_size, start_offset, position - start);
+                    shell.publish(Message::ImageViewChanged(view));
+                }
+                event::Status::Captured
+            }
+            (
+                _,
+                Event::Mouse(mouse::Event::ButtonReleased(
+                    mouse::Button::Right | mouse::Button::Middle,
+                )),
+            ) if state.panning.is_some() => {
+                state.panning = None;
+                event::Status::Captured
+            }
+            (
+                CompareMode::Split,
+                Event::Mouse(mouse::Event::ButtonPressed(mouse::Button::Left)),
+            ) => {
+                let Some(position) = cursor.position_over(bounds) else {
+                    return event::Status::Ignored;
+                };
+                state.dragging_split = true;
+                shell.publish(Message::CompareSplitChanged(
+                    self.split_ratio(bounds, position),
+                ));
+                event::Status::Captured
+            }
+            (CompareMode::Split, Event::Mouse(mouse::Event::CursorMoved { position }))
+                if state.dragging_split =>
+            {
+                shell.publish(Message::CompareSplitChanged(
+                    self.split_ratio(bounds, position),
+                ));
+                event::Status::Captured
+            }
+            (CompareMode::Hold, Event::Mouse(mouse::Event::ButtonPressed(mouse::Button::Left))) => {
+                if !cursor.is_over(bounds) {
+                    return event::Status::Ignored;
+                }
+                shell.publish(Message::CompareHeld(true));
+                event::Status::Captured
+            }
+            (
+                CompareMode::Hold,
+                Event::Mouse(mouse::Event::ButtonReleased(mouse::Button::Left)),
+            ) if self.view.holding => {
+                shell.publish(Message::CompareHeld(false));
+                event::Status::Captured
+            }
+            //比べていないときと並べているときは左ドラッグで切り抜きの枠を選ぶ
+            (
+                CompareMode::Off | CompareMode::SideBySide,
+                Event::Mouse(mouse::Event::ButtonPressed(mouse::Button::Left)),
+            ) => {
+                let Some(point) = cursor
+                    .position_over(bounds)
+                    .and_then(|position| self.image_point(renderer, bounds, position))
+                else {
+                    return event::Status::Ignored;
+                };
+                state.cropping = Some((point, point));
+                event::Status::Captured
+            }
+            (_, Event::Mouse(mouse::Event::CursorMoved { position }))
+                if state.cropping.is_some() =>
+            {
+                if let (Some((start, _)), Some(point)) =
+                    (state.cropping, self.image_point(renderer, bounds, position))
+                {
+                    state.cropping = Some((start, point));
+                }
+                event::Status::Captured
+            }
+            (_, Event::Mouse(mouse::Event::ButtonReleased(mouse::Button::Left))) => {
+                if let Some((start, end)) = state.cropping.take() {
+                    let crop = snap_to_pixels(rectangle_between(start, end), converted_size);
+                    shell.publish(Message::CropSelected(crop));
+                    event::Status::Captured
+                } else if state.dragging_split {
+                    state.dragging_split = false;
+                    event::Status::Captured
+                } else {
+                    event::Status::Ignored
+                }
+            }
+            _ => event::Status::Ignored,
+        }
+    }
+
+    fn mouse_interaction(
+        &self,
+        tree: &Tree,
+        layout: Layout<'_>,
+        cursor: mouse::Cursor,
+        _viewport: &Rectangle,
+        _renderer: &iced::Renderer,
+    ) -> mouse::Interaction {
+        let state = tree.state.downcast_ref::<CanvasState>();
+        if state.panning.is_some() {
+            return mouse::Interaction::Grabbing;
+        }
+        if !cursor.is_over(layout.bounds()) {
+            return mouse::Interaction::Idle;
+        }
+        if self.view.picking {
+            return mouse::Interaction::Crosshair;
+        }
+        match self.compare_mode() {
+            CompareMode::Split => mouse::Interaction::ResizingHorizontally,
+            CompareMode::Hold => mouse::Interaction::Pointer,
+            CompareMode::Off | CompareMode::SideBySide => mouse::Interaction::Crosshair,
+        }
+    }
+
+    fn draw(
+        &self,
+        tree: &Tree,
+        renderer: &mut iced::Renderer,
+        _theme: &iced::Theme,
+        _style: &renderer::Style,
+        layout: Layout<'_>,
+        _cursor: mouse::Cursor,
+        _viewport: &Rectangle,
+    ) {
+        let state = tree.state.downcast_ref::<CanvasState>();
+        let bounds = layout.bounds();
+        let panes = self.panes(bounds);
+        let converted_size = self.converted_size(renderer);
+        let scale = self.current_view(bounds).scale(converted_size);
+
+        let Some(original) = &self.original else {
+            let placed = self.draw_image(
+                renderer,
+                &self.converted,
+                converted_size,
+                bounds,
+                bounds,
+                scale,
+            );
+            self.draw_overlay(renderer, state, placed, bounds, scale);
+            return;
+        };
+        let original_size = image_size(renderer, original);
+
+        match self.compare_mode() {
+            CompareMode::SideBySide => {
+                let placed =
+                    self.draw_image(renderer, original, original_size, panes[0], panes[0], scale);
+                self.draw_overlay(renderer, state, placed, panes[0], scale);
+                let placed = self.draw_image(
+                    renderer,
+                    &self.converted,
+                    converted_size,
+                    panes[1],
+                    panes[1],
+                    scale,
+                );
+                self.draw_overlay(renderer, state, placed, panes[1], scale);
+            }
+            CompareMode::Split => {
+                let split_x = bounds.width * self.view.split;
+                let left = Rectangle {
+                    width: split_x,
+                    ..bounds
+                };
+                let right = Rectangle {
+                    x: bounds.x + split_x,
+                    width: bounds.width - split_x,
+                    ..bounds
+                };
+                let placed =
+                    self.draw_image(renderer, original, original_size, bounds, left, scale);
+                self.draw_overlay(renderer, state, placed, left, scale);
+                let placed = self.draw_image(
+                    renderer,
+                    &self.converted,
+                    converted_size,
+                    bounds,
+                    right,
+                    scale,
+                );
+                self.draw_overlay(renderer, state, placed, right, scale);
+
+                renderer.fill_quad(
+                    Quad {
+                        bounds: Rectangle {
+                            x: bounds.x + split_x - DIVIDER_WIDTH / 2.0,
+                            width: DIVIDER_WIDTH,
+                            ..bounds
+                        },
+                        border_radius: 0.0.into(),
+                        border_width: 0.0,
+                        border_color: Color::TRANSPARENT,
+                    },
+                    Color::WHITE,
+                );
+            }
+            CompareMode::Hold if self.view.holding => {
+                let placed =
+                    self.draw_image(renderer, original, original_size, bounds, bounds, scale);
+                self.draw_overlay(renderer, state, placed, bounds, scale);
+            }
+            CompareMode::Hold | CompareMode::Off => {
+                let placed = self.draw_image(
+                    renderer,
+                    &self.converted,
+                    converted_size,
+                    bounds,
+                    bounds,
+                    scale,
+                );
+                self.draw_overlay(renderer, state, placed, bounds, scale);
+            }
+        }
+    }
+}
+
+impl<'a> From<ImageCanvas> for Element<'a, Message> {
+    fn from(canvas: ImageCanvas) -> Self {
+        Element::new(canvas)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn place_image_test() {
+        let pane = Rectangle::new(Point::new(10.0, 0.0), Size::new(200.0, 100.0));
+        let image_size = Size::new(400.0, 100.0);
+
+        assert_eq!(fit_scale(pane.size(), image_size), 0.5);
+        assert_eq!(fill_scale(pane.size(), image_size), 1.0);
+        let placed = place_image(pane, image_size, 0.5, Vector::ZERO);
+        assert_eq!(
+            placed,
+            Rectangle::new(Point::new(10.0, 25.0), Size::new(200.0, 50.0))
+        );
+
+        //offsetの分だけ画像の右下が中央に寄る
+        let placed = place_image(pane, image_size, 1.0, Vector::new(100.0, 0.0));
+        assert_eq!(
+            screen_to_image(placed, 1.0, pane.center()),
+            Point::new(300.0, 50.0)
+        );
+    }
+
+    #[test]
+    fn zoom_at_test() {
+        let image_size = Size::new(400.0, 200.0);
+        let pane = Rectangle::new(Point::ORIGIN, Size::new(200.0, 100.0));
+        let mut view = ImageView {
+            pane: pane.size(),
+            ..ImageView::default()
+        };
+        let cursor = Point::new(150.0, 30.0);
+        let scale = view.scale(image_size);
+        let before = screen_to_image(
+            place_image(pane, image_size, scale, view.offset),
+            scale,
+            cursor,
+        );
+
+        //カーソルの下の点は動かない
+        view.zoom_at(image_size, cursor - pane.center(), 4.0);
+        assert_eq!(view.zoom, Zoom::Scale(2.0));
+        let after = screen_to_image(place_image(pane, image_size, 2.0, view.offset), 2.0, cursor);
+        assert!((before.x - after.x).abs() < 1e-3 && (before.y - after.y).abs() < 1e-3);
+
+        view.pan_from(image_size, Vector::ZERO, Vector::new(10_000.0, 0.0));
+        assert_eq!(view.offset.x, -200.0);
+    }
+
+    #[test]
+    fn snap_to_pixels_test() {
+        let image_size = Size::new(10.0, 10.0);
+        let crop = rectangle_between(Point::new(8.5, 2.2), Point::new(-3.0, 0.7));
+        assert_eq!(
+            snap_to_pixels(crop, image_size),
+            Some(Rectangle::new(Point::new(0.0, 0.0), Size::new(9.0, 3.0)))
+        );
+        assert_eq!(
+            snap_to_pixels(
+                rectangle_between(Point::new(12.0, 1.0), Point::new(15.0, 4.0)),
+                image_size
+            ),
+            None
+        );
+    }
+}
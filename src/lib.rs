@@ -0,0 +1,4 @@
+//GUIとベンチマークから使う画像処理の部分
+mod coffee_image;
+
+pub use coffee_image::*;
@@ -0,0 +1,142 @@
+use std::{
+    path::PathBuf,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use iced::{widget::image::Handle, Command, Size};
+use image::DynamicImage;
+
+use coffee_image::{
+    convert::{
+        image_wrap::{downscale_image, get_dynamic_image},
+        operation::Operation,
+        progress::Progress,
+    },
+    error::Error,
+};
+
+use crate::{convert_job::ConvertJob, Message};
+
+//プレビューの長い辺
+const PREVIEW_MAX_SIDE: u32 = 640;
+//値が止まってからこれだけ待って作る
+const PREVIEW_DELAY: Duration = Duration::from_millis(200);
+
+//縮小した変換前の画像 同じ画像なら作り直さない
+#[derive(Debug, Clone)]
+pub struct PreviewSource {
+    path: PathBuf,
+    image: Arc<DynamicImage>,
+    //元の画像に対する倍率
+    factor: f32,
+}
+
+#[derive(Debug, Clone)]
+pub struct PreviewOutput {
+    source: PreviewSource,
+    handle: Handle,
+    //元の画像の倍率に戻した大きさ
+    size: Size,
+}
+
+impl PreviewSource {
+    fn load(path: PathBuf) -> Result<Self, Error> {
+        let (image, factor) = downscale_image(&get_dynamic_image(&path)?, PREVIEW_MAX_SIDE);
+        Ok(Self {
+            path,
+            image: Arc::new(image),
+            factor,
+        })
+    }
+
+    fn render(self, operation: &Operation, progress: &Progress) -> Result<PreviewOutput, Error> {
+        let converted = operation
+            .scaled(self.factor)
+            .apply_with_progress(&self.image, progress)?
+            .to_rgba8();
+        let (width, height) = converted.dimensions();
+        Ok(PreviewOutput {
+            size: Size::new(width as f32 / self.factor, height as f32 / self.factor),
+            handle: Handle::from_pixels(width, height, converted.into_raw()),
+            source: self,
+        })
+    }
+}
+
+//パラメータを動かしている間の縮小プレビュー 変換を押したら元の大きさで作り直す
+#[derive(Debug, Clone, Default)]
+pub struct LivePreview {
+    source: Option<PreviewSource>,
+    //最後に値が変わった時刻
+    requested: Option<Instant>,
+    job: Option<ConvertJob>,
+    output: Option<(Handle, Size)>,
+}
+
+impl LivePreview {
+    pub fn request(&mut self, now: Instant) {
+        self.requested = Some(now);
+    }
+
+    pub fn is_pending(&self) -> bool {
+        self.requested.is_some()
+    }
+
+    //PREVIEW_DELAYの間、値が変わらなければtrue
+    pub fn is_due(&mut self, now: Instant) -> bool {
+        match self.requested {
+            Some(requested) if now.duration_since(requested) >= PREVIEW_DELAY => {
+                self.requested = None;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    pub fn output(&self) -> Option<(Handle, Size)> {
+        self.output.clone()
+    }
+
+    //表示中のプレビューを消す 縮小した画像は残す
+    pub fn clear(&mut self) {
+        if let Some(job) = self.job.take() {
+            job.progress.cancel();
+        }
+        self.requested = None;
+        self.output = None;
+    }
+
+    pub fn start(&mut self, path: PathBuf, operation: Operation) -> Command<Message> {
+        if let Some(job) = self.job.take() {
+            job.progress.cancel();
+        }
+        let job = ConvertJob::new();
+        let (id, progress) = (job.id, job.progress.clone());
+        self.job = Some(job);
+
+        let source = self.source.clone().filter(|source| source.path == path);
+        Command::perform(
+            async move {
+                let source = match source {
+                    Some(source) => source,
+                    None => PreviewSource::load(path)?,
+                };
+                source.render(&operation, &progress)
+            },
+            move |result| Message::PreviewRendered(id, result),
+        )
+    }
+
+    //古い結果と失敗は捨てる 失敗は変換を押したときに知らせる
+    pub fn finish(&mut self, id: u64, result: Result<PreviewOutput, Error>) {
+        if self.job.as_ref().map(|job| job.id) != Some(id) {
+            return;
+        }
+        self.job = None;
+        if let Ok(output) = result {
+            self.output = Some((output.handle, output.size));
+            self.source = Some(output.source);
+        }
+    }
+}
@@ -1,49 +1,125 @@
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")] // hide console window on Windows in release
 use coffee_image::{
-    convert::image_wrap::{get_dynamic_image, ImageConverter},
-    error::Error,
+    convert::{
+        animation::Animation,
+        image_wrap::{get_dynamic_image, save_temp_image, ImageConverter},
+        operation::Operation,
+    },
+    encoder_options::{
+        ChromaSubsampling, EncoderOptions, PngCompression, PngFilter, TiffCompression,
+    },
+    error::{Error, Input},
+    metadata::{ImageMetadata, MetadataCategory, MetadataOptions, MetadataPolicy},
     io::{
-        coffee_image_io::{self, image_open, mkdir_result_temp_folder, remove_all_temp_file, save},
-        dialog::error_dialog_show,
+        coffee_image_io::{
+            self, image_open, save,
+            images_open, recipe_open, recipe_save_path, save_animation, save_text, text_open,
+        },
+        temp_session::{sweep_stale_sessions, SessionGuard},
+        text::TextFile,
     },
     save_format::{self, SaveFormat},
+    string_art::{
+        ascii::{grid_size, scale_from_columns, Ramp},
+        reverse::ascii_file_to_image,
+    },
+    text_format::TextFormat,
 };
 use iced_futures::core::Widget;
 
-use std::path::PathBuf;
+use std::{collections::HashMap, path::PathBuf, sync::Arc, time::Instant};
 
 use iced::{
     executor,
     keyboard::{self, KeyCode, Modifiers},
-    widget::{button, column, container, horizontal_space, pick_list, row, text_input, Image, canvas},
-    Application, Command, Event, Length, Settings, Theme, mouse,
+    widget::{
+        button, canvas, checkbox, column, container, horizontal_space, pick_list, row, slider,
+        image::Handle, text, text_input,
+    },
+    Application, Command, Event, Length, Settings, Theme,
+};
+use image::{Pixel, Rgba, RgbaImage};
+use animation_player::AnimationPlayer;
+use color_picker::{pixel_at, swatch_color, PixelInfo, PixelProbe};
+use convert_job::{ConvertJob, ConvertOutput};
+use gif_maker::GifSettings;
+use image_view::{CompareMode, ImageCanvas, ImageView, Zoom, ZoomPreset};
+use live_preview::{LivePreview, PreviewOutput};
+use i18n::{
+    ascii_grid, error_message, remove_category, saved_to, set_language, tr, Language, Localized, Text,
 };
-use select_mode::SelectMode;
+use notification::{Level, Notifications};
+use pipeline::{Pipeline, Step};
+use recipe::{Preset, Recipe};
+use select_mode::{ParamKind, ParamValue, SelectMode};
+use size_estimate::SizeEstimate;
 use text_viewer_::TextViewerState;
 
-mod coffee_image;
+mod animation_player;
+mod cli;
+mod color_picker;
 mod components;
+mod convert_job;
+mod gif_maker;
+mod i18n;
+mod image_view;
+mod live_preview;
+mod notification;
+mod pipeline;
+mod recipe;
 mod select_mode;
+mod size_estimate;
 mod text_viewer_;
+
 //https://github.com/iced-rs/iced
 //https://docs.rs/iced/latest/iced/
 //https://zenn.dev/tris/articles/e60efe7c60a770
 fn main() -> iced::Result {
-    init();
-    ImageState::run(Settings::default())
+    let session = init();
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if cli::is_cli(&args) {
+        let code = cli::run(&args);
+        //exitはデストラクタを呼ばないので先に片付ける
+        drop(session);
+        std::process::exit(code);
+    }
+    ImageState::run(Settings::with_flags(args.first().map(PathBuf::from)))
 }
 
 #[derive(Debug, Clone)]
 struct ImageState {
     image_paths: (Option<PathBuf>, Option<PathBuf>),
-    error: Option<Error>,
+    //変換前と比べるために開いたときの画像を残しておく
+    original_path: Option<PathBuf>,
+    //表示している画像の大きさ
+    image_dimensions: Option<(u32, u32)>,
+    image_view: ImageView,
+    ascii_text_path: Option<PathBuf>,
+    notifications: Notifications,
+    convert_job: Option<ConvertJob>,
     image_converter: ImageConverter,
     mode: SelectMode,
-    input_value: String,
+    //モードごとのパラメータ 未設定なら既定値
+    steps: HashMap<SelectMode, Step>,
+    //今のモードの数値入力欄の文字列 数として読めたときだけ値に入れる
+    param_inputs: Vec<String>,
+    //開いてから適用した変換 レシピとして保存できる
+    pipeline: Pipeline,
+    //変換中のステップ 成功したらpipelineに足す
+    converting_steps: Vec<Step>,
+    live_preview: LivePreview,
+    ascii_columns: String,
     view_state: ViewState,
-    cursor_position: Vec<iced::Point>,
-    rectangle:Option< iced::Rectangle>,
-    mouse_state:MouseState,
+    size_estimate: SizeEstimate,
+    animation_player: Option<AnimationPlayer>,
+    gif_settings: GifSettings,
+    //切り抜きの枠(画像の座標)
+    rectangle: Option<iced::Rectangle>,
+    pixel_probe: PixelProbe,
+    //カーソルの下の画素
+    hovered: Option<PixelInfo>,
+    //スポイトで取った色 回転の背景、クロマキー、二値化に使う
+    picked_color: Option<Rgba<u8>>,
 }
 #[derive(Debug, Clone)]
 struct ViewState {
@@ -51,17 +127,6 @@ struct ViewState {
     text_view: Option<TextViewerState>,
 }
 
-#[derive(Debug,Clone)]
-struct MouseState{
-    is_pressed:bool,
-    is_released:bool,
-}
-
-impl Default for MouseState{
-    fn default() -> Self {
-        Self { is_pressed: false, is_released:false }
-    }
-}
 #[derive(Debug, Clone)]
 pub enum Views {
     Image,
@@ -71,15 +136,55 @@ pub enum Views {
 pub enum Message {
     Open,
     ImageOpened(Result<PathBuf, Error>),
+    AsciiTextOpened(Result<PathBuf, Error>),
     Save,
     ImageSaved(Result<PathBuf, Error>),
     Convert,
-    GrayConverted(Result<ImageConverter, Error>),
+    Converted(u64, Result<ConvertOutput, Error>),
+    CancelConvert,
+    ConvertProgressed(Instant),
     Selected(SelectMode),
-    InputChanged(String),
+    ParamChanged(&'static str, ParamValue),
+    ParamInputChanged(&'static str, String),
+    PreviewTick(Instant),
+    PreviewRendered(u64, Result<PreviewOutput, Error>),
+    PresetSelected(Preset),
+    RecipeOpen,
+    RecipeOpened(Result<Recipe, Error>),
+    RecipeSave,
+    RecipeSaved(Result<PathBuf, Error>),
+    AsciiColumnsChanged(String),
     ViewChanged(Views),
     SaveFormatSelected(SaveFormat),
+    EncoderOptionsChanged(EncoderOptions),
+    MetadataOptionsChanged(MetadataOptions),
+    SizeEstimateTick(Instant),
+    SizeEstimated(u64, Result<usize, Error>),
     EventOccurred(Event),
+    AnimationTick(Instant),
+    GifSettingsChanged(GifSettings),
+    GifImagesOpen,
+    GifImagesOpened(Result<Vec<PathBuf>, Error>),
+    TextFontSizeChanged(u16),
+    TextScaleChanged(u32),
+    TextRerun,
+    TextFormatSelected(TextFormat),
+    TextSave,
+    TextSaved(Result<PathBuf, Error>),
+    NotificationDismissed(u64),
+    LanguageSelected(Language),
+    CompareModeSelected(CompareMode),
+    CompareSplitChanged(f32),
+    CompareHeld(bool),
+    ImageViewChanged(ImageView),
+    ZoomPresetSelected(ZoomPreset),
+    CropSelected(Option<iced::Rectangle>),
+    //(画像の座標, 元画像かどうか)
+    ImageHovered(Option<(iced::Point, bool)>),
+    ColorPicked((iced::Point, bool)),
+    ProbeImageLoaded(PathBuf, Option<Arc<RgbaImage>>),
+    PickingToggled,
+    NotificationTick(Instant),
     Exit,
 }
 
@@ -90,26 +195,42 @@ impl Application for ImageState {
 
     type Theme = Theme;
 
-    type Flags = ();
-
-    fn new(_flags: Self::Flags) -> (Self, iced::Command<Message>) {
-        (
-            Self {
-                image_paths: (None, None),
-                error: None,
-                image_converter: ImageConverter::new(),
-                mode: SelectMode::default(),
-                input_value: "".to_string(),
-                view_state: ViewState {
-                    current_view: Views::Image,
-                    text_view: None,
-                },
-                cursor_position: vec![iced::Point::default();2],
-                rectangle: None,
-                mouse_state:MouseState::default(),
+    //引数で渡された画像
+    type Flags = Option<PathBuf>;
+
+    fn new(image_path: Self::Flags) -> (Self, iced::Command<Message>) {
+        let mut state = Self {
+            image_paths: (None, None),
+            original_path: None,
+            image_dimensions: None,
+            image_view: ImageView::default(),
+            ascii_text_path: None,
+            notifications: Notifications::default(),
+            convert_job: None,
+            image_converter: ImageConverter::new(),
+            mode: SelectMode::default(),
+            steps: HashMap::new(),
+            param_inputs: Vec::new(),
+            pipeline: Pipeline::default(),
+            converting_steps: Vec::new(),
+            live_preview: LivePreview::default(),
+            ascii_columns: "".to_string(),
+            view_state: ViewState {
+                current_view: Views::Image,
+                text_view: None,
             },
-            Command::none(),
-        )
+            size_estimate: SizeEstimate::default(),
+            animation_player: None,
+            gif_settings: GifSettings::default(),
+            rectangle: None,
+            pixel_probe: PixelProbe::default(),
+            hovered: None,
+            picked_color: None,
+        };
+        if let Some(path) = image_path {
+            state.open_image(path);
+        }
+        (state, Command::none())
     }
 
     fn title(&self) -> String {
@@ -118,92 +239,170 @@ impl Application for ImageState {
 
     fn update(&mut self, message: Message) -> iced::Command<Message> {
         match message {
-            Message::Open => Command::perform(coffee_image_io::image_open(), Message::ImageOpened),
+            Message::Open => Command::perform(coffee_image_io::image_open(tr(Text::OpenImageTitle)), Message::ImageOpened),
             Message::ImageOpened(Ok(path)) => {
                 if self.mode == SelectMode::Add {
                     self.image_paths.1 = Some(path)
                 } else {
-                    self.image_paths.0 = Some(path);
+                    self.open_image(path);
                 }
 
                 Command::none()
             }
             Message::ImageOpened(Err(error)) => {
-                self.error = Some(error.clone());
-                error_dialog_show(error);
+                self.report_error(error);
+                Command::none()
+            }
+            Message::AsciiTextOpened(Ok(path)) => {
+                self.ascii_text_path = Some(path);
+                Command::none()
+            }
+            Message::AsciiTextOpened(Err(error)) => {
+                self.report_error(error);
+                Command::none()
+            }
+            Message::Save => self.save_command(),
+            Message::ImageSaved(Ok(path)) => {
+                self.notifications.push(Level::Info, saved_to(&path));
                 Command::none()
             }
-            Message::Save => Command::perform(
-                save(
-                    None,
-                    self.image_converter.clone(),
-                    self.image_converter.save_format.clone(),
-                ),
-                Message::ImageSaved,
-            ),
-            Message::ImageSaved(Ok(_path)) => Command::none(),
             Message::ImageSaved(Err(error)) => {
-                self.error = Some(error.clone());
-                error_dialog_show(error);
+                self.report_error(error);
                 Command::none()
             }
             Message::Convert => {
-                self.image_converter
-                    .set_image_path(self.image_paths.0.clone().unwrap());
-                let converted_image = match self.mode {
-                    SelectMode::BitwiseNot => self.image_converter.bitwise_not(),
-                    SelectMode::Gray => self.image_converter.gray_scale(),
-                    SelectMode::HueRotate => self
-                        .image_converter
-                        .hue_rotate(self.convert_input_value_to_float() as i32),
-                    SelectMode::Blur => self
-                        .image_converter
-                        .blur(self.convert_input_value_to_float()),
-                    SelectMode::Add => self
-                        .image_converter
-                        .add_images(self.image_paths.1.as_ref().unwrap()),
-                    SelectMode::ToAscii => {
-                        let path = self.image_converter.clone().ascii_art(4);
-
-                        self.view_state.text_view = Some(TextViewerState::new(
-                            path.unwrap_or_else(|error| error.show_dialog_return_default()),
-                        ));
-                        self.view_state.current_view = Views::Text;
-                        get_dynamic_image(self.image_paths.0.as_ref().unwrap())
+                self.live_preview.clear();
+                let step = self.current_step();
+                match step.operation() {
+                    Ok(Some(operation)) => {
+                        self.converting_steps = vec![step];
+                        self.start_convert(operation)
                     }
-                    SelectMode::Rotate => self
-                        .image_converter
-                        .rotate(self.convert_input_value_to_float()),
-                };
-                let converted_image =
-                    converted_image.unwrap_or_else(|error| error.show_dialog_return_default());
-                self.image_converter.save_temp_result_image(converted_image);
-                self.image_paths.0 = self.image_converter.get_temp_result_path();
-
-                Command::none()
+                    Ok(None) => self.convert_without_operation(step),
+                    Err(error) => {
+                        self.report_error(error);
+                        Command::none()
+                    }
+                }
             }
-            Message::GrayConverted(result) => {
-                match result {
-                    Ok(image_converter) => self.image_converter = image_converter,
-                    Err(error) => self.error = Some(error),
+            Message::Converted(id, result) => {
+                //別の画像を開いたり中止したりした後に届いた結果は捨てる
+                if self.convert_job.as_ref().map(|job| job.id) != Some(id) {
+                    return Command::none();
+                }
+                self.convert_job = None;
+                if result.is_ok() {
+                    self.pipeline.steps.append(&mut self.converting_steps);
                 }
 
+                match result {
+                    Ok(ConvertOutput::Image(path)) => {
+                        self.image_converter.set_temp_result_path(path);
+                        self.set_current_image(self.image_converter.get_temp_result_path());
+                        self.request_size_estimate();
+                        Command::none()
+                    }
+                    Ok(ConvertOutput::Animation(animation)) => {
+                        self.animation_player = Some(AnimationPlayer::new(animation));
+                        Command::none()
+                    }
+                    Err(error) => {
+                        self.report_error(error);
+                        Command::none()
+                    }
+                }
+            }
+            Message::CancelConvert => {
+                if let Some(job) = self.convert_job.take() {
+                    job.progress.cancel();
+                    self.report_error(Error::Cancelled);
+                }
                 Command::none()
             }
+            Message::ConvertProgressed(_now) => Command::none(),
             Message::Selected(mode) => {
                 self.mode = mode;
+                self.live_preview.clear();
+                let step = self.current_step();
+                self.param_inputs = mode
+                    .params()
+                    .iter()
+                    .zip(step.values.iter())
+                    .map(|(spec, value)| spec.label(value))
+                    .collect();
                 if self.mode == SelectMode::Add {
-                    Command::perform(image_open(), Message::ImageOpened)
+                    Command::perform(image_open(tr(Text::OpenImageTitle)), Message::ImageOpened)
+                } else if self.mode == SelectMode::FromAscii {
+                    Command::perform(text_open(tr(Text::OpenTextTitle)), Message::AsciiTextOpened)
                 } else {
                     Command::none()
                 }
             }
             Message::SaveFormatSelected(save_format) => {
                 self.image_converter.save_format = save_format;
+                self.request_size_estimate();
+                Command::none()
+            }
+            Message::EncoderOptionsChanged(encoder_options) => {
+                self.image_converter.encoder_options = encoder_options;
+                self.request_size_estimate();
+                Command::none()
+            }
+            Message::MetadataOptionsChanged(metadata_options) => {
+                self.image_converter.metadata_options = metadata_options;
+                self.request_size_estimate();
+                Command::none()
+            }
+            Message::SizeEstimateTick(now) => {
+                if self.size_estimate.is_due(now) {
+                    return self.size_estimate.start(self.image_converter.clone());
+                }
+                Command::none()
+            }
+            Message::SizeEstimated(id, result) => {
+                self.size_estimate.finish(id, result);
                 Command::none()
             }
-            Message::InputChanged(value) => {
-                self.input_value = value;
+            Message::ParamChanged(key, value) => {
+                self.set_param(key, value);
+                Command::none()
+            }
+            Message::ParamInputChanged(key, input) => {
+                let Some(index) = self.mode.params().iter().position(|spec| spec.key == key) else {
+                    return Command::none();
+                };
+                let number = input.trim().parse::<f32>().ok();
+                if let Some(value) = number.and_then(|number| self.mode.params()[index].number(number)) {
+                    self.set_param(key, value);
+                }
+                if let Some(param_input) = self.param_inputs.get_mut(index) {
+                    *param_input = input;
+                }
+                Command::none()
+            }
+            Message::PreviewTick(now) => {
+                if !self.live_preview.is_due(now) {
+                    return Command::none();
+                }
+                match (self.image_paths.0.clone(), self.selected_operation()) {
+                    (Some(path), Ok(Some(operation))) => self.live_preview.start(path, operation),
+                    _ => Command::none(),
+                }
+            }
+            Message::PreviewRendered(id, result) => {
+                self.live_preview.finish(id, result);
+                Command::none()
+            }
+            //列数から1文字の幅を決める
+            Message::AsciiColumnsChanged(value) => {
+                self.ascii_columns = value;
+                let columns = self.ascii_columns.trim().parse::<u32>().ok();
+                if let (Some(columns), Some((width, _))) = (columns, self.image_dimensions) {
+                    let scale = scale_from_columns(width, columns) as f32;
+                    if let Some(scale) = self.mode.param("scale").and_then(|spec| spec.number(scale)) {
+                        self.set_param("scale", scale);
+                    }
+                }
                 Command::none()
             }
             Message::ViewChanged(views) => {
@@ -214,7 +413,7 @@ impl Application for ImageState {
                 match event {
                     Event::Window(window_event) => {
                         if let iced::window::Event::FileDropped(dropped_image_path) = window_event {
-                            self.image_paths.0 = Some(dropped_image_path)
+                            self.open_image(dropped_image_path)
                         }
                     }
                     Event::Keyboard(key_event) => {
@@ -226,62 +425,203 @@ impl Application for ImageState {
                             if key_code == KeyCode::B && modifiers.command() {
                                 return iced::window::close();
                             } else if key_code == KeyCode::S && modifiers.command() {
-                                return Command::perform(
-                                    save(
-                                        None,
-                                        self.image_converter.clone(),
-                                        self.image_converter.save_format.clone(),
-                                    ),
-                                    Message::ImageSaved,
-                                );
+                                return self.save_command();
                             } else if key_code == KeyCode::O && modifiers.command() {
                                 return Command::perform(
-                                    coffee_image_io::image_open(),
+                                    coffee_image_io::image_open(tr(Text::OpenImageTitle)),
                                     Message::ImageOpened,
                                 );
                             }
                         }
                     }
-                    Event::Mouse(mouse_event)=>{
-                        let mut posi1 = iced::Point::default();
-                        
-                        if let mouse::Event::CursorMoved { position }=mouse_event{
-                            //println!("{},{}",position.x,position.y);
-                            posi1=position;
-                        }
-                        if let mouse::Event::ButtonPressed(button)=mouse_event{
-                            if mouse::Button::Left==button{
-                                //println!("left");
-                                self.mouse_state.is_pressed=true;
-                                self.cursor_position[0]=posi1;
-                                //println!("{},{}",self.cursor_position[0].x,self.cursor_position[0].y);
-                            }
-                        }
-                        if let mouse::Event::CursorMoved { position } =mouse_event{
-                            //println!("{},{}",position.x,position.y);
-                            posi1=position;
-                        }
-                        if let mouse::Event::ButtonReleased(button)=mouse_event{
-                            if mouse::Button::Left==button{
-                                self.mouse_state.is_released=true;
-                                self.cursor_position[1]=posi1;
-                                
-                                
-                                //println!("{},{}",self.cursor_position[1].x,self.cursor_position[1].y);
-                            }
+                    _ => {}
+                }
+                Command::none()
+            }
+            Message::TextFontSizeChanged(font_size) => {
+                if let Some(text_view) = self.view_state.text_view.as_mut() {
+                    text_view.font_size = font_size;
+                }
+                Command::none()
+            }
+            Message::TextScaleChanged(scale) => {
+                if let Some(text_view) = self.view_state.text_view.as_mut() {
+                    text_view.scale = scale;
+                }
+                Command::none()
+            }
+            Message::TextRerun => {
+                if let Some(text_view) = self.view_state.text_view.as_ref() {
+                    let text_file =
+                        self.ascii_convert(text_view.mode, text_view.scale, text_view.ramp);
+                    self.view_state.text_view = Some(text_view.rerun(text_file));
+                }
+                Command::none()
+            }
+            Message::TextFormatSelected(text_format) => {
+                if let Some(text_view) = self.view_state.text_view.as_mut() {
+                    text_view.text_format = text_format;
+                }
+                Command::none()
+            }
+            Message::TextSave => {
+                let Some(text_view) = self.view_state.text_view.as_ref() else {
+                    return Command::none();
+                };
+                let Some(content) = text_view.content() else {
+                    return Command::none();
+                };
+                let colors = if text_view.text_format.is_colored() {
+                    match self.image_converter.ascii_cell_colors(text_view.scale) {
+                        Ok(colors) => colors,
+                        Err(error) => {
+                            self.report_error(error);
+                            return Command::none();
                         }
-                        if self.mouse_state.is_pressed && self.mouse_state.is_released{
-                            let position1=self.cursor_position[0];
-                            let position2 = self.cursor_position[1];
-                            let size = iced::Size::new((position2.x-position1.x).abs() , (position1.y-position2.y).abs());
-                            self.rectangle = Some(iced::Rectangle::new(position2, size));
-                            self.mouse_state.is_pressed=false;
-                            self.mouse_state.is_released=false;
+                    }
+                } else {
+                    Vec::new()
+                };
+                Command::perform(
+                    save_text(
+                        tr(Text::SaveTitle),
+                        text_view.text_format.render(content, &colors),
+                        text_view.text_format,
+                    ),
+                    Message::TextSaved,
+                )
+            }
+            Message::TextSaved(Ok(path)) => {
+                self.notifications.push(Level::Info, saved_to(&path));
+                Command::none()
+            }
+            Message::TextSaved(Err(error)) => {
+                self.report_error(error);
+                Command::none()
+            }
+            Message::AnimationTick(now) => {
+                if let Some(player) = self.animation_player.as_mut() {
+                    player.tick(now);
+                }
+                Command::none()
+            }
+            Message::GifSettingsChanged(gif_settings) => {
+                self.gif_settings = gif_settings;
+                Command::none()
+            }
+            Message::GifImagesOpen => {
+                Command::perform(images_open(tr(Text::OpenImageTitle)), Message::GifImagesOpened)
+            },
+            Message::GifImagesOpened(Ok(paths)) => {
+                self.gif_settings.image_paths = paths;
+                Command::none()
+            }
+            Message::GifImagesOpened(Err(error)) => {
+                self.report_error(error);
+                Command::none()
+            }
+            Message::LanguageSelected(language) => {
+                set_language(language);
+                Command::none()
+            }
+            Message::CompareModeSelected(compare_mode) => {
+                self.image_view.compare_mode = compare_mode;
+                self.image_view.holding = false;
+                Command::none()
+            }
+            Message::CompareSplitChanged(split) => {
+                self.image_view.split = split;
+                Command::none()
+            }
+            Message::CompareHeld(holding) => {
+                self.image_view.holding = holding;
+                Command::none()
+            }
+            Message::ImageViewChanged(image_view) => {
+                self.image_view = image_view;
+                Command::none()
+            }
+            Message::ZoomPresetSelected(preset) => {
+                self.image_view.set_zoom(preset.zoom());
+                Command::none()
+            }
+            Message::CropSelected(rectangle) => {
+                self.rectangle = rectangle;
+                Command::none()
+            }
+            Message::ImageHovered(source) => {
+                self.hovered = source.and_then(|source| self.probe_pixel(source));
+                match source {
+                    Some((_, is_original)) => self.load_probe_image(is_original),
+                    None => Command::none(),
+                }
+            }
+            Message::ColorPicked(source) => {
+                if let Some(pixel) = self.probe_pixel(source) {
+                    self.picked_color = Some(pixel.color);
+                    self.image_view.picking = false;
+                    //二値化は取った色の明るさをしきい値にする
+                    if self.mode == SelectMode::Threshold {
+                        let level = pixel.color.to_luma().0[0] as f32 / 255.0 * 100.0;
+                        if let Some(level) = self.mode.param("level").and_then(|spec| spec.number(level)) {
+                            self.set_param("level", level);
                         }
-                        
                     }
-                    _ => {}
                 }
+                self.load_probe_image(source.1)
+            }
+            Message::ProbeImageLoaded(path, image) => {
+                self.pixel_probe.finish(path, image);
+                Command::none()
+            }
+            Message::PickingToggled => {
+                self.image_view.picking = !self.image_view.picking;
+                Command::none()
+            }
+            Message::NotificationDismissed(id) => {
+                self.notifications.dismiss(id);
+                Command::none()
+            }
+            Message::NotificationTick(now) => {
+                self.notifications.expire(now);
+                Command::none()
+            }
+            Message::PresetSelected(preset) => self.apply_pipeline(preset.pipeline()),
+            Message::RecipeOpen => Command::perform(
+                async {
+                    let path = recipe_open(tr(Text::OpenRecipeTitle)).await?;
+                    Recipe::load(&path)
+                },
+                Message::RecipeOpened,
+            ),
+            Message::RecipeOpened(result) => match result.and_then(|recipe| recipe.pipeline()) {
+                Ok(pipeline) => self.apply_pipeline(pipeline),
+                Err(error) => {
+                    self.report_error(error);
+                    Command::none()
+                }
+            },
+            Message::RecipeSave => {
+                let pipeline = self.pipeline.clone();
+                Command::perform(
+                    async move {
+                        let path = recipe_save_path(tr(Text::SaveRecipeTitle)).await?;
+                        //名前はファイル名から付ける
+                        let name = path
+                            .file_stem()
+                            .map(|name| name.to_string_lossy().into_owned())
+                            .unwrap_or_default();
+                        Recipe::from_pipeline(&name, &pipeline).save(&path)
+                    },
+                    Message::RecipeSaved,
+                )
+            }
+            Message::RecipeSaved(Ok(path)) => {
+                self.notifications.push(Level::Info, saved_to(&path));
+                Command::none()
+            }
+            Message::RecipeSaved(Err(error)) => {
+                self.report_error(error);
                 Command::none()
             }
             Message::Exit => iced::window::close(),
@@ -289,40 +629,91 @@ impl Application for ImageState {
     }
 
     fn subscription(&self) -> iced::Subscription<Message> {
-        iced::subscription::events().map(Message::EventOccurred)
+        let events = iced::subscription::events().map(Message::EventOccurred);
+        let is_playing = self
+            .animation_player
+            .as_ref()
+            .is_some_and(|player| player.is_playing());
+        let mut subscriptions = vec![events];
+
+        if is_playing {
+            subscriptions.push(iced::window::frames().map(Message::AnimationTick));
+        }
+        //値が止まるのを待つ
+        if self.live_preview.is_pending() {
+            subscriptions.push(iced::window::frames().map(Message::PreviewTick));
+        }
+        if self.size_estimate.is_pending() {
+            subscriptions.push(iced::window::frames().map(Message::SizeEstimateTick));
+        }
+        //変換中は進み具合を描き直す
+        if self.convert_job.is_some() {
+            subscriptions.push(iced::window::frames().map(Message::ConvertProgressed));
+        }
+        //通知が出ている間だけ時間切れを調べる
+        if !self.notifications.is_empty() {
+            subscriptions.push(iced::window::frames().map(Message::NotificationTick));
+        }
+        iced::Subscription::batch(subscriptions)
     }
 
     fn view(&self) -> iced::Element<'_, Message> {
-        let open_button = button("Open").on_press(Message::Open);
+        column![self.notifications.view(), self.main_view()].into()
+    }
+
+    fn theme(&self) -> Self::Theme {
+        Theme::Dark
+    }
+}
+
+impl ImageState {
+    fn main_view(&self) -> iced::Element<'_, Message> {
+        let open_button = button(tr(Text::Open)).on_press(Message::Open);
         let convert_button = components::button_component(
-            "Convert",
-            self.image_paths.0.is_some().then_some(Message::Convert),
+            tr(Text::Convert),
+            (self.can_convert() && self.convert_job.is_none()).then_some(Message::Convert),
         );
         let save_button = components::button_component(
-            "Save",
-            self.image_converter
-                .is_result_temp_path()
+            tr(Text::Save),
+            (self.image_converter.is_result_temp_path() || self.animation_player.is_some())
                 .then_some(Message::Save),
         );
 
         let select_mode_pick_list =
             pick_list(&SelectMode::ALL[..], Some(self.mode), Message::Selected);
+        let language_list = pick_list(
+            &Language::ALL[..],
+            Some(i18n::language()),
+            Message::LanguageSelected,
+        );
+        let compare_list = pick_list(
+            &CompareMode::ALL[..],
+            Some(self.image_view.compare_mode),
+            Message::CompareModeSelected,
+        );
         let save_format_list = pick_list(
             &SaveFormat::ALL[..],
             Some(self.image_converter.save_format),
             Message::SaveFormatSelected,
         );
         //TODO リファクタリング
-        let controlls = if self.mode == SelectMode::Add {
-            let reselect_button = components::button_component("Reselect", Some(Message::Open));
+        let controlls = if self.mode == SelectMode::Add || self.mode == SelectMode::FromAscii {
+            let reselect_message = if self.mode == SelectMode::Add {
+                Message::Open
+            } else {
+                Message::Selected(SelectMode::FromAscii)
+            };
+            let reselect_button = components::button_component(tr(Text::Reselect), Some(reselect_message));
             row![
                 open_button,
                 save_button,
                 convert_button,
                 reselect_button,
                 horizontal_space(Length::Fill),
+                compare_list,
                 save_format_list,
-                select_mode_pick_list
+                select_mode_pick_list,
+                language_list
             ]
         } else {
             row![
@@ -330,72 +721,677 @@ impl Application for ImageState {
                 save_button,
                 convert_button,
                 horizontal_space(Length::Fill),
+                compare_list,
                 save_format_list,
-                select_mode_pick_list
+                select_mode_pick_list,
+                language_list
             ]
         }
         .padding(10);
+        let controlls = match &self.convert_job {
+            Some(job) => column![controlls, self.encoder_controlls(), job.view()],
+            None => column![controlls, self.encoder_controlls()],
+        }
+        .push(self.recipe_controlls());
 
-        let image_path = self.image_paths.0.clone().unwrap_or(PathBuf::from(""));
+        let image = column![
+            row![self.image_canvas(), self.metadata_panel()].height(Length::Fill),
+            self.status_bar()
+        ];
 
-        let image = container(
-            Image::new(image_path)
-                .width(Length::Fill)
-                .height(Length::Fill),
-        )
-        .width(Length::Fill)
-        .height(Length::Fill)
-        .align_x(iced::alignment::Horizontal::Center)
-        .align_y(iced::alignment::Vertical::Center);
-
-        if self.mode == SelectMode::HueRotate
-            || self.mode == SelectMode::Blur
-            || self.mode == SelectMode::Rotate
+        if let (Views::Text, Some(text_view)) =
+            (&self.view_state.current_view, self.view_state.text_view.as_ref())
         {
-            let input_angle_text =
-                text_input("", &self.input_value).on_input(Message::InputChanged);
-            return container(column!(controlls, input_angle_text, image))
-                .width(Length::Fill)
-                .height(Length::Fill)
-                .padding(10)
-                .into();
-        }
-        let image_view = container(column!(controlls, image))
+            return text_view.view();
+        }
+
+        let mut body = column![controlls];
+        if self.mode == SelectMode::ToAscii || self.mode == SelectMode::ToAsciiEdge {
+            let ascii_controlls = row![
+                text_input(tr(Text::Columns), &self.ascii_columns).on_input(Message::AsciiColumnsChanged),
+                text(self.ascii_preview()),
+            ]
+            .spacing(10)
+            .padding([0, 10])
+            .align_items(iced::Alignment::Center);
+            body = body.push(ascii_controlls);
+        }
+        if self.mode == SelectMode::MakeGif {
+            body = body.push(self.gif_settings.view());
+        }
+        if !self.mode.params().is_empty() {
+            body = body.push(self.param_controlls());
+        }
+        container(body.push(image))
             .width(Length::Fill)
             .height(Length::Fill)
             .padding(10)
-            .into();
-        if self.rectangle.is_some(){
-            let rect = self.rectangle.unwrap();
-            
+            .into()
+    }
+
+    //モードのパラメータから入力欄を作る 色はスポイト、画像は開き直しで選ぶ
+    fn param_controlls(&self) -> iced::Element<'_, Message> {
+        let step = self.current_step();
+        let controlls = self.mode.params().iter().enumerate().map(|(index, spec)| {
+            let key = spec.key;
+            let control: iced::Element<'_, Message> = match spec.kind {
+                ParamKind::Float { min, max, step: increment, .. } => row![
+                    slider(min..=max, step.float(key), move |value| {
+                        Message::ParamChanged(key, ParamValue::Float(value))
+                    })
+                    .step(increment)
+                    .width(Length::Fill),
+                    self.param_input(index, key),
+                ]
+                .spacing(10)
+                .into(),
+                ParamKind::Int { min, max, .. } => row![
+                    slider(min..=max, step.int(key), move |value| {
+                        Message::ParamChanged(key, ParamValue::Int(value))
+                    })
+                    .width(Length::Fill),
+                    self.param_input(index, key),
+                ]
+                .spacing(10)
+                .into(),
+                ParamKind::Ramp => pick_list(&Ramp::ALL[..], Some(step.ramp(key)), move |ramp| {
+                    Message::ParamChanged(key, ParamValue::Ramp(ramp))
+                })
+                .into(),
+                ParamKind::Color { .. } => match step.color(key) {
+                    Some(color) => text("■").size(18).style(swatch_color(color)).into(),
+                    None => text(tr(Text::Eyedropper)).into(),
+                },
+                ParamKind::Image => text(
+                    self.image_paths
+                        .1
+                        .as_ref()
+                        .and_then(|path| path.file_name())
+                        .map_or("-".to_string(), |name| name.to_string_lossy().to_string()),
+                )
+                .into(),
+            };
+            row![text(tr(spec.name)), control]
+                .spacing(10)
+                .align_items(iced::Alignment::Center)
+                .into()
+        });
+        column(controlls.collect()).spacing(5).padding([0, 10]).into()
+    }
+
+    fn param_input(&self, index: usize, key: &'static str) -> iced::Element<'_, Message> {
+        let input = self.param_inputs.get(index).map_or("", String::as_str);
+        text_input("", input)
+            .on_input(move |input| Message::ParamInputChanged(key, input))
+            .width(80)
+            .into()
+    }
+
+    //比較するときは元画像も渡す 並べるときは上に見出しを付ける
+    fn image_canvas(&self) -> iced::Element<'_, Message> {
+        let image_path = self.image_paths.0.clone().unwrap_or(PathBuf::from(""));
+        let image_handle = match &self.animation_player {
+            Some(player) => player.current_handle(),
+            None => Handle::from_path(image_path),
+        };
+        let original_handle = self
+            .original_path
+            .as_ref()
+            .filter(|original_path| {
+                self.animation_player.is_none()
+                    && self.image_view.compare_mode != CompareMode::Off
+                    && self.image_paths.0.as_ref() != Some(*original_path)
+            })
+            .map(Handle::from_path);
+
+        let is_side_by_side = original_handle.is_some()
+            && self.image_view.compare_mode == CompareMode::SideBySide;
+        let mut canvas =
+            ImageCanvas::new(image_handle, original_handle, self.image_view).crop(self.rectangle);
+        if let Some((preview, size)) = self.live_preview.output() {
+            canvas = canvas.preview(preview, size);
         }
-        match self.view_state.current_view {
-            Views::Image => image_view,
-            Views::Text => self.view_state.text_view.as_ref().unwrap().view(),
+        if !is_side_by_side {
+            return canvas.into();
         }
+
+        let labels = row![
+            text(tr(Text::Before)).width(Length::Fill),
+            text(tr(Text::After)).width(Length::Fill),
+        ];
+        column![labels, canvas].spacing(5).into()
     }
 
-    fn theme(&self) -> Self::Theme {
-        Theme::Dark
+    //画像の大きさ、倍率、切り抜きの枠と倍率の切り替え
+    fn status_bar(&self) -> iced::Element<'_, Message> {
+        let presets = ZoomPreset::ALL.iter().fold(row![], |presets, preset| {
+            presets.push(
+                button(text(preset.to_string()).size(14))
+                    .on_press(Message::ZoomPresetSelected(*preset))
+                    .style(iced::theme::Button::Secondary),
+            )
+        });
+
+        let eyedropper = button(text(tr(Text::Eyedropper)).size(14))
+            .on_press(Message::PickingToggled)
+            .style(if self.image_view.picking {
+                iced::theme::Button::Primary
+            } else {
+                iced::theme::Button::Secondary
+            });
+
+        let mut status = row![presets.spacing(5), eyedropper];
+        if let Some(color) = self.picked_color {
+            status = status.push(
+                row![
+                    text(tr(Text::PickedColor)).size(14),
+                    text("■").size(18).style(swatch_color(color)),
+                ]
+                .spacing(5)
+                .align_items(iced::Alignment::Center),
+            );
+        }
+        status = status.push(horizontal_space(Length::Fill));
+        if let Some(pixel) = &self.hovered {
+            status = status.push(pixel.view());
+        }
+        if let Some((width, height)) = self.image_dimensions {
+            let scale = self
+                .image_view
+                .scale(iced::Size::new(width as f32, height as f32));
+            status = status
+                .push(text(format!("{} x {} px", width, height)).size(14))
+                .push(text(format!("{} {:.0}%", tr(Text::Zoom), scale * 100.0)).size(14));
+        }
+        if let Some(rectangle) = self.rectangle {
+            status = status.push(
+                text(format!(
+                    "{} ({}, {}) {} x {}",
+                    tr(Text::Selection),
+                    rectangle.x,
+                    rectangle.y,
+                    rectangle.width,
+                    rectangle.height
+                ))
+                .size(14),
+            );
+        }
+        status
+            .spacing(15)
+            .padding([5, 0])
+            .align_items(iced::Alignment::Center)
+            .into()
     }
-}
 
-impl Drop for ImageState {
-    fn drop(&mut self) {
-        remove_all_temp_file();
+    //アニメーションは再生中のフレームから読む 静止画はデコードが済むまでNone
+    fn probe_pixel(&self, (point, is_original): (iced::Point, bool)) -> Option<PixelInfo> {
+        if let (Some(player), false) = (&self.animation_player, is_original) {
+            return pixel_at(player.current_frame(), point);
+        }
+        self.pixel_probe.pixel(self.probe_path(is_original)?, point)
     }
-}
 
-impl ImageState {
-    fn convert_input_value_to_float(&self) -> f32 {
-        let float_value = &self.input_value.parse::<f32>().map_err(Error::ParseError);
-        match float_value {
-            Ok(value) => value.to_owned(),
-            Err(error) => error.show_dialog_return_default::<f32>(),
+    fn load_probe_image(&mut self, is_original: bool) -> Command<Message> {
+        if self.animation_player.is_some() && !is_original {
+            return Command::none();
+        }
+        match self.probe_path(is_original).cloned() {
+            Some(path) => self.pixel_probe.load(&path),
+            None => Command::none(),
+        }
+    }
+
+    fn probe_path(&self, is_original: bool) -> Option<&PathBuf> {
+        if is_original {
+            self.original_path.as_ref()
+        } else {
+            self.image_paths.0.as_ref()
+        }
+    }
+
+    fn ascii_convert(&self, mode: SelectMode, scale: u32, ramp: Ramp) -> Result<TextFile, Error> {
+        if mode == SelectMode::ToAsciiEdge {
+            self.image_converter.clone().ascii_edge_art(scale, ramp)
+        } else {
+            self.image_converter.clone().ascii_art(scale, ramp)
+        }
+    }
+
+    //GIF/APNGなら全フレームを読み込んでプレビュー再生する
+    fn open_image(&mut self, path: PathBuf) {
+        self.cancel_convert_job();
+        self.image_converter.metadata = ImageMetadata::read(&path);
+        match Animation::open(&path) {
+            Ok(animation) => self.animation_player = animation.map(AnimationPlayer::new),
+            Err(error) => {
+                self.animation_player = None;
+                self.report_error(error);
+            }
+        }
+        self.original_path = Some(path.clone());
+        self.set_current_image(Some(path));
+        self.pipeline = Pipeline::default();
+        //前の画像の座標の枠は使えない
+        self.rectangle = None;
+        self.hovered = None;
+        self.live_preview.clear();
+        self.size_estimate.clear();
+        self.image_view.set_zoom(Zoom::Fit);
+    }
+
+    //ダイアログを閉じただけのときは何も表示しない
+    fn report_error(&mut self, error: Error) {
+        if error.is_cancelled() {
+            self.notifications.push(Level::Info, error_message(&error));
+        } else if !error.is_dialog_closed() {
+            self.notifications.push_error(&error);
+        }
+    }
+
+    //Operationにならないモード GIFの作成とアスキーアートの変換はその場で行う
+    fn convert_without_operation(&mut self, step: Step) -> Command<Message> {
+        if let Some(image_path) = self.image_paths.0.clone() {
+            self.image_converter.set_image_path(image_path);
+        }
+        let converted_image = match step.mode {
+            SelectMode::MakeGif => {
+                match self.gif_settings.build(self.image_paths.0.as_ref()) {
+                    Ok(animation) => self.animation_player = Some(AnimationPlayer::new(animation)),
+                    Err(error) => self.report_error(error),
+                }
+                return Command::none();
+            }
+            SelectMode::ToAscii | SelectMode::ToAsciiEdge => {
+                let (scale, ramp) = (step.int("scale") as u32, step.ramp("ramp"));
+                let text_file = self.ascii_convert(step.mode, scale, ramp);
+
+                self.view_state.text_view =
+                    Some(TextViewerState::new(text_file, step.mode, scale, ramp));
+                self.view_state.current_view = Views::Text;
+                self.image_paths
+                    .0
+                    .as_ref()
+                    .ok_or(Error::MissingInput(Input::Image))
+                    .and_then(get_dynamic_image)
+            }
+            SelectMode::FromAscii => {
+                self.animation_player = None;
+                self.pipeline = Pipeline::default();
+                self.ascii_text_to_image()
+            }
+            mode => Err(Error::UnknownOperation(mode.key().to_string())),
+        };
+        //失敗したときは前の画像のまま
+        let saved = converted_image
+            .and_then(|image| self.image_converter.save_temp_result_image(image));
+        if let Err(error) = saved {
+            self.report_error(error);
+            return Command::none();
+        }
+        self.set_current_image(self.image_converter.get_temp_result_path());
+        self.request_size_estimate();
+        Command::none()
+    }
+
+    //変換は別スレッドで行い、進み具合はconvert_jobのprogressで見る
+    fn start_convert(&mut self, operation: Operation) -> Command<Message> {
+        self.cancel_convert_job();
+        let job = ConvertJob::new();
+        let (id, progress) = (job.id, job.progress.clone());
+        self.convert_job = Some(job);
+
+        if let Some(player) = &self.animation_player {
+            let animation = player.animation.clone();
+            return Command::perform(
+                async move {
+                    animation
+                        .map_with_progress(&operation, &progress)
+                        .map(ConvertOutput::Animation)
+                },
+                move |result| Message::Converted(id, result),
+            );
+        }
+
+        if let Some(image_path) = self.image_paths.0.clone() {
+            self.image_converter.set_image_path(image_path);
+        }
+        let image_converter = self.image_converter.clone();
+        Command::perform(
+            async move {
+                image_converter
+                    .apply(&operation, &progress)
+                    .and_then(|image| save_temp_image(&image))
+                    .map(ConvertOutput::Image)
+            },
+            move |result| Message::Converted(id, result),
+        )
+    }
+
+    //レシピは元の大きさの画像に1回の変換としてまとめて適用する
+    fn apply_pipeline(&mut self, pipeline: Pipeline) -> Command<Message> {
+        self.live_preview.clear();
+        if self.image_paths.0.is_none() && self.animation_player.is_none() {
+            self.report_error(Error::MissingInput(Input::Image));
+            return Command::none();
+        }
+        match pipeline.operation() {
+            Ok(operation) => {
+                self.converting_steps = pipeline.steps;
+                self.start_convert(operation)
+            }
+            Err(error) => {
+                self.report_error(error);
+                Command::none()
+            }
+        }
+    }
+
+    fn cancel_convert_job(&mut self) {
+        if let Some(job) = self.convert_job.take() {
+            job.progress.cancel();
+        }
+    }
+
+    fn save_command(&self) -> Command<Message> {
+        if let Some(player) = &self.animation_player {
+            return Command::perform(
+                save_animation(
+                    tr(Text::SaveTitle),
+                    player.animation.clone(),
+                    self.image_converter.save_format,
+                    self.gif_settings.speed,
+                ),
+                Message::ImageSaved,
+            );
+        }
+        Command::perform(
+            save(
+                tr(Text::SaveTitle),
+                None,
+                self.image_converter.clone(),
+                self.image_converter.save_format,
+            ),
+            Message::ImageSaved,
+        )
+    }
+
+    //フレームごとに適用できる変換 ASCII系は画像を返さないのでNone
+    fn selected_operation(&self) -> Result<Option<Operation>, Error> {
+        self.current_step().operation()
+    }
+
+    //変換した画像だけ見積もる エンコードは設定が止まってから行う
+    fn request_size_estimate(&mut self) {
+        if self.image_converter.is_result_temp_path() {
+            self.size_estimate.request(Instant::now());
+        }
+    }
+
+    //保存形式ごとのエンコード設定と保存後のサイズの見積もり
+    fn encoder_controlls(&self) -> iced::Element<'_, Message> {
+        let options = self.image_converter.encoder_options;
+
+        let format_controlls: iced::Element<'_, Message> = match self.image_converter.save_format
+        {
+            SaveFormat::Jpeg => row![
+                text(format!("{} {}", tr(Text::Quality), options.jpeg_quality)),
+                slider(1..=100, options.jpeg_quality, move |jpeg_quality| {
+                    Message::EncoderOptionsChanged(EncoderOptions {
+                        jpeg_quality,
+                        ..options
+                    })
+                })
+                .width(200),
+                pick_list(
+                    &ChromaSubsampling::ALL[..],
+                    Some(options.jpeg_subsampling),
+                    move |jpeg_subsampling| {
+                        Message::EncoderOptionsChanged(EncoderOptions {
+                            jpeg_subsampling,
+                            ..options
+                        })
+                    }
+                ),
+            ]
+            .spacing(10)
+            .into(),
+            SaveFormat::Png => row![
+                pick_list(
+                    &PngCompression::ALL[..],
+                    Some(options.png_compression),
+                    move |png_compression| {
+                        Message::EncoderOptionsChanged(EncoderOptions {
+                            png_compression,
+                            ..options
+                        })
+                    }
+                ),
+                pick_list(&PngFilter::ALL[..], Some(options.png_filter), move |png_filter| {
+                    Message::EncoderOptionsChanged(EncoderOptions {
+                        png_filter,
+                        ..options
+                    })
+                }),
+            ]
+            .spacing(10)
+            .into(),
+            SaveFormat::WebP => {
+                let lossless = checkbox(tr(Text::Lossless), options.webp_lossless, move |webp_lossless| {
+                    Message::EncoderOptionsChanged(EncoderOptions {
+                        webp_lossless,
+                        ..options
+                    })
+                });
+                if options.webp_lossless {
+                    row![lossless].into()
+                } else {
+                    row![
+                        lossless,
+                        text(format!("{} {}", tr(Text::Quality), options.webp_quality)),
+                        slider(1..=100, options.webp_quality, move |webp_quality| {
+                            Message::EncoderOptionsChanged(EncoderOptions {
+                                webp_quality,
+                                ..options
+                            })
+                        })
+                        .width(200),
+                    ]
+                    .spacing(10)
+                    .into()
+                }
+            }
+            SaveFormat::Tiff => row![pick_list(
+                &TiffCompression::ALL[..],
+                Some(options.tiff_compression),
+                move |tiff_compression| {
+                    Message::EncoderOptionsChanged(EncoderOptions {
+                        tiff_compression,
+                        ..options
+                    })
+                }
+            )]
+            .into(),
+            _ => row![].into(),
+        };
+
+        let estimated_size = match (
+            self.image_converter.is_result_temp_path(),
+            self.size_estimate.size(),
+        ) {
+            (true, Some(size)) => format!("≈ {:.1} KB", size as f32 / 1024.0),
+            _ => "".to_string(),
+        };
+
+        row![
+            horizontal_space(Length::Fill),
+            format_controlls,
+            self.metadata_controlls(),
+            text(estimated_size)
+        ]
+        .spacing(10)
+        .padding([0, 10])
+        .align_items(iced::Alignment::Center)
+        .into()
+    }
+
+    //プリセットとレシピファイル 保存できるのは開いてから適用した変換
+    fn recipe_controlls(&self) -> iced::Element<'_, Message> {
+        let preset_list = pick_list(&Preset::ALL[..], None, Message::PresetSelected)
+            .placeholder(tr(Text::Preset));
+        let load_button = button(tr(Text::LoadRecipe)).on_press(Message::RecipeOpen);
+        let save_button = button(tr(Text::SaveRecipe)).on_press_maybe(
+            (!self.pipeline.steps.is_empty()).then_some(Message::RecipeSave),
+        );
+        let steps = self
+            .pipeline
+            .steps
+            .iter()
+            .map(|step| step.mode.to_string())
+            .collect::<Vec<_>>()
+            .join(" → ");
+
+        row![preset_list, load_button, save_button, text(steps)]
+            .spacing(10)
+            .padding([0, 10])
+            .align_items(iced::Alignment::Center)
+            .into()
+    }
+
+    //EXIFを埋め込める形式のときだけ表示する
+    fn metadata_controlls(&self) -> iced::Element<'_, Message> {
+        let options = self.image_converter.metadata_options;
+        if self.image_converter.metadata.is_none()
+            || !self.image_converter.save_format.supports_metadata()
+        {
+            return row![].into();
+        }
+
+        let policies: Vec<Localized<MetadataPolicy>> =
+            MetadataPolicy::ALL.into_iter().map(Localized).collect();
+        let policy_list = pick_list(policies, Some(Localized(options.policy)), move |policy| {
+            Message::MetadataOptionsChanged(MetadataOptions {
+                policy: policy.0,
+                ..options
+            })
+        });
+        if options.policy != MetadataPolicy::Custom {
+            return row![policy_list].into();
+        }
+
+        MetadataCategory::ALL
+            .iter()
+            .fold(row![policy_list], |controlls, category| {
+                controlls.push(checkbox(
+                    remove_category(*category),
+                    options.is_removed(*category),
+                    move |removed| {
+                        Message::MetadataOptionsChanged(options.set_removed(*category, removed))
+                    },
+                ))
+            })
+            .spacing(10)
+            .align_items(iced::Alignment::Center)
+            .into()
+    }
+
+    fn metadata_panel(&self) -> iced::Element<'_, Message> {
+        let Some(metadata) = &self.image_converter.metadata else {
+            return column![].into();
+        };
+
+        metadata
+            .summary()
+            .into_iter()
+            .fold(column![text(tr(Text::Exif)).size(18)], |panel, (field, value)| {
+                panel.push(text(format!("{}: {}", Localized(field), value)).size(14))
+            })
+            .spacing(4)
+            .padding(10)
+            .width(220)
+            .into()
+    }
+
+    fn can_convert(&self) -> bool {
+        if self.mode == SelectMode::FromAscii {
+            self.ascii_text_path.is_some()
+        } else if self.mode == SelectMode::MakeGif {
+            self.gif_settings.can_build(self.image_paths.0.as_ref())
+        } else {
+            self.image_paths.0.is_some()
+        }
+    }
+
+    //入力値をセルの幅として、縦はascii_artと同じく幅の2倍
+    fn ascii_text_to_image(&self) -> Result<image::DynamicImage, Error> {
+        let cell_width = self.current_step().int("cell_width") as u32;
+        let text_path = self
+            .ascii_text_path
+            .as_ref()
+            .ok_or(Error::MissingInput(Input::TextFile))?;
+
+        ascii_file_to_image(text_path, cell_width, cell_width * 2)
+            .map(image::DynamicImage::ImageLuma8)
+    }
+
+    //view()で毎回ファイルを読まないように大きさは表示する画像を変えたときに読んでおく
+    fn set_current_image(&mut self, path: Option<PathBuf>) {
+        self.image_dimensions = path
+            .as_ref()
+            .and_then(|path| image::image_dimensions(path).ok());
+        self.image_paths.0 = path;
+    }
+
+    fn ascii_preview(&self) -> String {
+        match self.image_dimensions {
+            Some((width, height)) => {
+                let scale = self.current_step().int("scale") as u32;
+                let (columns, rows) = grid_size(width, height, scale);
+                ascii_grid(columns, rows)
+            }
+            None => "-".to_string(),
+        }
+    }
+
+    //選んでいるモードの値 色と2枚目の画像はスポイトと開いた画像から入れる
+    fn current_step(&self) -> Step {
+        let mut step = self
+            .steps
+            .get(&self.mode)
+            .cloned()
+            .unwrap_or_else(|| Step::new(self.mode));
+        for spec in self.mode.params() {
+            match spec.kind {
+                ParamKind::Color { .. } => step.set(spec.key, ParamValue::Color(self.picked_color)),
+                ParamKind::Image => step.set(spec.key, ParamValue::Image(self.image_paths.1.clone())),
+                ParamKind::Float { .. } | ParamKind::Int { .. } | ParamKind::Ramp => {}
+            }
+        }
+        step
+    }
+
+    //値を入れて数値入力欄もそろえ、縮小プレビューを頼む
+    fn set_param(&mut self, key: &'static str, value: ParamValue) {
+        let Some(index) = self.mode.params().iter().position(|spec| spec.key == key) else {
+            return;
+        };
+        if let Some(param_input) = self.param_inputs.get_mut(index) {
+            *param_input = self.mode.params()[index].label(&value);
+        }
+        self.steps
+            .entry(self.mode)
+            .or_insert_with(|| Step::new(self.mode))
+            .set(key, value);
+        if self.mode.is_pipeline_step()
+            && self.animation_player.is_none()
+            && self.image_paths.0.is_some()
+        {
+            self.live_preview.request(Instant::now());
         }
     }
 }
 
-fn init() {
-    let _ = mkdir_result_temp_folder();
+fn init() -> SessionGuard {
+    set_language(Language::system());
+    sweep_stale_sessions();
+    SessionGuard::default()
 }
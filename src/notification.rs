@@ -0,0 +1,114 @@
+use std::time::{Duration, Instant};
+
+use iced::{
+    theme,
+    widget::{button, column, container, horizontal_space, row, text},
+    Color, Length,
+};
+
+use coffee_image::error::Error;
+
+use crate::{i18n::error_message, Message};
+
+//この時間が過ぎた通知は消す
+const NOTIFICATION_DURATION: Duration = Duration::from_secs(6);
+const MAX_NOTIFICATIONS: usize = 3;
+const ERROR_COLOR: Color = Color::from_rgb(1.0, 0.45, 0.45);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Level {
+    Info,
+    Error,
+}
+
+#[derive(Debug, Clone)]
+pub struct Notification {
+    id: u64,
+    level: Level,
+    message: String,
+    created: Instant,
+}
+
+//ウィンドウの上に出す通知 操作は止めない
+#[derive(Debug, Clone, Default)]
+pub struct Notifications {
+    items: Vec<Notification>,
+    next_id: u64,
+}
+
+impl Notifications {
+    pub fn push(&mut self, level: Level, message: String) {
+        self.items.push(Notification {
+            id: self.next_id,
+            level,
+            message,
+            created: Instant::now(),
+        });
+        self.next_id += 1;
+        if self.items.len() > MAX_NOTIFICATIONS {
+            self.items.remove(0);
+        }
+    }
+
+    pub fn push_error(&mut self, error: &Error) {
+        self.push(Level::Error, error_message(error));
+    }
+
+    pub fn dismiss(&mut self, id: u64) {
+        self.items.retain(|notification| notification.id != id);
+    }
+
+    pub fn expire(&mut self, now: Instant) {
+        self.items
+            .retain(|notification| now.duration_since(notification.created) < NOTIFICATION_DURATION);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+
+    pub fn view(&self) -> iced::Element<'_, Message> {
+        let notifications = self.items.iter().map(|notification| {
+            let message = match notification.level {
+                Level::Info => text(&notification.message),
+                Level::Error => text(&notification.message).style(ERROR_COLOR),
+            };
+            container(
+                row![
+                    message,
+                    horizontal_space(Length::Fill),
+                    button("x")
+                        .on_press(Message::NotificationDismissed(notification.id))
+                        .style(theme::Button::Text),
+                ]
+                .align_items(iced::Alignment::Center),
+            )
+            .width(Length::Fill)
+            .padding([5, 10])
+            .style(theme::Container::Box)
+            .into()
+        });
+
+        column(notifications.collect()).spacing(5).padding([0, 10]).into()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn expire_test() {
+        let mut notifications = Notifications::default();
+        for number in 0..5 {
+            notifications.push(Level::Info, number.to_string());
+        }
+        assert_eq!(notifications.items.len(), MAX_NOTIFICATIONS);
+        assert_eq!(notifications.items[0].message, "2");
+
+        notifications.dismiss(notifications.items[0].id);
+        assert_eq!(notifications.items.len(), MAX_NOTIFICATIONS - 1);
+        notifications.expire(Instant::now() + NOTIFICATION_DURATION);
+        assert!(notifications.is_empty());
+    }
+}
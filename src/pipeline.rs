@@ -0,0 +1,319 @@
+use std::{fmt::Display, str::FromStr};
+
+use image::{DynamicImage, Rgba};
+
+use coffee_image::{
+    convert::operation::Operation,
+    error::{Error, Input, ResultExt},
+    string_art::ascii::Ramp,
+};
+
+use crate::select_mode::{ParamSpec, ParamValue, SelectMode};
+
+//変換1回分 値はモードのparams()と同じ順
+//文字列では "rotate angle=30 fill=#FF0000FF" のように書く 未設定の値は書かない
+//空白を含む値は add image="my photo.png" のように""で囲む
+#[derive(Debug, Clone, PartialEq)]
+pub struct Step {
+    pub mode: SelectMode,
+    pub values: Vec<ParamValue>,
+}
+
+//上から順に適用する変換 文字列では1行に1つ
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Pipeline {
+    pub steps: Vec<Step>,
+}
+
+impl Step {
+    pub fn new(mode: SelectMode) -> Self {
+        Self {
+            mode,
+            values: mode.params().iter().map(ParamSpec::default_value).collect(),
+        }
+    }
+
+    fn index(&self, key: &str) -> Option<usize> {
+        self.mode.params().iter().position(|spec| spec.key == key)
+    }
+
+    pub fn get(&self, key: &str) -> Option<&ParamValue> {
+        self.values.get(self.index(key)?)
+    }
+
+    pub fn set(&mut self, key: &str, value: ParamValue) {
+        if let Some(index) = self.index(key) {
+            self.values[index] = value;
+        }
+    }
+
+    pub fn float(&self, key: &str) -> f32 {
+        match self.get(key) {
+            Some(ParamValue::Float(value)) => *value,
+            Some(ParamValue::Int(value)) => *value as f32,
+            _ => 0.0,
+        }
+    }
+
+    pub fn int(&self, key: &str) -> i32 {
+        match self.get(key) {
+            Some(ParamValue::Int(value)) => *value,
+            Some(ParamValue::Float(value)) => *value as i32,
+            _ => 0,
+        }
+    }
+
+    pub fn ramp(&self, key: &str) -> Ramp {
+        match self.get(key) {
+            Some(ParamValue::Ramp(ramp)) => *ramp,
+            _ => Ramp::default(),
+        }
+    }
+
+    pub fn color(&self, key: &str) -> Option<Rgba<u8>> {
+        match self.get(key) {
+            Some(ParamValue::Color(color)) => *color,
+            _ => None,
+        }
+    }
+
+    pub fn check(&self) -> Result<(), Error> {
+        self.mode
+            .params()
+            .iter()
+            .zip(self.values.iter())
+            .try_for_each(|(spec, value)| spec.check(value))
+    }
+
+    //画像を返さないモード(ASCII系、GIF作成)はNone
+    pub fn operation(&self) -> Result<Option<Operation>, Error> {
+        self.check()?;
+        let operation = match self.mode {
+            SelectMode::Gray => Operation::Gray,
+            SelectMode::BitwiseNot => Operation::BitwiseNot,
+            SelectMode::HueRotate => Operation::HueRotate(self.int("degrees")),
+            SelectMode::Blur => Operation::Blur(self.float("sigma")),
+            SelectMode::Rotate => match self.color("fill") {
+                Some(fill) => Operation::RotateFill(self.float("angle"), fill),
+                None => Operation::Rotate(self.float("angle")),
+            },
+            SelectMode::Add => match self.get("image") {
+                Some(ParamValue::Image(Some(path))) => Operation::Add(path.clone()),
+                _ => return Err(Error::MissingInput(Input::SecondImage)),
+            },
+            //許容量とレベルは%で持つ
+            SelectMode::ChromaKey => Operation::ChromaKey(
+                self.color("key").ok_or(Error::MissingInput(Input::Color))?,
+                self.float("tolerance") / 100.0,
+            ),
+            SelectMode::Threshold => Operation::Threshold(self.float("level") / 100.0),
+            SelectMode::Resize => Operation::Resize(self.int("max_side") as u32),
+            SelectMode::ToAscii
+            | SelectMode::ToAsciiEdge
+            | SelectMode::FromAscii
+            | SelectMode::MakeGif => return Ok(None),
+        };
+        Ok(Some(operation))
+    }
+}
+
+impl Display for Step {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.mode.key())?;
+        for (spec, value) in self.mode.params().iter().zip(self.values.iter()) {
+            let value = spec.format(value);
+            if value.contains(char::is_whitespace) {
+                write!(f, " {}=\"{}\"", spec.key, value)?;
+            } else if !value.is_empty() {
+                write!(f, " {}={}", spec.key, value)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for Step {
+    type Err = Error;
+
+    //書かれていない値は既定値のまま パイプラインに並べられないモードはErr
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let words = split_words(text);
+        let mut words = words.iter().map(String::as_str);
+        let name = words.next().unwrap_or_default();
+        let mode = SelectMode::from_key(name)
+            .filter(SelectMode::is_pipeline_step)
+            .ok_or_else(|| Error::UnknownOperation(name.to_string()))?;
+
+        let mut step = Step::new(mode);
+        for word in words {
+            let (key, value) = word.split_once('=').unwrap_or((word, ""));
+            let spec = mode
+                .params()
+                .iter()
+                .find(|spec| spec.key == key)
+                .ok_or_else(|| Error::InvalidParameter {
+                    name: key.to_string(),
+                    value: value.to_string(),
+                })?;
+            step.set(key, spec.parse(value)?);
+        }
+        Ok(step)
+    }
+}
+
+//空白で区切る ""で囲んだ部分の空白は区切らず、""は取り除く
+fn split_words(text: &str) -> Vec<String> {
+    let mut words = Vec::new();
+    let mut word: Option<String> = None;
+    let mut is_quoted = false;
+    for c in text.chars() {
+        if c == '"' {
+            is_quoted = !is_quoted;
+            word.get_or_insert_with(String::new);
+        } else if c.is_whitespace() && !is_quoted {
+            words.extend(word.take());
+        } else {
+            word.get_or_insert_with(String::new).push(c);
+        }
+    }
+    words.extend(word);
+    words
+}
+
+impl Pipeline {
+    //GUIでは1回の変換として進み具合を出し、中断できるようにまとめる
+    pub fn operation(&self) -> Result<Operation, Error> {
+        self.steps
+            .iter()
+            .map(|step| {
+                step.operation()?
+                    .ok_or_else(|| Error::UnknownOperation(step.mode.key().to_string()))
+            })
+            .collect::<Result<_, _>>()
+            .map(Operation::Sequence)
+    }
+
+    pub fn apply(&self, image: DynamicImage) -> Result<DynamicImage, Error> {
+        self.steps.iter().try_fold(image, |image, step| {
+            let operation = step
+                .operation()?
+                .ok_or_else(|| Error::UnknownOperation(step.mode.key().to_string()))?;
+            operation.apply(&image).operation_context(step.mode.key())
+        })
+    }
+}
+
+impl Display for Pipeline {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for step in self.steps.iter() {
+            writeln!(f, "{}", step)?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for Pipeline {
+    type Err = Error;
+
+    //空行と#で始まる行は飛ばす
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let steps = text
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .map(Step::from_str)
+            .collect::<Result<_, _>>()?;
+        Ok(Self { steps })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use image::{ImageBuffer, Rgb};
+
+    use super::*;
+
+    #[test]
+    fn pipeline_text_test() {
+        let text = "# test\nblur sigma=1.5\n\nrotate angle=30 fill=#FF0000FF\nhue_rotate\n";
+        let pipeline: Pipeline = text.parse().unwrap();
+
+        assert_eq!(pipeline.steps.len(), 3);
+        assert_eq!(pipeline.steps[0].float("sigma"), 1.5);
+        assert_eq!(
+            pipeline.steps[1].color("fill"),
+            Some(Rgba([255, 0, 0, 255]))
+        );
+        assert_eq!(pipeline.steps[2].int("degrees"), 180);
+        assert_eq!(
+            pipeline.to_string(),
+            "blur sigma=1.5\nrotate angle=30 fill=#FF0000FF\nhue_rotate degrees=180\n"
+        );
+        assert_eq!(pipeline.to_string().parse::<Pipeline>().unwrap(), pipeline);
+
+        assert!(matches!(
+            "sharpen".parse::<Step>(),
+            Err(Error::UnknownOperation(_))
+        ));
+        assert!(matches!(
+            "to_ascii".parse::<Step>(),
+            Err(Error::UnknownOperation(_))
+        ));
+        assert!(matches!(
+            "blur radius=2".parse::<Step>(),
+            Err(Error::InvalidParameter { .. })
+        ));
+        assert!(matches!(
+            "blur sigma=99".parse::<Step>(),
+            Err(Error::InvalidParameter { .. })
+        ));
+    }
+
+    #[test]
+    fn off_step_value_test() {
+        //stepの刻みにない値も丸めずに書き戻す
+        let pipeline: Pipeline = "rotate angle=12.25\nblur sigma=1.55".parse().unwrap();
+        assert_eq!(pipeline.steps[0].float("angle"), 12.25);
+        assert_eq!(pipeline.steps[1].float("sigma"), 1.55);
+        assert_eq!(
+            pipeline.to_string(),
+            "rotate angle=12.25\nblur sigma=1.55\n"
+        );
+        assert_eq!(pipeline.to_string().parse::<Pipeline>().unwrap(), pipeline);
+    }
+
+    #[test]
+    fn quoted_value_test() {
+        let step: Step = "add image=\"my photos/a b.png\"".parse().unwrap();
+        assert_eq!(
+            step.get("image"),
+            Some(&ParamValue::Image(Some("my photos/a b.png".into())))
+        );
+        assert_eq!(step.to_string(), "add image=\"my photos/a b.png\"");
+        assert_eq!(step.to_string().parse::<Step>().unwrap(), step);
+
+        assert_eq!(split_words("  blur  sigma=2 "), ["blur", "sigma=2"]);
+        assert_eq!(split_words("add image=\"\""), ["add", "image="]);
+    }
+
+    #[test]
+    fn pipeline_apply_test() {
+        let image = DynamicImage::ImageRgb8(ImageBuffer::from_fn(16, 8, |x, _| {
+            Rgb([x as u8 * 16, 200, 40])
+        }));
+        let pipeline: Pipeline = "invert\nthreshold level=50".parse().unwrap();
+        let converted = pipeline.apply(image.clone()).unwrap().to_luma8();
+
+        let expected = Operation::Threshold(0.5)
+            .apply(&Operation::BitwiseNot.apply(&image).unwrap())
+            .unwrap()
+            .to_luma8();
+        assert_eq!(converted, expected);
+
+        //色が必須のクロマキーは色がないと適用できない
+        let missing = Pipeline {
+            steps: vec![Step::new(SelectMode::ChromaKey)],
+        };
+        assert!(missing.apply(image).is_err());
+    }
+}
@@ -0,0 +1,312 @@
+use std::{
+    collections::BTreeMap,
+    fmt::Display,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use serde::{Deserialize, Serialize};
+
+use coffee_image::error::{Error, ResultExt};
+
+use crate::{
+    i18n::{tr, Text},
+    pipeline::{Pipeline, Step},
+    select_mode::{ParamKind, SelectMode},
+};
+
+//変換の並びを名前付きで保存したもの 拡張子でJSONかTOMLかを決める 拡張子がなければJSON
+//値は数ならそのまま、それ以外(色、文字の種類、パス)はパイプラインと同じ文字列で書く
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Recipe {
+    pub name: String,
+    pub steps: Vec<RecipeStep>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RecipeStep {
+    pub operation: String,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub params: BTreeMap<String, RecipeValue>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum RecipeValue {
+    Number(f64),
+    Text(String),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RecipeFormat {
+    Json,
+    Toml,
+}
+
+impl RecipeFormat {
+    pub fn from_path(path: &Path) -> Option<RecipeFormat> {
+        let Some(extension) = path.extension() else {
+            return Some(RecipeFormat::Json);
+        };
+        match extension.to_str()?.to_lowercase().as_str() {
+            "json" => Some(RecipeFormat::Json),
+            "toml" => Some(RecipeFormat::Toml),
+            _ => None,
+        }
+    }
+}
+
+impl Recipe {
+    pub fn from_pipeline(name: &str, pipeline: &Pipeline) -> Self {
+        let steps = pipeline
+            .steps
+            .iter()
+            .map(|step| RecipeStep {
+                operation: step.mode.key().to_string(),
+                params: step
+                    .mode
+                    .params()
+                    .iter()
+                    .zip(step.values.iter())
+                    .filter_map(|(spec, value)| {
+                        let text = spec.format(value);
+                        let value = match spec.kind {
+                            ParamKind::Float { .. } | ParamKind::Int { .. } => {
+                                RecipeValue::Number(text.parse().ok()?)
+                            }
+                            _ if text.is_empty() => return None,
+                            _ => RecipeValue::Text(text),
+                        };
+                        Some((spec.key.to_string(), value))
+                    })
+                    .collect(),
+            })
+            .collect();
+        Self {
+            name: name.to_string(),
+            steps,
+        }
+    }
+
+    //パラメータはスキーマで確かめる 書かれていない値は既定値
+    pub fn pipeline(&self) -> Result<Pipeline, Error> {
+        let steps = self
+            .steps
+            .iter()
+            .map(|recipe_step| {
+                let mode = SelectMode::from_key(&recipe_step.operation)
+                    .filter(SelectMode::is_pipeline_step)
+                    .ok_or_else(|| Error::UnknownOperation(recipe_step.operation.clone()))?;
+                let mut step = Step::new(mode);
+                for (key, value) in recipe_step.params.iter() {
+                    let text = match value {
+                        RecipeValue::Number(number) => number.to_string(),
+                        RecipeValue::Text(text) => text.clone(),
+                    };
+                    let spec = mode.param(key).ok_or_else(|| Error::InvalidParameter {
+                        name: key.clone(),
+                        value: text.clone(),
+                    })?;
+                    step.set(spec.key, spec.parse(&text)?);
+                }
+                Ok(step)
+            })
+            .collect::<Result<_, Error>>()?;
+        Ok(Pipeline { steps })
+    }
+
+    pub fn parse(text: &str, format: RecipeFormat) -> Result<Self, Error> {
+        match format {
+            RecipeFormat::Json => serde_json::from_str(text).map_err(Error::invalid_file),
+            RecipeFormat::Toml => toml::from_str(text).map_err(Error::invalid_file),
+        }
+    }
+
+    pub fn to_text(&self, format: RecipeFormat) -> Result<String, Error> {
+        match format {
+            RecipeFormat::Json => serde_json::to_string_pretty(self).map_err(Error::invalid_file),
+            RecipeFormat::Toml => toml::to_string_pretty(self).map_err(Error::invalid_file),
+        }
+    }
+
+    pub fn load(path: &Path) -> Result<Self, Error> {
+        let format = recipe_format(path)?;
+        let text = fs::read_to_string(path).open_context(path)?;
+        Recipe::parse(&text, format).open_context(path)
+    }
+
+    //拡張子がなければ.jsonを付けて保存し、保存したパスを返す
+    pub fn save(&self, path: &Path) -> Result<PathBuf, Error> {
+        let path = match path.extension() {
+            Some(_) => path.to_path_buf(),
+            None => path.with_extension("json"),
+        };
+        let format = recipe_format(&path)?;
+        fs::write(&path, self.to_text(format)?).save_context(&path)?;
+        Ok(path)
+    }
+}
+
+fn recipe_format(path: &Path) -> Result<RecipeFormat, Error> {
+    RecipeFormat::from_path(path).ok_or_else(|| Error::InvalidParameter {
+        name: "recipe".to_string(),
+        value: path.display().to_string(),
+    })
+}
+
+//組み込みのレシピ
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Preset {
+    Vintage,
+    HighContrastBw,
+    WebThumbnail,
+}
+
+impl Preset {
+    pub const ALL: [Preset; 3] = [
+        Preset::Vintage,
+        Preset::HighContrastBw,
+        Preset::WebThumbnail,
+    ];
+
+    //コマンドラインの--recipeでも使う
+    pub fn key(&self) -> &'static str {
+        match self {
+            Preset::Vintage => "vintage",
+            Preset::HighContrastBw => "high-contrast-bw",
+            Preset::WebThumbnail => "web-thumbnail",
+        }
+    }
+
+    pub fn from_key(key: &str) -> Option<Preset> {
+        Preset::ALL.into_iter().find(|preset| preset.key() == key)
+    }
+
+    //パイプラインの文字列で書いておく
+    fn pipeline_text(&self) -> &'static str {
+        match self {
+            Preset::Vintage => "hue_rotate degrees=20\nblur sigma=0.8",
+            Preset::HighContrastBw => "gray\nthreshold level=50",
+            Preset::WebThumbnail => "resize max_side=320",
+        }
+    }
+
+    pub fn pipeline(&self) -> Pipeline {
+        self.pipeline_text()
+            .parse()
+            .expect("built-in presets are valid pipelines")
+    }
+}
+
+impl Display for Preset {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}",
+            tr(match self {
+                Preset::Vintage => Text::PresetVintage,
+                Preset::HighContrastBw => Text::PresetHighContrastBw,
+                Preset::WebThumbnail => Text::PresetWebThumbnail,
+            })
+        )
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn recipe_round_trip_test() {
+        let pipeline: Pipeline =
+            "blur sigma=1.5\nrotate angle=30 fill=#FF0000FF\nresize max_side=320"
+                .parse()
+                .unwrap();
+        let recipe = Recipe::from_pipeline("test", &pipeline);
+
+        for format in [RecipeFormat::Json, RecipeFormat::Toml] {
+            let text = recipe.to_text(format).unwrap();
+            let parsed = Recipe::parse(&text, format).unwrap();
+            assert_eq!(parsed, recipe, "{}", text);
+            assert_eq!(parsed.pipeline().unwrap(), pipeline);
+        }
+
+        let json = recipe.to_text(RecipeFormat::Json).unwrap();
+        assert!(json.contains("\"sigma\": 1.5"), "{}", json);
+        assert!(json.contains("\"fill\": \"#FF0000FF\""), "{}", json);
+    }
+
+    #[test]
+    fn unrounded_number_test() {
+        let pipeline: Pipeline = "rotate angle=12.25
+blur sigma=1.55"
+            .parse()
+            .unwrap();
+        let recipe = Recipe::from_pipeline("test", &pipeline);
+        let json = recipe.to_text(RecipeFormat::Json).unwrap();
+        assert!(json.contains("\"angle\": 12.25"), "{}", json);
+        assert!(json.contains("\"sigma\": 1.55"), "{}", json);
+        assert_eq!(recipe.pipeline().unwrap(), pipeline);
+    }
+
+    #[test]
+    fn default_extension_test() {
+        let path = std::env::temp_dir().join("coffee_image_recipe_test");
+        let pipeline: Pipeline = "invert".parse().unwrap();
+        let saved = Recipe::from_pipeline("test", &pipeline)
+            .save(&path)
+            .unwrap();
+        assert_eq!(saved, path.with_extension("json"));
+        let loaded = Recipe::load(&saved).unwrap().pipeline();
+        let _ = fs::remove_file(&saved);
+        assert_eq!(loaded.unwrap(), pipeline);
+        assert_eq!(RecipeFormat::from_path(&path), Some(RecipeFormat::Json));
+        assert_eq!(RecipeFormat::from_path(Path::new("a.yaml")), None);
+    }
+
+    #[test]
+    fn handwritten_recipe_test() {
+        let toml = r#"
+            name = "soft"
+
+            [[steps]]
+            operation = "blur"
+            params = { sigma = 3 }
+
+            [[steps]]
+            operation = "invert"
+        "#;
+        let pipeline = Recipe::parse(toml, RecipeFormat::Toml)
+            .unwrap()
+            .pipeline()
+            .unwrap();
+        assert_eq!(pipeline.to_string(), "blur sigma=3\ninvert\n");
+
+        let unknown = r#"{"name": "x", "steps": [{"operation": "sharpen"}]}"#;
+        let result = Recipe::parse(unknown, RecipeFormat::Json)
+            .unwrap()
+            .pipeline();
+        assert!(matches!(result, Err(Error::UnknownOperation(_))));
+
+        let out_of_range =
+            r#"{"name": "x", "steps": [{"operation": "blur", "params": {"sigma": 99}}]}"#;
+        let result = Recipe::parse(out_of_range, RecipeFormat::Json)
+            .unwrap()
+            .pipeline();
+        assert!(matches!(result, Err(Error::InvalidParameter { .. })));
+
+        assert!(matches!(
+            Recipe::parse("{", RecipeFormat::Json),
+            Err(Error::InvalidFile(_))
+        ));
+    }
+
+    #[test]
+    fn preset_test() {
+        for preset in Preset::ALL {
+            assert_eq!(Preset::from_key(preset.key()), Some(preset));
+            assert!(!preset.pipeline().steps.is_empty());
+            assert!(preset.pipeline().operation().is_ok(), "{:?}", preset);
+        }
+    }
+}
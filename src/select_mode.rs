@@ -1,6 +1,17 @@
-use std::fmt::Display;
+use std::{fmt::Display, path::PathBuf};
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+use coffee_image::{
+    error::{Error, Input},
+    string_art::ascii::Ramp,
+};
+use image::Rgba;
+
+use crate::{
+    color_picker::{hex, parse_hex},
+    i18n::{tr, Text},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
 pub enum SelectMode {
     #[default]
     Gray,
@@ -8,36 +19,431 @@ pub enum SelectMode {
     HueRotate,
     Blur,
     ToAscii,
+    ToAsciiEdge,
+    FromAscii,
     Rotate,
     Add,
+    ChromaKey,
+    Threshold,
+    Resize,
+    MakeGif,
 }
 
 impl SelectMode {
-    pub const ALL: [SelectMode; 7] = [
+    pub const ALL: [SelectMode; 13] = [
         SelectMode::BitwiseNot,
         SelectMode::Gray,
         SelectMode::HueRotate,
         SelectMode::Blur,
         SelectMode::ToAscii,
+        SelectMode::ToAsciiEdge,
+        SelectMode::FromAscii,
         SelectMode::Rotate,
         SelectMode::Add,
+        SelectMode::ChromaKey,
+        SelectMode::Threshold,
+        SelectMode::Resize,
+        SelectMode::MakeGif,
     ];
+
+    //表示名
+    pub fn text(&self) -> Text {
+        match self {
+            SelectMode::Gray => Text::Gray,
+            SelectMode::BitwiseNot => Text::Invert,
+            SelectMode::HueRotate => Text::HueRotate,
+            SelectMode::Blur => Text::Blur,
+            SelectMode::ToAscii => Text::ToAscii,
+            SelectMode::ToAsciiEdge => Text::ToAsciiEdge,
+            SelectMode::FromAscii => Text::FromAscii,
+            SelectMode::Rotate => Text::Rotate,
+            SelectMode::Add => Text::Add,
+            SelectMode::ChromaKey => Text::ChromaKey,
+            SelectMode::Threshold => Text::Threshold,
+            SelectMode::Resize => Text::Resize,
+            SelectMode::MakeGif => Text::MakeGif,
+        }
+    }
+
+    //パイプラインとコマンドラインでの名前
+    pub fn key(&self) -> &'static str {
+        match self {
+            SelectMode::Gray => "gray",
+            SelectMode::BitwiseNot => "invert",
+            SelectMode::HueRotate => "hue_rotate",
+            SelectMode::Blur => "blur",
+            SelectMode::ToAscii => "to_ascii",
+            SelectMode::ToAsciiEdge => "to_ascii_edge",
+            SelectMode::FromAscii => "from_ascii",
+            SelectMode::Rotate => "rotate",
+            SelectMode::Add => "add",
+            SelectMode::ChromaKey => "chroma_key",
+            SelectMode::Threshold => "threshold",
+            SelectMode::Resize => "resize",
+            SelectMode::MakeGif => "make_gif",
+        }
+    }
+
+    pub fn from_key(key: &str) -> Option<SelectMode> {
+        SelectMode::ALL.into_iter().find(|mode| mode.key() == key)
+    }
+
+    //画像から画像への変換で、パイプラインに並べられるもの
+    pub fn is_pipeline_step(&self) -> bool {
+        match self {
+            SelectMode::Gray
+            | SelectMode::BitwiseNot
+            | SelectMode::HueRotate
+            | SelectMode::Blur
+            | SelectMode::Rotate
+            | SelectMode::Add
+            | SelectMode::ChromaKey
+            | SelectMode::Threshold
+            | SelectMode::Resize => true,
+            SelectMode::ToAscii
+            | SelectMode::ToAsciiEdge
+            | SelectMode::FromAscii
+            | SelectMode::MakeGif => false,
+        }
+    }
+
+    //モードごとのパラメータ GUIの入力欄、値の確かめ、パイプラインの読み書きはすべてこれを見る
+    pub fn params(&self) -> &'static [ParamSpec] {
+        match self {
+            SelectMode::HueRotate => &HUE_ROTATE_PARAMS,
+            SelectMode::Blur => &BLUR_PARAMS,
+            SelectMode::ToAscii | SelectMode::ToAsciiEdge => &TO_ASCII_PARAMS,
+            SelectMode::FromAscii => &FROM_ASCII_PARAMS,
+            SelectMode::Rotate => &ROTATE_PARAMS,
+            SelectMode::Add => &ADD_PARAMS,
+            SelectMode::ChromaKey => &CHROMA_KEY_PARAMS,
+            SelectMode::Threshold => &THRESHOLD_PARAMS,
+            SelectMode::Resize => &RESIZE_PARAMS,
+            SelectMode::Gray | SelectMode::BitwiseNot | SelectMode::MakeGif => &[],
+        }
+    }
+
+    pub fn param(&self, key: &str) -> Option<&'static ParamSpec> {
+        self.params().iter().find(|spec| spec.key == key)
+    }
+}
+
+const HUE_ROTATE_PARAMS: [ParamSpec; 1] = [ParamSpec {
+    key: "degrees",
+    name: Text::HueAngle,
+    kind: ParamKind::Int {
+        min: 0,
+        max: 360,
+        default: 180,
+    },
+}];
+
+const BLUR_PARAMS: [ParamSpec; 1] = [ParamSpec {
+    key: "sigma",
+    name: Text::BlurSigma,
+    kind: ParamKind::Float {
+        min: 0.1,
+        max: 20.0,
+        step: 0.1,
+        default: 2.0,
+    },
+}];
+
+const TO_ASCII_PARAMS: [ParamSpec; 2] = [
+    ParamSpec {
+        key: "scale",
+        name: Text::CellWidth,
+        kind: ParamKind::Int {
+            min: 1,
+            max: 32,
+            default: 4,
+        },
+    },
+    ParamSpec {
+        key: "ramp",
+        name: Text::Glyphs,
+        kind: ParamKind::Ramp,
+    },
+];
+
+const FROM_ASCII_PARAMS: [ParamSpec; 1] = [ParamSpec {
+    key: "cell_width",
+    name: Text::CellWidth,
+    kind: ParamKind::Int {
+        min: 1,
+        max: 32,
+        default: 8,
+    },
+}];
+
+const ROTATE_PARAMS: [ParamSpec; 2] = [
+    ParamSpec {
+        key: "angle",
+        name: Text::RotateAngle,
+        kind: ParamKind::Float {
+            min: -180.0,
+            max: 180.0,
+            step: 1.0,
+            default: 45.0,
+        },
+    },
+    ParamSpec {
+        key: "fill",
+        name: Text::FillColor,
+        kind: ParamKind::Color { required: false },
+    },
+];
+
+const ADD_PARAMS: [ParamSpec; 1] = [ParamSpec {
+    key: "image",
+    name: Text::InputSecondImage,
+    kind: ParamKind::Image,
+}];
+
+const CHROMA_KEY_PARAMS: [ParamSpec; 2] = [
+    ParamSpec {
+        key: "key",
+        name: Text::KeyColor,
+        kind: ParamKind::Color { required: true },
+    },
+    ParamSpec {
+        key: "tolerance",
+        name: Text::Tolerance,
+        kind: ParamKind::Float {
+            min: 0.0,
+            max: 100.0,
+            step: 1.0,
+            default: 10.0,
+        },
+    },
+];
+
+const THRESHOLD_PARAMS: [ParamSpec; 1] = [ParamSpec {
+    key: "level",
+    name: Text::Level,
+    kind: ParamKind::Float {
+        min: 0.0,
+        max: 100.0,
+        step: 1.0,
+        default: 50.0,
+    },
+}];
+
+const RESIZE_PARAMS: [ParamSpec; 1] = [ParamSpec {
+    key: "max_side",
+    name: Text::MaxSide,
+    kind: ParamKind::Int {
+        min: 16,
+        max: 4096,
+        default: 1024,
+    },
+}];
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ParamSpec {
+    pub key: &'static str,
+    pub name: Text,
+    pub kind: ParamKind,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ParamKind {
+    Float {
+        min: f32,
+        max: f32,
+        step: f32,
+        default: f32,
+    },
+    Int {
+        min: i32,
+        max: i32,
+        default: i32,
+    },
+    Ramp,
+    //GUIではスポイトで取る requiredでなければ未設定のままでよい
+    Color {
+        required: bool,
+    },
+    //2枚目の画像
+    Image,
+}
+
+//ParamSpecと同じ順に並べる 色と画像はまだ選ばれていなければNone
+#[derive(Debug, Clone, PartialEq)]
+pub enum ParamValue {
+    Float(f32),
+    Int(i32),
+    Ramp(Ramp),
+    Color(Option<Rgba<u8>>),
+    Image(Option<PathBuf>),
+}
+
+impl ParamSpec {
+    pub fn default_value(&self) -> ParamValue {
+        match self.kind {
+            ParamKind::Float { default, .. } => ParamValue::Float(default),
+            ParamKind::Int { default, .. } => ParamValue::Int(default),
+            ParamKind::Ramp => ParamValue::Ramp(Ramp::default()),
+            ParamKind::Color { .. } => ParamValue::Color(None),
+            ParamKind::Image => ParamValue::Image(None),
+        }
+    }
+
+    //スライダーの範囲と刻み 数でないものはNone
+    pub fn range(&self) -> Option<(f32, f32, f32)> {
+        match self.kind {
+            ParamKind::Float { min, max, step, .. } => Some((min, max, step)),
+            ParamKind::Int { min, max, .. } => Some((min as f32, max as f32, 1.0)),
+            ParamKind::Ramp | ParamKind::Color { .. } | ParamKind::Image => None,
+        }
+    }
+
+    //スライダーや数値入力の値を範囲に収めてstepの倍数にそろえる
+    pub fn number(&self, value: f32) -> Option<ParamValue> {
+        let (min, max, step) = self.range()?;
+        let value = (min + ((value - min) / step).round() * step).clamp(min, max);
+        Some(match self.kind {
+            ParamKind::Int { .. } => ParamValue::Int(value as i32),
+            _ => ParamValue::Float(value),
+        })
+    }
+
+    //範囲外や必須の値がないときはErr
+    pub fn check(&self, value: &ParamValue) -> Result<(), Error> {
+        let in_range = match (self.kind, value) {
+            (ParamKind::Float { min, max, .. }, ParamValue::Float(value)) => {
+                (min..=max).contains(value)
+            }
+            (ParamKind::Int { min, max, .. }, ParamValue::Int(value)) => {
+                (min..=max).contains(value)
+            }
+            (ParamKind::Ramp, ParamValue::Ramp(_)) => true,
+            (ParamKind::Color { required: true }, ParamValue::Color(None)) => {
+                return Err(Error::MissingInput(Input::Color))
+            }
+            (ParamKind::Color { .. }, ParamValue::Color(_)) => true,
+            (ParamKind::Image, ParamValue::Image(None)) => {
+                return Err(Error::MissingInput(Input::SecondImage))
+            }
+            (ParamKind::Image, ParamValue::Image(Some(_))) => true,
+            _ => false,
+        };
+        if in_range {
+            Ok(())
+        } else {
+            Err(self.invalid(&self.format(value)))
+        }
+    }
+
+    //パイプラインの文字列から読む 範囲も確かめる
+    pub fn parse(&self, text: &str) -> Result<ParamValue, Error> {
+        let value = match self.kind {
+            ParamKind::Float { .. } => text.parse().ok().map(ParamValue::Float),
+            ParamKind::Int { .. } => text.parse().ok().map(ParamValue::Int),
+            ParamKind::Ramp => Ramp::ALL
+                .into_iter()
+                .find(|ramp| ramp.to_string().eq_ignore_ascii_case(text))
+                .map(ParamValue::Ramp),
+            ParamKind::Color { .. } => parse_hex(text).map(|color| ParamValue::Color(Some(color))),
+            ParamKind::Image => Some(ParamValue::Image(Some(PathBuf::from(text)))),
+        }
+        .ok_or_else(|| self.invalid(text))?;
+        self.check(&value).map_err(|error| match error {
+            Error::InvalidParameter { .. } => self.invalid(text),
+            error => error,
+        })?;
+        Ok(value)
+    }
+
+    //パイプラインの文字列やレシピに書く形 parseで同じ値に戻る
+    pub fn format(&self, value: &ParamValue) -> String {
+        match value {
+            ParamValue::Float(value) => value.to_string(),
+            value => self.label(value),
+        }
+    }
+
+    //数値入力欄に出す形 小数はstepに合わせて丸める
+    pub fn label(&self, value: &ParamValue) -> String {
+        match (self.kind, value) {
+            (ParamKind::Float { step, .. }, ParamValue::Float(value)) if step >= 1.0 => {
+                format!("{:.0}", value)
+            }
+            (_, ParamValue::Float(value)) => format!("{:.1}", value),
+            (_, ParamValue::Int(value)) => value.to_string(),
+            (_, ParamValue::Ramp(ramp)) => ramp.to_string().to_lowercase(),
+            (_, ParamValue::Color(Some(color))) => hex(*color),
+            (_, ParamValue::Image(Some(path))) => path.display().to_string(),
+            (_, ParamValue::Color(None) | ParamValue::Image(None)) => "".to_string(),
+        }
+    }
+
+    fn invalid(&self, value: &str) -> Error {
+        Error::InvalidParameter {
+            name: self.key.to_string(),
+            value: value.to_string(),
+        }
+    }
+}
+
+//パラメータのキーから表示名を探す 同じキーのパラメータは同じ意味
+pub fn param_name(key: &str) -> Option<Text> {
+    SelectMode::ALL
+        .iter()
+        .flat_map(SelectMode::params)
+        .find(|spec| spec.key == key)
+        .map(|spec| spec.name)
 }
 
 impl Display for SelectMode {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(
-            f,
-            "{}",
-            match self {
-                SelectMode::Gray => "Gray",
-                SelectMode::BitwiseNot => "Invert",
-                SelectMode::HueRotate => "HueRotate",
-                SelectMode::Blur => "Blur",
-                SelectMode::ToAscii => "ToAscii",
-                SelectMode::Rotate => "Rotate",
-                SelectMode::Add => "Add",
+        write!(f, "{}", tr(self.text()))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn param_schema_test() {
+        for mode in SelectMode::ALL {
+            assert_eq!(SelectMode::from_key(mode.key()), Some(mode));
+            for spec in mode.params() {
+                let value = spec.default_value();
+                if spec.range().is_some() {
+                    assert!(spec.check(&value).is_ok(), "{:?} {}", mode, spec.key);
+                }
+                if let Some(number) = spec.range().and_then(|(min, _, _)| spec.number(min)) {
+                    assert_eq!(spec.parse(&spec.format(&number)).ok(), Some(number));
+                }
             }
-        )
+        }
+
+        let blur = &SelectMode::Blur.params()[0];
+        assert_eq!(blur.number(100.0), Some(ParamValue::Float(20.0)));
+        assert_eq!(blur.number(-1.0), Some(ParamValue::Float(0.1)));
+        assert_eq!(blur.label(&ParamValue::Float(2.34)), "2.3");
+        assert_eq!(blur.format(&ParamValue::Float(2.34)), "2.34");
+        assert!(matches!(
+            blur.parse("25"),
+            Err(Error::InvalidParameter { .. })
+        ));
+        assert!(matches!(
+            blur.parse("abc"),
+            Err(Error::InvalidParameter { .. })
+        ));
+
+        let hue = &SelectMode::HueRotate.params()[0];
+        assert_eq!(hue.number(12.6), Some(ParamValue::Int(13)));
+
+        let key = &SelectMode::ChromaKey.params()[0];
+        assert!(matches!(
+            key.check(&key.default_value()),
+            Err(Error::MissingInput(Input::Color))
+        ));
+        let green = ParamValue::Color(Some(Rgba([0, 255, 0, 255])));
+        assert_eq!(key.parse("#00FF00").ok(), Some(green.clone()));
+        assert_eq!(key.format(&green), "#00FF00FF");
     }
 }
@@ -0,0 +1,96 @@
+use std::time::{Duration, Instant};
+
+use iced::Command;
+
+use coffee_image::{
+    convert::image_wrap::ImageConverter, error::Error, io::coffee_image_io::estimate_size,
+};
+
+use crate::{convert_job::next_job_id, Message};
+
+//設定が止まってからこれだけ待ってエンコードする
+const ESTIMATE_DELAY: Duration = Duration::from_millis(300);
+
+//保存後のサイズの見積もり 古い設定の結果はidで捨てる
+#[derive(Debug, Clone, Default)]
+pub struct SizeEstimate {
+    //最後に設定が変わった時刻
+    requested: Option<Instant>,
+    //実行中の見積もり
+    id: Option<u64>,
+    size: Option<usize>,
+}
+
+impl SizeEstimate {
+    //前の見積もりは今の設定のものではないので消す
+    pub fn request(&mut self, now: Instant) {
+        self.requested = Some(now);
+        self.id = None;
+        self.size = None;
+    }
+
+    pub fn is_pending(&self) -> bool {
+        self.requested.is_some()
+    }
+
+    //ESTIMATE_DELAYの間、設定が変わらなければtrue
+    pub fn is_due(&mut self, now: Instant) -> bool {
+        match self.requested {
+            Some(requested) if now.duration_since(requested) >= ESTIMATE_DELAY => {
+                self.requested = None;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    pub fn size(&self) -> Option<usize> {
+        self.size
+    }
+
+    pub fn clear(&mut self) {
+        *self = Self::default();
+    }
+
+    pub fn start(&mut self, image_converter: ImageConverter) -> Command<Message> {
+        let id = next_job_id();
+        self.id = Some(id);
+        let save_format = image_converter.save_format;
+        Command::perform(estimate_size(image_converter, save_format), move |result| {
+            Message::SizeEstimated(id, result)
+        })
+    }
+
+    pub fn finish(&mut self, id: u64, result: Result<usize, Error>) {
+        if self.id != Some(id) {
+            return;
+        }
+        self.id = None;
+        self.size = result.ok();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn stale_estimate_test() {
+        let mut estimate = SizeEstimate::default();
+        let now = Instant::now();
+        estimate.request(now);
+        assert!(!estimate.is_due(now + ESTIMATE_DELAY / 2));
+        assert!(estimate.is_due(now + ESTIMATE_DELAY));
+        assert!(!estimate.is_pending());
+
+        //見積もり中に設定が変わったら前の結果は使わない
+        estimate.id = Some(1);
+        estimate.request(now);
+        estimate.finish(1, Ok(100));
+        assert_eq!(estimate.size(), None);
+
+        estimate.id = Some(2);
+        estimate.finish(2, Ok(200));
+        assert_eq!(estimate.size(), Some(200));
+    }
+}
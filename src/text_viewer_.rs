@@ -1,44 +1,135 @@
+
 use iced::{
-    widget::{container, text, button, row, column},
+    widget::{button, column, container, horizontal_space, pick_list, row, scrollable, text},
     Length,
 };
 
 use std::path::PathBuf;
 
-use crate::{coffee_image::{error::Error, io::text::TextFile}, Message};
+use coffee_image::{
+    error::Error, io::text::TextFile, string_art::ascii::Ramp, text_format::TextFormat,
+};
+
+use crate::{
+    i18n::{error_message, tr, Text},
+    select_mode::SelectMode,
+    Message,
+};
+
+const FONT_SIZE_RANGE: (u16, u16) = (2, 32);
+const DEFAULT_FONT_SIZE: u16 = 8;
 
 #[derive(Debug, Clone)]
 pub struct TextViewerState {
     content_or_error: Result<String, Error>,
     text_path: Option<PathBuf>,
+    pub mode: SelectMode,
+    pub scale: u32,
+    pub ramp: Ramp,
+    pub font_size: u16,
+    pub text_format: TextFormat,
 }
 
 impl TextViewerState {
-    pub fn new(text_file:TextFile) -> Self {
-        let result = text_file.read_text_file();
+    pub fn new(
+        text_file: Result<TextFile, Error>,
+        mode: SelectMode,
+        scale: u32,
+        ramp: Ramp,
+    ) -> Self {
+        let content_or_error = text_file
+            .as_ref()
+            .map_err(Clone::clone)
+            .and_then(TextFile::read_text_file);
+        Self {
+            content_or_error,
+            text_path: text_file.ok().map(|text_file| text_file.get_result_text_file()),
+            mode,
+            scale,
+            ramp,
+            font_size: DEFAULT_FONT_SIZE,
+            text_format: TextFormat::default(),
+        }
+    }
+
+    //再変換しても表示設定は引き継ぐ
+    pub fn rerun(&self, text_file: Result<TextFile, Error>) -> Self {
         Self {
-            content_or_error: result,
-            text_path: Some(text_file.get_result_text_file()),
+            font_size: self.font_size,
+            text_format: self.text_format,
+            ..Self::new(text_file, self.mode, self.scale, self.ramp)
         }
     }
+
+    pub fn content(&self) -> Option<&String> {
+        self.content_or_error.as_ref().ok()
+    }
+
     pub fn view(&self) -> iced::Element<'_, Message> {
-        let change_view_button=button("Return Main").on_press(Message::ViewChanged(crate::Views::Image));
-        let controll = row![change_view_button];
-        
+        let change_view_button =
+            button(tr(Text::ReturnMain)).on_press(Message::ViewChanged(crate::Views::Image));
+
+        let (min_size, max_size) = FONT_SIZE_RANGE;
+        let font_size_controll = row![
+            button("A-").on_press(Message::TextFontSizeChanged(
+                self.font_size.saturating_sub(1).max(min_size)
+            )),
+            text(format!("{}px", self.font_size)),
+            button("A+").on_press(Message::TextFontSizeChanged(
+                (self.font_size + 1).min(max_size)
+            )),
+        ]
+        .spacing(5)
+        .align_items(iced::Alignment::Center);
+
+        let scale_controll = row![
+            button("-").on_press(Message::TextScaleChanged(self.scale.saturating_sub(1).max(1))),
+            text(format!("{} {}", tr(Text::Scale), self.scale)),
+            button("+").on_press(Message::TextScaleChanged(self.scale + 1)),
+            button(tr(Text::Rerun)).on_press(Message::TextRerun),
+        ]
+        .spacing(5)
+        .align_items(iced::Alignment::Center);
+
+        let text_format_list = pick_list(
+            &TextFormat::ALL[..],
+            Some(self.text_format),
+            Message::TextFormatSelected,
+        );
+        let save_button = crate::components::button_component(
+            tr(Text::SaveAs),
+            self.content().is_some().then_some(Message::TextSave),
+        );
+
+        let controll = row![
+            change_view_button,
+            horizontal_space(Length::Fill),
+            font_size_controll,
+            scale_controll,
+            text_format_list,
+            save_button
+        ]
+        .spacing(20)
+        .padding(10);
+
         let content_or_error_text = match &self.content_or_error {
-            Ok(content) => text(content).size(8),
-            Err(error) => {
-                let error = format!("{:?}", error);
-                text(error)
-            }
+            Ok(content) => text(content).size(self.font_size),
+            Err(error) => text(error_message(error)),
         };
 
-        container(column![controll,content_or_error_text])
-            .width(Length::Fill)
-            .height(Length::Fill)
-            .center_x()
-            .center_y()
-            .into()
+        container(column![
+            controll,
+            scrollable(content_or_error_text)
+                .width(Length::Fill)
+                .direction(scrollable::Direction::Both {
+                    vertical: scrollable::Properties::default(),
+                    horizontal: scrollable::Properties::default(),
+                })
+        ])
+        .width(Length::Fill)
+        .height(Length::Fill)
+        .center_x()
+        .center_y()
+        .into()
     }
 }
-
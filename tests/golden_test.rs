@@ -0,0 +1,217 @@
+//合成した入力画像に全部の変換をかけ、tests/golden/の参照画像と比べる
+//参照を作り直すとき: UPDATE_GOLDEN=1 cargo test --test golden_test
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+
+use image::{DynamicImage, GenericImageView, ImageBuffer, Rgb, Rgba};
+
+use coffee_image::convert::image_wrap::{
+    crop_image, save_temp_image, threshold_image, ImageConverter,
+};
+use coffee_image::convert::operation::Operation;
+use coffee_image::io::temp_session::SessionGuard;
+use coffee_image::string_art::ascii::Ramp;
+
+const WIDTH: u32 = 32;
+const HEIGHT: u32 = 24;
+//16bitに直した値での許容量
+const MAX_DIFF: u16 = 2 * 257;
+const MIN_PSNR: f64 = 50.0;
+
+fn golden_dir() -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests").join("golden")
+}
+
+fn is_update_mode() -> bool {
+    env::var_os("UPDATE_GOLDEN").is_some()
+}
+
+//グラデーションの上に不透明な四角と半透明な帯を置く 色の型ごとに同じ模様
+fn pattern(x: u32, y: u32) -> [f32; 4] {
+    let in_square = (8..20).contains(&x) && (6..16).contains(&y);
+    let in_band = (18..22).contains(&y);
+    let checker = ((x / 2 + y / 2) % 2) as f32 * 0.25;
+
+    let red = if in_square { 1.0 } else { x as f32 / (WIDTH - 1) as f32 };
+    let green = if in_square { 0.1 } else { (y as f32 / (HEIGHT - 1) as f32 * 0.75 + checker).min(1.0) };
+    let blue = if in_band { 0.9 } else { 0.3 };
+    let alpha = if in_square { 1.0 } else if in_band { 0.5 } else if x < 4 { 0.0 } else { 0.8 };
+    [red, green, blue, alpha]
+}
+
+fn fixtures() -> Vec<(&'static str, DynamicImage)> {
+    let to_u8 = |value: f32| (value * 255.0).round() as u8;
+    let to_u16 = |value: f32| (value * 65535.0).round() as u16;
+
+    let rgb8 = ImageBuffer::from_fn(WIDTH, HEIGHT, |x, y| {
+        let [r, g, b, _] = pattern(x, y);
+        Rgb([to_u8(r), to_u8(g), to_u8(b)])
+    });
+    let rgba8 = ImageBuffer::from_fn(WIDTH, HEIGHT, |x, y| Rgba(pattern(x, y).map(to_u8)));
+    let rgb16 = ImageBuffer::from_fn(WIDTH, HEIGHT, |x, y| {
+        let [r, g, b, _] = pattern(x, y);
+        Rgb([to_u16(r), to_u16(g), to_u16(b)])
+    });
+    let rgba32f = ImageBuffer::from_fn(WIDTH, HEIGHT, |x, y| Rgba(pattern(x, y)));
+
+    vec![
+        ("rgb8", DynamicImage::ImageRgb8(rgb8)),
+        ("rgba8", DynamicImage::ImageRgba8(rgba8)),
+        ("rgb16", DynamicImage::ImageRgb16(rgb16)),
+        ("rgba32f", DynamicImage::ImageRgba32F(rgba32f)),
+    ]
+}
+
+//(名前, 結果)
+fn run_operations(image: &DynamicImage) -> Vec<(&'static str, DynamicImage)> {
+    let path = save_temp_image(image).unwrap();
+    let mut ic = ImageConverter::new();
+    ic.set_image_path(path.clone());
+
+    let operations = [
+        ("gray", Operation::Gray),
+        ("bitwise_not", Operation::BitwiseNot),
+        ("hue_rotate", Operation::HueRotate(90)),
+        ("blur", Operation::Blur(2.0)),
+        ("rotate", Operation::Rotate(45.0)),
+        ("threshold", Operation::Threshold(0.5)),
+        ("rotate_fill", Operation::RotateFill(30.0, Rgba([255, 0, 128, 255]))),
+        ("chroma_key", Operation::ChromaKey(Rgba([255, 26, 77, 255]), 0.2)),
+        ("resize", Operation::Resize(20)),
+        (
+            "sequence",
+            Operation::Sequence(vec![Operation::Resize(16), Operation::Gray, Operation::Blur(1.0)]),
+        ),
+        ("add", Operation::Add(path)),
+    ];
+    let mut results: Vec<_> = operations
+        .into_iter()
+        .map(|(name, operation)| (name, operation.apply(image).unwrap()))
+        .collect();
+
+    let mask = threshold_image(&image.grayscale());
+    results.push(("bitwise_and", ic.bitwise_and(image, &mask)));
+    results.push(("crop", crop_image(image, 20, 10)));
+    results
+}
+
+fn run_ascii(image: &DynamicImage) -> Vec<(&'static str, String)> {
+    let mut ic = ImageConverter::new();
+    ic.set_image_path(save_temp_image(image).unwrap());
+
+    let ascii = ic.clone().ascii_art(2, Ramp::Standard).unwrap();
+    let edge = ic.ascii_edge_art(2, Ramp::Standard).unwrap();
+    vec![
+        ("ascii_art", ascii.read_text_file().unwrap()),
+        ("ascii_edge_art", edge.read_text_file().unwrap()),
+    ]
+}
+
+//(チャンネルの最大の差, PSNR) 16bitのRGBAに直して比べる
+fn compare(actual: &DynamicImage, expected: &DynamicImage) -> (u16, f64) {
+    let (actual, expected) = (actual.to_rgba16(), expected.to_rgba16());
+    let mut max_diff = 0;
+    let mut squared_sum = 0.0;
+    for (a, e) in actual.as_raw().iter().zip(expected.as_raw()) {
+        let diff = a.abs_diff(*e);
+        max_diff = max_diff.max(diff);
+        squared_sum += (diff as f64 / 65535.0).powi(2);
+    }
+
+    let mse = squared_sum / actual.as_raw().len().max(1) as f64;
+    let psnr = if mse == 0.0 { f64::INFINITY } else { 10.0 * (1.0 / mse).log10() };
+    (max_diff, psnr)
+}
+
+fn is_float(image: &DynamicImage) -> bool {
+    matches!(image, DynamicImage::ImageRgb32F(_) | DynamicImage::ImageRgba32F(_))
+}
+
+//色の型も比べられるように、pngで保存できる型はそのまま保存する
+fn save_reference(image: &DynamicImage, path: &PathBuf) {
+    if is_float(image) {
+        DynamicImage::ImageRgba16(image.to_rgba16()).save(path).unwrap();
+    } else {
+        image.save(path).unwrap();
+    }
+}
+
+fn check_image(name: &str, actual: &DynamicImage) -> Result<(), String> {
+    let path = golden_dir().join(format!("{}.png", name));
+    if is_update_mode() {
+        save_reference(actual, &path);
+        return Ok(());
+    }
+
+    let expected = image::open(&path)
+        .map_err(|error| format!("{}: {} (UPDATE_GOLDEN=1で作り直す)", path.display(), error))?;
+    if actual.dimensions() != expected.dimensions() {
+        return Err(format!(
+            "{}: size {:?} != {:?}",
+            name,
+            actual.dimensions(),
+            expected.dimensions()
+        ));
+    }
+
+    //pngに保存できない32bit floatは16bitで比べる
+    if !is_float(actual) && actual.color() != expected.color() {
+        return Err(format!("{}: color {:?} != {:?}", name, actual.color(), expected.color()));
+    }
+
+    let (max_diff, psnr) = compare(actual, &expected);
+    if max_diff > MAX_DIFF || psnr < MIN_PSNR {
+        return Err(format!("{}: max diff {} psnr {:.1}dB", name, max_diff, psnr));
+    }
+    Ok(())
+}
+
+fn check_text(name: &str, actual: &str) -> Result<(), String> {
+    let path = golden_dir().join(format!("{}.txt", name));
+    if is_update_mode() {
+        fs::write(&path, actual).unwrap();
+        return Ok(());
+    }
+
+    let expected = fs::read_to_string(&path)
+        .map_err(|error| format!("{}: {} (UPDATE_GOLDEN=1で作り直す)", path.display(), error))?;
+    //checkout時の改行コードの違いは無視する
+    if actual.lines().ne(expected.lines()) {
+        return Err(format!("{}: text differs", name));
+    }
+    Ok(())
+}
+
+#[test]
+fn golden_images_test() {
+    //変換に使った一時ファイルは失敗しても消す
+    let _session = SessionGuard::default();
+    fs::create_dir_all(golden_dir()).unwrap();
+    let mut failures = Vec::new();
+
+    for (color, image) in fixtures() {
+        for (operation, result) in run_operations(&image) {
+            let name = format!("{}_{}", operation, color);
+            failures.extend(check_image(&name, &result).err());
+        }
+        for (operation, text) in run_ascii(&image) {
+            let name = format!("{}_{}", operation, color);
+            failures.extend(check_text(&name, &text).err());
+        }
+    }
+
+    assert!(failures.is_empty(), "golden mismatch:\n{}", failures.join("\n"));
+}
+
+#[test]
+fn compare_test() {
+    let image = fixtures().remove(1).1;
+    assert_eq!(compare(&image, &image), (0, f64::INFINITY));
+
+    let mut changed = image.to_rgba8();
+    changed.get_pixel_mut(0, 0)[0] ^= 0xff;
+    let (max_diff, psnr) = compare(&DynamicImage::ImageRgba8(changed), &image);
+    assert!(max_diff > MAX_DIFF);
+    assert!(psnr.is_finite());
+}